@@ -381,6 +381,12 @@ pub fn convert_airport() {
                 }
             }
         }
+        let length_of = |seg: &String| length.iter().find_map(|(s, l)| (s == seg).then(|| *l)).unwrap();
+        let mut plan_mapping = PlanMapping {
+            time_scale,
+            ..Default::default()
+        };
+
         let push_mode = "Push";
         let move_mode = "Move";
         let park_mode = "Parked";
@@ -397,6 +403,12 @@ pub fn convert_airport() {
                 continue;
             };
 
+            let airplane_type = has_type.iter().find_map(|(a, t)| (a == airplane).then(|| t)).unwrap();
+            let airplane_engines = engines.iter().find_map(|(a, e)| (a == airplane).then(|| *e)).unwrap_or(0.);
+            let action = |name: &str, args: Vec<&String>, duration: f64| {
+                (name.to_string(), args.into_iter().cloned().collect::<Vec<_>>(), duration)
+            };
+
             let mut nodes = HashSet::new();
             let mut edges = Vec::new();
 
@@ -411,7 +423,15 @@ pub fn convert_airport() {
                 let to_name = format!("{}_{}_{}", push_mode, to_seg, to_dir);
                 nodes.insert((push_mode, from_seg, from_dir));
                 nodes.insert((push_mode, to_seg, to_dir));
-                edges.push((from_name, to_name));
+                edges.push((
+                    from_name,
+                    to_name,
+                    action(
+                        "pushback",
+                        vec![airplane, airplane_type, from_dir, from_seg, to_seg, to_dir],
+                        length_of(from_seg) / 5.0,
+                    ),
+                ));
             }
             // PUSH->MOVE edges
             for seg in segments.iter() {
@@ -420,7 +440,7 @@ pub fn convert_airport() {
                     let to_name = format!("{}_{}_{}", move_mode, seg, dir);
                     nodes.insert((push_mode, seg, dir));
                     nodes.insert((move_mode, seg, dir));
-                    edges.push((from_name, to_name));
+                    edges.push((from_name, to_name, action("startup", vec![airplane], 60. * airplane_engines)));
                 }
             }
             // MOVE EDGES
@@ -434,7 +454,15 @@ pub fn convert_airport() {
                 let to_name = format!("{}_{}_{}", move_mode, to_seg, to_dir);
                 nodes.insert((move_mode, from_seg, from_dir));
                 nodes.insert((move_mode, to_seg, to_dir));
-                edges.push((from_name, to_name));
+                edges.push((
+                    from_name,
+                    to_name,
+                    action(
+                        "move",
+                        vec![airplane, airplane_type, from_dir, from_seg, to_seg, to_dir],
+                        length_of(from_seg) / 30.0,
+                    ),
+                ));
             }
             // PARK EDGES
             for seg in segments.iter() {
@@ -443,7 +471,11 @@ pub fn convert_airport() {
                     let to_name = format!("{}_{}", park_mode, seg);
                     nodes.insert((move_mode, seg, dir));
                     nodes.insert((park_mode, seg, dir));
-                    edges.push((from_name, to_name));
+                    edges.push((
+                        from_name,
+                        to_name,
+                        action("park", vec![airplane, airplane_type, seg, dir], 40.0),
+                    ));
                 }
             }
             // TAKEOFF EDGES
@@ -452,7 +484,7 @@ pub fn convert_airport() {
                 let to_name = format!("{}_{}_{}", airborne_mode, seg, dir);
                 nodes.insert((move_mode, seg, dir));
                 nodes.insert((airborne_mode, seg, dir));
-                edges.push((from_name, to_name));
+                edges.push((from_name, to_name, action("takeoff", vec![airplane, seg, dir], 30.0)));
             }
 
             let mut values = Vec::new();
@@ -514,7 +546,15 @@ pub fn convert_airport() {
                 })
             }
 
-            for (from, to) in edges {
+            for (from, to, (action, args, duration)) in edges {
+                plan_mapping.actions.push(ActionMapping {
+                    timeline: airplane.clone(),
+                    value: format!("{}->{}", from, to),
+                    action,
+                    args: args.into_iter().map(PlanArg::Object).collect(),
+                    duration,
+                });
+
                 let mut conditions = vec![
                     Condition {
                         value: from.clone(),
@@ -670,5 +710,11 @@ pub fn convert_airport() {
         };
         let json = serde_json::to_string(&problem).unwrap();
         std::fs::write(&format!("airport_{}.json", file.file_name().to_str().unwrap()), &json).unwrap();
+        let json = serde_json::to_string(&plan_mapping).unwrap();
+        std::fs::write(
+            &format!("airport_{}.actions.json", file.file_name().to_str().unwrap()),
+            &json,
+        )
+        .unwrap();
     }
 }
@@ -1,9 +1,29 @@
 mod airport;
 mod pipesworld;
+mod plan;
 mod problem;
 mod satellite;
 
+/// Without arguments, convert all the instances. With `plan <actions.json>
+/// <solution.json>`, write the solution of a converted problem as a PDDL plan. The
+/// mapping from tokens to actions is written next to each converted problem as
+/// `<name>.actions.json`. A Pipesworld token moves a pipe in `1/speed`, so the
+/// actions of its plans may overlap where PDDL needs them one after the other.
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() == 4 && args[1] == "plan" {
+        let mapping: problem::PlanMapping = serde_json::from_str(&std::fs::read_to_string(&args[2]).unwrap()).unwrap();
+        let solution: problem::Solution = serde_json::from_str(&std::fs::read_to_string(&args[3]).unwrap()).unwrap();
+        match plan::write_plan(&solution, &mapping) {
+            Ok(plan) => print!("{}", plan),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     airport::convert_airport();
     satellite::convert_satellites();
     pipesworld::convert_pipesworld_notankage_temporal_deadlines();
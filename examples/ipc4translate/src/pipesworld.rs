@@ -303,6 +303,35 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                 );
             }
         }
+
+        // The batches moved by an action are the timelines of the tokens, or are
+        // found through the conditions on the tokens of the batch they replace.
+        let mut plan_mapping = PlanMapping {
+            time_scale: 1000.0,
+            object_properties: vec![(
+                "product".to_string(),
+                is_product_map
+                    .iter()
+                    .map(|(b, p)| (b.to_string(), p.to_string()))
+                    .collect(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut add_action = |timeline: &str, value: String, action: &str, args: Vec<PlanArg>, duration: f64| {
+            plan_mapping.actions.push(ActionMapping {
+                timeline: timeline.to_string(),
+                value,
+                action: action.to_string(),
+                args,
+                duration,
+            })
+        };
+        let object = |name: &String| PlanArg::Object(name.clone());
+        let linked = |value: &String| PlanArg::Linked(vec![value.clone()]);
+        let linked_product = |value: &String| PlanArg::LinkedProperty(vec![value.clone()], "product".to_string());
+
         for batch in batch_atoms.iter() {
             let tl_name = batch.clone();
 
@@ -471,6 +500,46 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                     let part2 = format!("{}_part{}", pipe, i + 1);
                     let pipe_speed = speed.iter().find_map(|(p, s)| (p == *pipe).then(|| s)).unwrap();
                     let dur = ((1.0 / pipe_speed) * 1000.0 + 0.5) as usize;
+                    let (a1, a2, _) = connect.iter().find(|(_, _, p)| p == *pipe).unwrap();
+                    let forward_link = format!("{}->{}", prev_part, part1);
+                    let backward_link = format!("{}->{}", next_part, part2);
+
+                    // The batch leaving the first part is pushed out by a batch from
+                    // the area, and the batch leaving the last part is popped out.
+                    if i == 0 {
+                        add_action(
+                            &tl_name,
+                            format!("{}->{}", part1, part2),
+                            "PUSH-START",
+                            vec![
+                                object(pipe),
+                                linked(&forward_link),
+                                object(a1),
+                                object(a2),
+                                object(batch),
+                                linked_product(&forward_link),
+                                object(is_product_map[batch]),
+                            ],
+                            1.0 / pipe_speed,
+                        );
+                    }
+                    if i + 2 == state.len() {
+                        add_action(
+                            &tl_name,
+                            format!("{}->{}", part2, part1),
+                            "POP-START",
+                            vec![
+                                object(pipe),
+                                linked(&backward_link),
+                                object(a1),
+                                object(a2),
+                                object(batch),
+                                linked_product(&backward_link),
+                                object(is_product_map[batch]),
+                            ],
+                            1.0 / pipe_speed,
+                        );
+                    }
 
                     let compatible_batches: Vec<String> = batch_atoms
                         .iter()
@@ -508,7 +577,7 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                                 amount: 0,
                                 object: ObjectSet::Set(compatible_batches.clone()),
                                 temporal_relationship: TemporalRelationship::Cover,
-                                value: format!("{}->{}", prev_part, part1.clone()),
+                                value: forward_link,
                             },
                         ],
                         capacity: 0,
@@ -540,7 +609,7 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                                 amount: 0,
                                 object: ObjectSet::Set(compatible_batches.clone()),
                                 temporal_relationship: TemporalRelationship::Cover,
-                                value: format!("{}->{}", next_part, part2.clone()),
+                                value: backward_link,
                             },
                         ],
                         capacity: 0,
@@ -571,6 +640,33 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                             .cloned()
                             .collect();
 
+                        let link = format!("{}->{}", next_part, pipe_part);
+                        if pipe_state[pipe].len() == 1 {
+                            add_action(
+                                &tl_name,
+                                format!("{}->{}", pipe_part, area),
+                                "POP-UNITARYPIPE",
+                                vec![
+                                    object(pipe),
+                                    linked(&link),
+                                    object(a1),
+                                    object(a2),
+                                    object(batch),
+                                    linked_product(&link),
+                                    object(is_product_map[batch]),
+                                ],
+                                2.0 / pipe_speed,
+                            );
+                        } else {
+                            add_action(
+                                &tl_name,
+                                format!("{}->{}", pipe_part, area),
+                                "POP-END",
+                                vec![object(pipe), object(a1), object(a2), object(batch), linked(&link)],
+                                1.0 / pipe_speed,
+                            );
+                        }
+
                         values.push(TokenType {
                             name: format!("{}->{}", pipe_part, area),
                             duration: (dur, Some(dur)),
@@ -597,7 +693,7 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                                     amount: 0,
                                     object: ObjectSet::Set(compatible_batches.clone()),
                                     temporal_relationship: TemporalRelationship::Cover,
-                                    value: format!("{}->{}", next_part, pipe_part),
+                                    value: link,
                                 },
                             ],
                             capacity: 0,
@@ -627,6 +723,33 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                             .cloned()
                             .collect();
 
+                        let link = format!("{}->{}", next_part, pipe_part);
+                        if part_idx == 0 {
+                            add_action(
+                                &tl_name,
+                                format!("{}->{}", pipe_part, area),
+                                "PUSH-UNITARYPIPE",
+                                vec![
+                                    object(pipe),
+                                    linked(&link),
+                                    object(a1),
+                                    object(a2),
+                                    object(batch),
+                                    linked_product(&link),
+                                    object(is_product_map[batch]),
+                                ],
+                                2.0 / pipe_speed,
+                            );
+                        } else {
+                            add_action(
+                                &tl_name,
+                                format!("{}->{}", pipe_part, area),
+                                "PUSH-END",
+                                vec![object(pipe), object(a1), object(a2), object(batch), linked(&link)],
+                                1.0 / pipe_speed,
+                            );
+                        }
+
                         values.push(TokenType {
                             name: format!("{}->{}", pipe_part, area),
                             duration: (dur, Some(dur)),
@@ -653,7 +776,7 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
                                     amount: 0,
                                     object: ObjectSet::Set(compatible_batches.clone()),
                                     temporal_relationship: TemporalRelationship::Cover,
-                                    value: format!("{}->{}", next_part, pipe_part.clone()),
+                                    value: link,
                                 },
                             ],
                             capacity: 0,
@@ -692,6 +815,12 @@ pub fn convert_pipesworld_batchencoding_notankage_temporal_deadlines() {
             &json,
         )
         .unwrap();
+        let json = serde_json::to_string(&plan_mapping).unwrap();
+        std::fs::write(
+            &format!("pipesworldalt_{}.actions.json", file.file_name().to_str().unwrap()),
+            &json,
+        )
+        .unwrap();
     }
 }
 
@@ -1144,6 +1273,11 @@ pub fn convert_pipesworld_notankage_temporal_deadlines() {
             .map(|(a, b)| (a, b))
             .collect::<HashMap<&String, &String>>();
 
+        let mut plan_mapping = PlanMapping {
+            time_scale: 1000.0,
+            ..Default::default()
+        };
+
         let mut prev_location = HashMap::new();
         let mut next_location = HashMap::new();
         for (l1, l2, pipe) in connect.iter() {
@@ -1299,6 +1433,34 @@ pub fn convert_pipesworld_notankage_temporal_deadlines() {
                             ],
                         };
 
+                        // The exchanges on the first and last parts of the pipe are the
+                        // actions that move it.
+                        let n_parts = pipe_state[pipe].len();
+                        let objects = |names: &[&String]| names.iter().map(|n| PlanArg::Object(n.to_string())).collect();
+                        let mut add_action = |direction: &str, action: &str, args: Vec<PlanArg>, duration: f64| {
+                            plan_mapping.actions.push(ActionMapping {
+                                timeline: name.clone(),
+                                value: format!("{}--{}--{}", b1, b2, direction),
+                                action: action.to_string(),
+                                args,
+                                duration,
+                            })
+                        };
+                        if n_parts == 1 {
+                            let args = objects(&[pipe, b2, l1, l2, b1, product2, product1]);
+                            add_action("forward", "PUSH-UNITARYPIPE", args, 2.0 / pipe_speed);
+                            let args = objects(&[pipe, b2, l1, l2, b1, product2, product1]);
+                            add_action("backward", "POP-UNITARYPIPE", args, 2.0 / pipe_speed);
+                        } else if part == 0 {
+                            let args = objects(&[pipe, b2, l1, l2, b1, product2, product1]);
+                            add_action("forward", "PUSH-START", args, 1.0 / pipe_speed);
+                            add_action("backward", "POP-END", objects(&[pipe, l1, l2, b1, b2]), 1.0 / pipe_speed);
+                        } else if part + 1 == n_parts {
+                            add_action("forward", "PUSH-END", objects(&[pipe, l1, l2, b1, b2]), 1.0 / pipe_speed);
+                            let args = objects(&[pipe, b2, l1, l2, b1, product2, product1]);
+                            add_action("backward", "POP-START", args, 1.0 / pipe_speed);
+                        }
+
                         // EXCHANGE FORWARD
                         let mut forward_conditions = vec![c_prevstate.clone(), c_nextstate.clone(), c_prevpart_f];
                         forward_conditions.extend(c_nextpart_f);
@@ -1402,5 +1564,11 @@ pub fn convert_pipesworld_notankage_temporal_deadlines() {
             &json,
         )
         .unwrap();
+        let json = serde_json::to_string(&plan_mapping).unwrap();
+        std::fs::write(
+            &format!("pipesworld_{}.actions.json", file.file_name().to_str().unwrap()),
+            &json,
+        )
+        .unwrap();
     }
 }
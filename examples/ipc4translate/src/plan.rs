@@ -0,0 +1,183 @@
+use crate::problem::*;
+use std::collections::HashMap;
+
+/// Write the solution of a converted problem as a PDDL temporal plan, with one line
+/// `time: (action args) [duration]` for each token that stands for an action.
+/// Actions start at the start of their tokens.
+pub fn write_plan(solution: &Solution, mapping: &PlanMapping) -> Result<String, String> {
+    let actions = mapping
+        .actions
+        .iter()
+        .map(|a| ((a.timeline.as_str(), a.value.as_str()), a))
+        .collect::<HashMap<_, _>>();
+
+    let mut steps = Vec::new();
+    for timeline in solution.timelines.iter() {
+        for (token_idx, token) in timeline.tokens.iter().enumerate() {
+            let action = match actions.get(&(timeline.name.as_str(), token.value.as_str())) {
+                Some(a) => a,
+                None => continue,
+            };
+            let linked_object = |values: &Vec<String>| {
+                let tl = linked_timeline(solution, &timeline.name, token_idx, values).ok_or_else(|| {
+                    format!(
+                        "token {} {} on {} has no causal link through {:?}",
+                        token_idx, token.value, timeline.name, values
+                    )
+                })?;
+                Ok::<_, String>(
+                    mapping
+                        .timeline_objects
+                        .get(tl)
+                        .cloned()
+                        .unwrap_or_else(|| tl.to_string()),
+                )
+            };
+            let mut args = Vec::new();
+            for arg in action.args.iter() {
+                args.push(match arg {
+                    PlanArg::Object(obj) => obj.clone(),
+                    PlanArg::Linked(values) => linked_object(values)?,
+                    PlanArg::LinkedProperty(values, property) => {
+                        let obj = linked_object(values)?;
+                        mapping
+                            .object_properties
+                            .get(property)
+                            .and_then(|p| p.get(&obj))
+                            .cloned()
+                            .ok_or_else(|| format!("object {} has no property {}", obj, property))?
+                    }
+                });
+            }
+            let start = token.start_time as f64 / mapping.time_scale;
+            steps.push((
+                start,
+                format!("({} {})", action.action, args.join(" ")),
+                action.duration,
+            ));
+        }
+    }
+
+    steps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut plan = String::new();
+    for (start, action, duration) in steps {
+        plan.push_str(&format!("{:.3}: {} [{:.3}]\n", start, action, duration));
+    }
+    Ok(plan)
+}
+
+/// Follow the causal links from a token to tokens with the given values, and return
+/// the timeline of the last one.
+fn linked_timeline<'a>(
+    solution: &'a Solution,
+    timeline: &'a str,
+    token_idx: usize,
+    values: &[String],
+) -> Option<&'a str> {
+    let token = |tl: &str, idx: usize| {
+        solution
+            .timelines
+            .iter()
+            .find(|t| t.name == tl)
+            .and_then(|t| t.tokens.get(idx))
+    };
+
+    let (mut timeline, mut token_idx) = (timeline, token_idx);
+    for value in values.iter() {
+        let link = token(timeline, token_idx)?
+            .conditions
+            .iter()
+            .find(|c| token(&c.timeline, c.token_idx).map(|t| &t.value) == Some(value))?;
+        timeline = &link.timeline;
+        token_idx = link.token_idx;
+    }
+    Some(timeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Batch b1 is pushed from the first part of pipe p by b2, as in the batch
+    // encoding of Pipesworld.
+    fn push_solution() -> Solution {
+        let token = |value: &str, start_time: f32, end_time: f32, conditions: Vec<SolutionCondition>| SolutionToken {
+            value: value.to_string(),
+            start_time,
+            end_time,
+            conditions,
+        };
+        let link = |timeline: &str, token_idx: usize| SolutionCondition {
+            timeline: timeline.to_string(),
+            token_idx,
+        };
+        Solution {
+            timelines: vec![
+                SolutionTimeline {
+                    name: "b1".to_string(),
+                    tokens: vec![
+                        token("p_part0", 0.0, 1000.0, vec![]),
+                        token("p_part0->p_part1", 1000.0, 1500.0, vec![link("b1", 0), link("b2", 1)]),
+                        token("p_part1", 1500.0, 2000.0, vec![]),
+                    ],
+                },
+                SolutionTimeline {
+                    name: "b2".to_string(),
+                    tokens: vec![
+                        token("a1", 0.0, 1000.0, vec![]),
+                        token("a1->p_part0", 1000.0, 1500.0, vec![link("b2", 0)]),
+                        token("p_part0", 1500.0, 2000.0, vec![]),
+                    ],
+                },
+            ],
+            end_of_time: 2000.0,
+        }
+    }
+
+    fn push_mapping(args: Vec<PlanArg>) -> PlanMapping {
+        let products = vec![("b1", "gasoleo"), ("b2", "lco")]
+            .into_iter()
+            .map(|(b, p)| (b.to_string(), p.to_string()))
+            .collect();
+        let mapping = PlanMapping {
+            time_scale: 1000.0,
+            actions: vec![ActionMapping {
+                timeline: "b1".to_string(),
+                value: "p_part0->p_part1".to_string(),
+                action: "PUSH-START".to_string(),
+                args,
+                duration: 0.5,
+            }],
+            object_properties: vec![("product".to_string(), products)].into_iter().collect(),
+            ..Default::default()
+        };
+        // The mapping is read back from its `.actions.json` file.
+        serde_json::from_str(&serde_json::to_string(&mapping).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn linked_batches() {
+        let object = |name: &str| PlanArg::Object(name.to_string());
+        let pushed_by = vec!["a1->p_part0".to_string()];
+        let mapping = push_mapping(vec![
+            object("p"),
+            PlanArg::Linked(pushed_by.clone()),
+            object("a1"),
+            object("a2"),
+            object("b1"),
+            PlanArg::LinkedProperty(pushed_by, "product".to_string()),
+            object("gasoleo"),
+        ]);
+        assert_eq!(
+            write_plan(&push_solution(), &mapping).unwrap(),
+            "1.000: (PUSH-START p b2 a1 a2 b1 lco gasoleo) [0.500]\n"
+        );
+    }
+
+    #[test]
+    fn missing_link() {
+        let mapping = push_mapping(vec![PlanArg::Linked(vec!["a2->p_part1".to_string()])]);
+        assert!(write_plan(&push_solution(), &mapping).is_err());
+        assert_eq!(linked_timeline(&push_solution(), "b1", 1, &[]), Some("b1"));
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Problem {
@@ -88,13 +89,68 @@ pub enum ObjectSet {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Solution {
+    pub timelines: Vec<SolutionTimeline>,
+    pub end_of_time: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SolutionTimeline {
+    pub name: String,
     pub tokens: Vec<SolutionToken>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SolutionToken {
-    pub object_name: String,
     pub value: String,
     pub start_time: f32,
     pub end_time: f32,
+    pub conditions: Vec<SolutionCondition>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SolutionCondition {
+    pub timeline: String,
+    pub token_idx: usize,
+}
+
+//
+// PLAN MAPPING
+//
+
+/// The PDDL actions that the token types of a converted problem stand for, used to
+/// write the solution as a PDDL plan.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PlanMapping {
+    /// Token times are PDDL times multiplied by this.
+    pub time_scale: f64,
+    pub actions: Vec<ActionMapping>,
+    /// The PDDL object for a timeline found through `PlanArg::Linked`, when it is
+    /// not the name of the timeline.
+    #[serde(default)]
+    pub timeline_objects: HashMap<String, String>,
+    /// For each property, its value for each PDDL object, used by
+    /// `PlanArg::LinkedProperty`.
+    #[serde(default)]
+    pub object_properties: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionMapping {
+    pub timeline: String,
+    pub value: String,
+    pub action: String,
+    pub args: Vec<PlanArg>,
+    /// Duration of the action in PDDL time.
+    pub duration: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum PlanArg {
+    Object(String),
+    /// The timeline reached by following the causal links of the token to tokens
+    /// with these values, for objects that are chosen by the planner.
+    Linked(Vec<String>),
+    /// A property of the object found as for `Linked`, such as the product of a
+    /// batch.
+    LinkedProperty(Vec<String>, String),
 }
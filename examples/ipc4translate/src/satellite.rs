@@ -260,6 +260,24 @@ pub fn convert_satellites() {
         // TIMELINES
         let mut timelines: HashMap<String, Vec<TokenType>> = HashMap::new();
         let mut statictokens = Vec::new();
+        let mut plan_mapping = PlanMapping {
+            time_scale: 100.0,
+            timeline_objects: instrument_belongs_to
+                .iter()
+                .map(|(i, s)| (i.to_string(), s.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let mut add_action = |timeline: &str, value: String, action: &str, args: Vec<PlanArg>, duration: f64| {
+            plan_mapping.actions.push(ActionMapping {
+                timeline: timeline.to_string(),
+                value,
+                action: action.to_string(),
+                args,
+                duration,
+            })
+        };
+        let object = |name: &String| PlanArg::Object(name.clone());
 
         // 1.
         //
@@ -281,6 +299,13 @@ pub fn convert_satellites() {
 
             for (a, b, l) in slew_time.iter() {
                 let dur = (l * 100.0 + 0.5) as usize;
+                add_action(
+                    &timeline_name,
+                    format!("slew_{}_{}", a, b),
+                    "turn_to",
+                    vec![object(satellite), object(b), object(a)],
+                    *l,
+                );
                 tokentypes.push(TokenType {
                     name: format!("slew_{}_{}", a, b),
                     duration: (dur, Some(dur)),
@@ -341,6 +366,21 @@ pub fn convert_satellites() {
         //
         for instrument in instruments.iter() {
             let tl_name = instrument;
+            let satellite = instrument_belongs_to[instrument];
+            add_action(
+                tl_name,
+                "SwitchOn".to_string(),
+                "switch_on",
+                vec![object(instrument), object(satellite)],
+                2.0,
+            );
+            add_action(
+                tl_name,
+                "SwitchOff".to_string(),
+                "switch_off",
+                vec![object(instrument), object(satellite)],
+                1.0,
+            );
             let power_cond = Condition {
                 amount: 0,
                 object: ObjectSet::Object(format!("power_instrument_{}", instrument)),
@@ -453,6 +493,13 @@ pub fn convert_satellites() {
                         .find_map(|(i, d, t)| (i == instrument && d == dir).then(|| t))
                         .unwrap();
                     let dur = (t * 100. + 0.5) as usize;
+                    add_action(
+                        tl_name,
+                        format!("calibrate_{}", dir),
+                        "calibrate",
+                        vec![object(satellite), object(instrument), object(dir)],
+                        t,
+                    );
                     values.push(TokenType {
                         name: format!("calibrate_{}", dir),
                         duration: (dur, None),
@@ -479,6 +526,13 @@ pub fn convert_satellites() {
             for dir in directions.iter() {
                 for mode in modes.iter() {
                     if supports.iter().any(|(i, m)| i == instrument && m == mode) {
+                        add_action(
+                            tl_name,
+                            format!("TakeImage_{}_{}", dir, mode),
+                            "take_image",
+                            vec![object(satellite), object(dir), object(instrument), object(mode)],
+                            7.0,
+                        );
                         values.push(TokenType {
                             name: format!("TakeImage_{}_{}", dir, mode),
                             duration: (700, None),
@@ -531,6 +585,20 @@ pub fn convert_satellites() {
             let dur = (t * 100. + 0.5) as usize;
 
             let timeline_name = format!("SendImage_{}_{}", dir, mode);
+            // The satellite is the one with the instrument that took the image, and
+            // the antenna is the one that was used.
+            add_action(
+                &timeline_name,
+                "Send".to_string(),
+                "send_image",
+                vec![
+                    PlanArg::Linked(vec!["HaveImage".to_string(), format!("TakeImage_{}_{}", dir, mode)]),
+                    PlanArg::Linked(vec!["Available".to_string()]),
+                    object(dir),
+                    object(mode),
+                ],
+                t,
+            );
             let tokentypes = vec![
                 TokenType {
                     name: "HaveImage".to_string(),
@@ -678,5 +746,11 @@ pub fn convert_satellites() {
 
         let json = serde_json::to_string(&problem).unwrap();
         std::fs::write(&format!("satellite_{}.json", file.file_name().to_str().unwrap()), &json).unwrap();
+        let json = serde_json::to_string(&plan_mapping).unwrap();
+        std::fs::write(
+            &format!("satellite_{}.actions.json", file.file_name().to_str().unwrap()),
+            &json,
+        )
+        .unwrap();
     }
 }
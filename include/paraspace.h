@@ -0,0 +1,92 @@
+#ifndef PARASPACE_H
+#define PARASPACE_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Flag for stopping a solver that is running in another thread.
+typedef struct ParaspaceCancelToken ParaspaceCancelToken;
+
+// A parsed planning problem.
+typedef struct ParaspaceProblem ParaspaceProblem;
+
+// Solver settings.
+typedef struct ParaspaceSettings ParaspaceSettings;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The version of the library, as a static string that must not be freed.
+const char *paraspace_version(void);
+
+// Parse a problem from JSON. Returns null if the JSON is not a valid problem.
+//
+// # Safety
+// `json` must be a NUL-terminated string. If `error` is not null, a message is
+// stored in it on failure, which must be freed with `paraspace_string_free`.
+ParaspaceProblem *paraspace_problem_from_json(const char *json, char **error);
+
+// # Safety
+// `problem` must be null or come from `paraspace_problem_from_json`, and must not
+// be used afterwards.
+void paraspace_problem_free(ParaspaceProblem *problem);
+
+// Parse solver settings from JSON. Missing fields take their default values, so
+// `"{}"` gives the default settings. Returns null if the JSON is not valid settings.
+//
+// # Safety
+// As for `paraspace_problem_from_json`.
+ParaspaceSettings *paraspace_settings_from_json(const char *json, char **error);
+
+// # Safety
+// `settings` must be null or come from `paraspace_settings_from_json`, and must not
+// be used afterwards.
+void paraspace_settings_free(ParaspaceSettings *settings);
+
+ParaspaceCancelToken *paraspace_cancel_token_new(void);
+
+// Stop the solver runs that use this token. This can be called from any thread.
+//
+// # Safety
+// `token` must come from `paraspace_cancel_token_new` and not be freed yet.
+void paraspace_cancel(const ParaspaceCancelToken *token);
+
+// # Safety
+// `token` must be null or come from `paraspace_cancel_token_new`, and no solver
+// may be using it.
+void paraspace_cancel_token_free(ParaspaceCancelToken *token);
+
+// Validate and solve the problem, and return the JSON response with the solution
+// or the error. `settings` and `cancel` may be null, for the default settings and
+// no cancellation. The result must be freed with `paraspace_string_free`.
+//
+// # Safety
+// The pointers must be null or come from the matching constructors.
+char *paraspace_solve(const ParaspaceProblem *problem,
+                      const ParaspaceSettings *settings,
+                      const ParaspaceCancelToken *cancel);
+
+// Solve a JSON request, as `solve_json`. The result must be freed with
+// `paraspace_string_free`.
+//
+// # Safety
+// `request` must be a NUL-terminated string.
+char *paraspace_solve_json(const char *request);
+
+// Free a string returned by the library.
+//
+// # Safety
+// `s` must be null or a string returned by this library, and must not be used
+// afterwards.
+void paraspace_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PARASPACE_H */
@@ -332,6 +332,8 @@ pub enum TemporalRelationPy {
     Cover,
     Equal,
     StartsAfter,
+    NotOverlapping,
+    NotDuring,
 }
 
 //
@@ -456,6 +458,7 @@ fn convert_problem_arguments(problem: ProblemArgument) -> Problem {
                         name,
                         token_types,
                         static_tokens,
+                        ..Default::default()
                     }
                 })
                 .collect(),
@@ -495,11 +498,7 @@ fn solve(problem: ProblemArgument) -> PyResult<SolutionPy> {
                 .collect(),
             end_of_time: s.end_of_time,
         }),
-        Err(e) => Err(pyo3::exceptions::PyException::new_err(match e {
-            paraspace::SolverError::NoSolution => "No solution found",
-            paraspace::SolverError::GoalValueDurationLimit => "Goal value duration limit error",
-            paraspace::SolverError::GoalStateMissing => "Goal state missing",
-        })),
+        Err(e) => Err(pyo3::exceptions::PyException::new_err(e.to_string())),
     }
 }
 
@@ -558,6 +557,7 @@ fn convert_timeline(timeline: TimelinePy) -> Timeline {
             .into_iter()
             .map(convert_static_token)
             .collect(),
+        ..Default::default()
     }
 }
 
@@ -567,6 +567,7 @@ fn convert_token_type(tt: TokenTypePy) -> TokenType {
         capacity: tt.capacity,
         duration_limits: tt.duration_limits,
         conditions: convert_conditions(tt.conditions),
+        ..Default::default()
     }
 }
 
@@ -602,6 +603,10 @@ fn convert_conditions(cond: Vec<Vec<TemporalCondPy>>) -> Vec<Vec<Condition>> {
                         TemporalRelationPy::Starts => TemporalRelationship::Starts,
                         TemporalRelationPy::StartPrecond => TemporalRelationship::StartPrecond,
                         TemporalRelationPy::StartEffect => TemporalRelationship::StartEffect,
+                        TemporalRelationPy::NotOverlapping => {
+                            TemporalRelationship::NotOverlapping
+                        }
+                        TemporalRelationPy::NotDuring => TemporalRelationship::NotDuring,
                     },
                 })
                 .collect()
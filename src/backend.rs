@@ -0,0 +1,78 @@
+//! The interface between the refinement algorithm and the solver that it calls.
+//!
+//! The transition solver builds its formulation from boolean literals, linear
+//! constraints on real-valued time points, and pseudo-boolean constraints. It solves
+//! the formulation under a set of assumptions, and uses the unsatisfiable cores to
+//! decide how to refine the problem. Any solver that supports these operations can
+//! be used by implementing `Backend`.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::atomic::AtomicBool;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    Unknown,
+}
+
+pub trait Backend: Debug {
+    /// A boolean term.
+    type Bool: Clone + Eq + Hash + Debug;
+    /// A real-valued term.
+    type Real: Clone + PartialEq + Debug;
+
+    /// A new boolean variable. The prefix is only used to make the formulation readable.
+    fn fresh_bool(&self, prefix: &str) -> Self::Bool;
+    /// A new real-valued variable.
+    fn fresh_real(&self, prefix: &str) -> Self::Real;
+    fn bool_const(&self, value: bool) -> Self::Bool;
+    /// The rational constant `num / den`.
+    fn real_const(&self, num: i32, den: i32) -> Self::Real;
+    /// The exact rational value of the decimal representation of a float.
+    fn real_from_f32(&self, value: f32) -> Self::Real;
+
+    fn not(&self, a: &Self::Bool) -> Self::Bool;
+    fn and(&self, xs: &[&Self::Bool]) -> Self::Bool;
+    fn or(&self, xs: &[&Self::Bool]) -> Self::Bool;
+    fn implies(&self, a: &Self::Bool, b: &Self::Bool) -> Self::Bool;
+    /// The sum of the coefficients of the true literals is at most `k`.
+    fn pb_le(&self, xs: &[(&Self::Bool, i32)], k: i32) -> Self::Bool;
+
+    fn add(&self, xs: &[&Self::Real]) -> Self::Real;
+    fn sub(&self, xs: &[&Self::Real]) -> Self::Real;
+    fn mul(&self, xs: &[&Self::Real]) -> Self::Real;
+    fn ite(&self, cond: &Self::Bool, a: &Self::Real, b: &Self::Real) -> Self::Real;
+    fn le(&self, a: &Self::Real, b: &Self::Real) -> Self::Bool;
+    fn lt(&self, a: &Self::Real, b: &Self::Real) -> Self::Bool;
+    fn ge(&self, a: &Self::Real, b: &Self::Real) -> Self::Bool;
+    fn eq(&self, a: &Self::Real, b: &Self::Real) -> Self::Bool;
+
+    fn assert(&self, a: &Self::Bool);
+
+    /// Check satisfiability under the assumptions. The backend should stop and return
+    /// `SatResult::Unknown` when the `cancel` flag is set.
+    fn check(&self, assumptions: &[Self::Bool], cancel: Option<&AtomicBool>) -> SatResult;
+
+    /// A subset of the assumptions that is unsatisfiable, after an unsatisfiable check.
+    fn unsat_core(&self) -> Vec<Self::Bool>;
+
+    /// The value of a boolean term in the model, after a satisfiable check.
+    fn bool_value(&self, a: &Self::Bool) -> bool;
+
+    /// The value of a real-valued term in the model, after a satisfiable check.
+    fn real_value(&self, a: &Self::Real) -> f32;
+
+    /// The assertions in standard SMT-LIB format, without solver-specific syntax.
+    fn to_smtlib(&self) -> String;
+
+    /// A boolean term in SMT-LIB format, using the names declared in `to_smtlib`.
+    fn term_to_smtlib(&self, a: &Self::Bool) -> String;
+
+    /// The first error from the solver, such as a failed solver process. After an
+    /// error, checks return `SatResult::Unknown` and models and cores are meaningless.
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
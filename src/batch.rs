@@ -0,0 +1,159 @@
+//! Solving many problem files at once.
+//!
+//! The files are solved by a number of worker threads, each with its own solver
+//! and the timeout from the settings. The solution to `<name>.json` is written to
+//! `<name>.out.json` in the same directory.
+
+use crate::benchmark::expand_pattern;
+use crate::jsonapi::{solve_problem, SolveResponse};
+use crate::problem::Problem;
+use crate::transitionsolver::SolverSettings;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchResult {
+    pub file: PathBuf,
+    /// `Solved`, or the kind of error as in `jsonapi::ErrorInfo`.
+    pub status: String,
+    /// Wall-clock time in seconds.
+    pub time: f64,
+    /// The end of time of the solution.
+    pub makespan: Option<f32>,
+    /// The file that the solution was written to.
+    pub output: Option<PathBuf>,
+}
+
+/// The problem files given by a directory, a file name, or a file name with the
+/// wildcards `*` and `?` in its last component. Directories give all their `.json`
+/// files. Solution files ending in `.out.json` are left out.
+pub fn input_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let files = if path.is_dir() {
+        expand_pattern(&path.join("*.json"))?
+    } else {
+        expand_pattern(path)?
+    };
+    Ok(files
+        .into_iter()
+        .filter(|f| !f.to_string_lossy().ends_with(".out.json"))
+        .collect())
+}
+
+/// The file that the solution to a problem file is written to.
+pub fn output_file(file: &Path) -> PathBuf {
+    let stem = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = stem.strip_suffix(".json").unwrap_or(&stem);
+    file.with_file_name(format!("{}.out.json", stem))
+}
+
+/// Solve the files with `jobs` worker threads, calling `progress` as each file is
+/// finished. The results are in the same order as the files.
+pub fn solve_files(
+    files: &[PathBuf],
+    settings: &SolverSettings,
+    jobs: usize,
+    progress: impl Fn(&BatchResult) + Sync,
+) -> Vec<BatchResult> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; files.len()]);
+    std::thread::scope(|s| {
+        for _ in 0..jobs.max(1) {
+            s.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let file = match files.get(idx) {
+                    Some(f) => f,
+                    None => break,
+                };
+                let result = solve_file(file, settings);
+                progress(&result);
+                results.lock().unwrap()[idx] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect()
+}
+
+fn solve_file(file: &Path, settings: &SolverSettings) -> BatchResult {
+    let start = std::time::Instant::now();
+    let mut result = BatchResult {
+        file: file.to_path_buf(),
+        status: "InvalidJson".to_string(),
+        time: 0.0,
+        makespan: None,
+        output: None,
+    };
+
+    let problem = match std::fs::read_to_string(file)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Problem>(&contents).ok())
+    {
+        Some(p) => p,
+        None => return result,
+    };
+
+    // Keep the SMT-LIB2 files of each problem apart.
+    let mut settings = settings.clone();
+    if let Some(dir) = settings.smt2_dump_dir.as_ref() {
+        let stem = file.file_stem().unwrap_or_default();
+        settings.smt2_dump_dir = Some(dir.join(stem));
+    }
+
+    let response = solve_problem(&problem, &settings, None);
+    result.time = start.elapsed().as_secs_f64();
+    match response {
+        SolveResponse::Solved { solution, .. } => {
+            result.makespan = Some(solution.end_of_time);
+            let output = output_file(file);
+            let json = serde_json::to_string_pretty(&solution).unwrap();
+            match std::fs::write(&output, json) {
+                Ok(()) => {
+                    result.status = "Solved".to_string();
+                    result.output = Some(output);
+                }
+                Err(_) => result.status = "WriteError".to_string(),
+            }
+        }
+        SolveResponse::Failed { error, .. } => result.status = error.kind,
+    }
+    result
+}
+
+/// A table of the results with the status, time and makespan of each file.
+pub fn summary(results: &[BatchResult]) -> String {
+    let names = results
+        .iter()
+        .map(|r| r.file.display().to_string())
+        .collect::<Vec<_>>();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+    let mut table = format!(
+        "{:<width$}  {:<12}  {:>10}  {:>10}\n",
+        "file",
+        "status",
+        "time (s)",
+        "makespan",
+        width = width
+    );
+    for (name, r) in names.iter().zip(results.iter()) {
+        table.push_str(&format!(
+            "{:<width$}  {:<12}  {:>10.3}  {:>10}\n",
+            name,
+            r.status,
+            r.time,
+            r.makespan.map(|m| m.to_string()).unwrap_or_default(),
+            width = width
+        ));
+    }
+    let solved = results.iter().filter(|r| r.status == "Solved").count();
+    table.push_str(&format!("Solved {} of {} files.\n", solved, results.len()));
+    table
+}
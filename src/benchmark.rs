@@ -0,0 +1,328 @@
+//! Reproducible benchmarks.
+//!
+//! A manifest lists sets of problem instances, given as file names or patterns
+//! relative to the manifest or as names of generated instances, together with the
+//! solver settings, the timeout and the number of repetitions:
+//!
+//! ```json
+//! {
+//!   "repetitions": 3,
+//!   "timeout": 60.0,
+//!   "sets": [
+//!     { "name": "jobs", "instances": ["instances/jobs_*.json"] },
+//!     { "name": "goac", "generated": ["goac_1pics_1wind", "goac_2pics_1wind"],
+//!       "settings": { "optimize": "Makespan" } }
+//!   ]
+//! }
+//! ```
+//!
+//! Each run produces a `RunResult` with the `SolveStats` of the solver. The results
+//! can be written as CSV or JSON, and a JSON file of earlier results can be used as
+//! a baseline to find regressions. The baseline for `benchmarks/manifest.json` is
+//! kept in `benchmarks/baseline.json`.
+
+use crate::generators;
+use crate::jsonapi::{solve_problem, SolveResponse};
+use crate::plancheck::check_plan;
+use crate::problem::Problem;
+use crate::transitionsolver::{SolveStats, SolverSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    pub sets: Vec<InstanceSet>,
+    /// Number of times each instance is solved.
+    #[serde(default = "default_repetitions")]
+    pub repetitions: usize,
+    /// Time limit in seconds for each run, overriding the timeout in the settings.
+    #[serde(default)]
+    pub timeout: Option<f64>,
+    /// Settings for the sets that do not have their own.
+    #[serde(default)]
+    pub settings: SolverSettings,
+}
+
+fn default_repetitions() -> usize {
+    1
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstanceSet {
+    pub name: String,
+    /// File names relative to the manifest. The last component of a name can
+    /// contain the wildcards `*` and `?`.
+    #[serde(default)]
+    pub instances: Vec<String>,
+    /// Names of instances that are made by `generators::by_name`.
+    #[serde(default)]
+    pub generated: Vec<String>,
+    #[serde(default)]
+    pub settings: Option<SolverSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunResult {
+    pub set: String,
+    pub instance: String,
+    pub repetition: usize,
+    /// `Solved`, or the kind of error as in `jsonapi::ErrorInfo`. A solution that
+    /// does not pass `plancheck::check_plan` gives `InvalidPlan`.
+    pub status: String,
+    #[serde(default)]
+    pub end_of_time: Option<f32>,
+    #[serde(default)]
+    pub total_cost: Option<f32>,
+    #[serde(flatten)]
+    pub stats: SolveStats,
+}
+
+impl RunResult {
+    pub fn solved(&self) -> bool {
+        self.status == "Solved"
+    }
+}
+
+/// A run that is worse than the baseline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub set: String,
+    pub instance: String,
+    pub message: String,
+}
+
+/// Differences in solve time below this many seconds are not regressions.
+pub const MIN_TIME_DIFFERENCE: f64 = 0.05;
+
+impl Manifest {
+    /// The instance files of a set, relative to the directory of the manifest.
+    pub fn instances(&self, set: &InstanceSet, base_dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let mut files = Vec::new();
+        for pattern in set.instances.iter() {
+            let matches = expand_pattern(&base_dir.join(pattern))?;
+            if matches.is_empty() {
+                return Err(format!(
+                    "no instances match \"{}\" in set \"{}\"",
+                    pattern, set.name
+                ));
+            }
+            files.extend(matches);
+        }
+        Ok(files)
+    }
+
+    /// Solve every instance of every set, calling `progress` after each run.
+    pub fn run(
+        &self,
+        base_dir: &Path,
+        mut progress: impl FnMut(&RunResult),
+    ) -> Result<Vec<RunResult>, String> {
+        let mut results = Vec::new();
+        for set in self.sets.iter() {
+            let mut settings = set
+                .settings
+                .clone()
+                .unwrap_or_else(|| self.settings.clone());
+            if self.timeout.is_some() {
+                settings.timeout = self.timeout;
+            }
+
+            let mut problems = Vec::new();
+            for file in self.instances(set, base_dir)? {
+                let instance = file
+                    .strip_prefix(base_dir)
+                    .unwrap_or(&file)
+                    .display()
+                    .to_string();
+                let contents = std::fs::read_to_string(&file)
+                    .map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+                problems.push((instance, serde_json::from_str::<Problem>(&contents).ok()));
+            }
+            for name in set.generated.iter() {
+                let problem = generators::by_name(name).ok_or_else(|| {
+                    format!(
+                        "unknown generated instance \"{}\" in set \"{}\"",
+                        name, set.name
+                    )
+                })?;
+                problems.push((name.clone(), Some(problem)));
+            }
+
+            for (instance, problem) in problems {
+                for repetition in 0..self.repetitions {
+                    let mut result = RunResult {
+                        set: set.name.clone(),
+                        instance: instance.clone(),
+                        repetition,
+                        status: "InvalidJson".to_string(),
+                        end_of_time: None,
+                        total_cost: None,
+                        stats: SolveStats::default(),
+                    };
+                    if let Some(problem) = problem.as_ref() {
+                        run_instance(problem, &settings, &mut result);
+                    }
+                    progress(&result);
+                    results.push(result);
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+fn run_instance(problem: &Problem, settings: &SolverSettings, result: &mut RunResult) {
+    match solve_problem(problem, settings, None) {
+        SolveResponse::Solved { solution, stats } => {
+            result.status = match check_plan(problem, &solution) {
+                Ok(()) => "Solved".to_string(),
+                Err(_) => "InvalidPlan".to_string(),
+            };
+            result.end_of_time = Some(solution.end_of_time);
+            result.total_cost = Some(solution.total_cost);
+            result.stats = stats;
+        }
+        SolveResponse::Failed { error, stats } => {
+            result.status = error.kind;
+            result.stats = stats.unwrap_or_default();
+        }
+    }
+}
+
+/// The results as CSV, with one line for each run.
+pub fn to_csv(results: &[RunResult]) -> String {
+    let mut csv = String::from(
+        "set,instance,repetition,status,solve_time,smt_calls,plans_found,states,tokens,conditions,end_of_time,total_cost\n",
+    );
+    let optional = |x: Option<f32>| x.map(|x| x.to_string()).unwrap_or_default();
+    for r in results.iter() {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            csv_field(&r.set),
+            csv_field(&r.instance),
+            r.repetition,
+            csv_field(&r.status),
+            r.stats.solve_time,
+            r.stats.smt_calls,
+            r.stats.plans_found,
+            r.stats.states,
+            r.stats.tokens,
+            r.stats.conditions,
+            optional(r.end_of_time),
+            optional(r.total_cost),
+        ));
+    }
+    csv
+}
+
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Compare the results with a baseline. An instance has regressed if it was solved
+/// in every run of the baseline and is not solved in some run now, or if its median
+/// solve time has grown by more than the `tolerance` fraction and by more than
+/// `MIN_TIME_DIFFERENCE` seconds. Instances that are not in the baseline are ignored.
+pub fn compare(results: &[RunResult], baseline: &[RunResult], tolerance: f64) -> Vec<Regression> {
+    let current = by_instance(results);
+    let baseline = by_instance(baseline);
+    let mut regressions = Vec::new();
+    for (key, runs) in current.iter() {
+        let base_runs = match baseline.get(key) {
+            Some(b) => b,
+            None => continue,
+        };
+        let regression = |message: String| Regression {
+            set: key.0.to_string(),
+            instance: key.1.to_string(),
+            message,
+        };
+
+        if base_runs.iter().all(|r| r.solved()) {
+            if let Some(failed) = runs.iter().find(|r| !r.solved()) {
+                regressions.push(regression(format!("was solved, now {}", failed.status)));
+                continue;
+            }
+        }
+
+        let time = median(runs.iter().map(|r| r.stats.solve_time).collect());
+        let base_time = median(base_runs.iter().map(|r| r.stats.solve_time).collect());
+        if time > base_time * (1.0 + tolerance) && time - base_time > MIN_TIME_DIFFERENCE {
+            regressions.push(regression(format!(
+                "median solve time {:.3}s, baseline {:.3}s",
+                time, base_time
+            )));
+        }
+    }
+    regressions
+}
+
+fn by_instance(results: &[RunResult]) -> BTreeMap<(&str, &str), Vec<&RunResult>> {
+    let mut map: BTreeMap<(&str, &str), Vec<&RunResult>> = BTreeMap::new();
+    for r in results.iter() {
+        map.entry((r.set.as_str(), r.instance.as_str()))
+            .or_default()
+            .push(r);
+    }
+    map
+}
+
+fn median(mut xs: Vec<f64>) -> f64 {
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    match xs.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => xs[n / 2],
+        n => (xs[n / 2 - 1] + xs[n / 2]) / 2.0,
+    }
+}
+
+/// The files matching a path whose last component may contain the wildcards `*`
+/// and `?`, in sorted order. A path without wildcards is returned if it exists.
+pub fn expand_pattern(pattern: &Path) -> Result<Vec<PathBuf>, String> {
+    let name = pattern
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("invalid file pattern {}", pattern.display()))?;
+    if !name.contains(&['*', '?'][..]) {
+        return Ok(if pattern.exists() {
+            vec![pattern.to_path_buf()]
+        } else {
+            vec![]
+        });
+    }
+
+    let dir = pattern.parent().unwrap_or_else(|| Path::new("."));
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("could not read directory {}: {}", dir.display(), e))?;
+    let mut files = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .map(|n| wildcard_match(name.as_bytes(), n.as_bytes()))
+                .unwrap_or(false)
+        })
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
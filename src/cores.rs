@@ -1,5 +1,27 @@
+#![allow(clippy::unnecessary_to_owned)]
 
-pub fn minimize_core<'ctx>(core: &mut Vec<z3::ast::Bool<'ctx>>, solver: &z3::Solver<'ctx>, print :impl Fn(&str)) {
+use crate::backend::{Backend, SatResult};
+use crate::SolverError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The error for a check that the SMT solver could not decide, either because it
+/// was stopped with the `cancel` flag, because it failed, or because it gave up.
+pub(crate) fn undecided<B: Backend>(backend: &B, cancel: Option<&AtomicBool>) -> SolverError {
+    if cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false) {
+        SolverError::Cancelled
+    } else if let Some(e) = backend.error() {
+        SolverError::SolverProcess(e)
+    } else {
+        SolverError::Unknown
+    }
+}
+
+pub fn minimize_core<B: Backend>(
+    core: &mut Vec<B::Bool>,
+    backend: &B,
+    cancel: Option<&AtomicBool>,
+    print: impl Fn(&str),
+) -> Result<(), SolverError> {
     print("Starting core minimization.");
     let mut i = 0;
     'minimize_loop: loop {
@@ -14,29 +36,37 @@ pub fn minimize_core<'ctx>(core: &mut Vec<z3::ast::Bool<'ctx>>, solver: &z3::Sol
                 assumptions.len(),
                 remove_idx
             ));
-            let result = solver.check_assumptions(&assumptions);
-            if matches!(result, z3::SatResult::Unsat) {
-                *core = solver.get_unsat_core();
-                print(&format!("Minimized {}->{}", last_core_size, core.len()));
-                continue 'minimize_loop;
+            match backend.check(&assumptions, cancel) {
+                SatResult::Unsat => {
+                    *core = backend.unsat_core();
+                    print(&format!("Minimized {}->{}", last_core_size, core.len()));
+                    continue 'minimize_loop;
+                }
+                SatResult::Sat => i += 1,
+                SatResult::Unknown => return Err(undecided(backend, cancel)),
             }
-            i += 1;
         }
         print(&"Finished core minimization.".to_string());
-        break;
+        return Ok(());
     }
 }
 
-pub fn trim_core<'ctx>(core: &mut Vec<z3::ast::Bool<'ctx>>, solver: &z3::Solver<'ctx>, print :impl Fn(&str)) {
+pub fn trim_core<B: Backend>(
+    core: &mut Vec<B::Bool>,
+    backend: &B,
+    cancel: Option<&AtomicBool>,
+    print: impl Fn(&str),
+) -> Result<(), SolverError> {
     print("Starting core trim.");
     loop {
         let last_core_size = core.len();
         // Try to trim the core.
-        let result = solver.check_assumptions(&*core);
-        assert!(matches!(result, z3::SatResult::Unsat));
-        *core = solver.get_unsat_core();
+        if backend.check(core, cancel) != SatResult::Unsat {
+            return Err(undecided(backend, cancel));
+        }
+        *core = backend.unsat_core();
         if core.len() == last_core_size {
-            break;
+            return Ok(());
         } else {
             print(&format!("Trimmed {}->{}", last_core_size, core.len()));
         }
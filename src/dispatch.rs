@@ -0,0 +1,286 @@
+//! Plan execution.
+//!
+//! The `Dispatcher` keeps track of the execution of a flexible plan. It gives the
+//! start and end events of the tokens ordered by their earliest possible time,
+//! takes feedback about the actual start and end times, and propagates delays
+//! through the temporal network of the plan, which contains the causal links of
+//! the solution. When the feedback cannot be reconciled with the plan, the
+//! dispatcher signals that replanning is required.
+
+use crate::flexible::{FlexibleSolution, END_OF_TIME, ORIGIN};
+use crate::problem::Solution;
+
+const EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Start,
+    End,
+}
+
+/// A start or end event of a token that has not been executed yet.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub timeline: String,
+    pub token_idx: usize,
+    pub value: String,
+    pub kind: EventKind,
+    /// Earliest time the event can happen, given the feedback so far.
+    pub earliest: f32,
+    /// Latest time the event can happen, given the feedback so far.
+    pub latest: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Feedback {
+    Started {
+        timeline: String,
+        token_idx: usize,
+        time: f32,
+    },
+    Ended {
+        timeline: String,
+        token_idx: usize,
+        time: f32,
+    },
+    Failed {
+        timeline: String,
+        token_idx: usize,
+        time: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplanReason {
+    /// A token could not be executed.
+    Failed { timeline: String, token_idx: usize },
+    /// An event happened outside of its time window.
+    OutsideWindow { timeline: String, token_idx: usize },
+    /// An event did not happen before its latest time.
+    DeadlineMissed { timeline: String, token_idx: usize },
+    /// The feedback refers to a token that is not in the plan.
+    UnknownToken { timeline: String, token_idx: usize },
+}
+
+pub struct Dispatcher {
+    plan: FlexibleSolution,
+    /// For each time point, the token it belongs to.
+    point_tokens: Vec<Option<(usize, usize, EventKind)>>,
+    executed: Vec<Option<f32>>,
+    replan: Option<ReplanReason>,
+}
+
+impl Dispatcher {
+    pub fn new(plan: FlexibleSolution) -> Self {
+        let n_points = plan.distances.len();
+        let mut point_tokens = vec![None; n_points];
+        for (tl_idx, tl) in plan.timelines.iter().enumerate() {
+            for (token_idx, token) in tl.tokens.iter().enumerate() {
+                point_tokens[token.start_point] = Some((tl_idx, token_idx, EventKind::Start));
+                point_tokens[token.end_point] = Some((tl_idx, token_idx, EventKind::End));
+            }
+        }
+
+        let mut executed = vec![None; n_points];
+        executed[ORIGIN] = Some(0.0);
+
+        Dispatcher {
+            plan,
+            point_tokens,
+            executed,
+            replan: None,
+        }
+    }
+
+    /// Dispatch a solution that has fixed times. Events can be delayed, but the gaps
+    /// between the causally linked events of the solution are kept.
+    pub fn from_solution(solution: &Solution) -> Option<Self> {
+        FlexibleSolution::from_fixed_times(solution).map(Self::new)
+    }
+
+    /// The reason for replanning, if the plan can no longer be executed.
+    pub fn replan_required(&self) -> Option<&ReplanReason> {
+        self.replan.as_ref()
+    }
+
+    /// All events have been executed.
+    pub fn is_finished(&self) -> bool {
+        self.point_tokens
+            .iter()
+            .zip(self.executed.iter())
+            .all(|(token, executed)| token.is_none() || executed.is_some())
+    }
+
+    /// The time window of a time point, given the times of the executed time points.
+    fn window(&self, point: usize) -> (f32, Option<f32>) {
+        let d = &self.plan.distances;
+        let mut earliest = 0.0f32;
+        let mut latest: Option<f32> = None;
+        for (q, time) in self.executed.iter().enumerate() {
+            if let Some(time) = time {
+                if let Some(d_pq) = d[point][q] {
+                    earliest = earliest.max(time - d_pq);
+                }
+                if let Some(d_qp) = d[q][point] {
+                    latest = Some(latest.map_or(time + d_qp, |l| l.min(time + d_qp)));
+                }
+            }
+        }
+        (earliest, latest)
+    }
+
+    /// Time points that must happen before or at the same time as the given point.
+    fn predecessors(&self, point: usize) -> impl Iterator<Item = usize> + '_ {
+        let d = &self.plan.distances;
+        (0..d.len()).filter(move |q| {
+            *q != point
+                && *q != END_OF_TIME
+                && d[point][*q].map(|x| x <= 0.0).unwrap_or(false)
+                && !d[*q][point].map(|x| x <= 0.0).unwrap_or(false)
+        })
+    }
+
+    fn event(&self, point: usize) -> Option<Event> {
+        let (tl_idx, token_idx, kind) = self.point_tokens[point]?;
+        let tl = &self.plan.timelines[tl_idx];
+        let (earliest, latest) = self.window(point);
+        Some(Event {
+            timeline: tl.name.clone(),
+            token_idx,
+            value: tl.tokens[token_idx].value.clone(),
+            kind,
+            earliest,
+            latest,
+        })
+    }
+
+    /// The events that have not been executed, ordered by their earliest time.
+    pub fn pending_events(&self) -> Vec<Event> {
+        let mut events = (0..self.executed.len())
+            .filter(|p| self.executed[*p].is_none())
+            .filter_map(|p| self.event(p))
+            .collect::<Vec<_>>();
+        events.sort_by(|a, b| a.earliest.total_cmp(&b.earliest));
+        events
+    }
+
+    /// The events that can happen now: their earliest time has passed, and all
+    /// the events that must happen before them have been executed.
+    pub fn ready_events(&self, now: f32) -> Vec<Event> {
+        let mut events = (0..self.executed.len())
+            .filter(|p| self.executed[*p].is_none())
+            .filter(|p| self.predecessors(*p).all(|q| self.executed[q].is_some()))
+            .filter_map(|p| self.event(p))
+            .filter(|e| e.earliest <= now + EPSILON)
+            .collect::<Vec<_>>();
+        events.sort_by(|a, b| a.earliest.total_cmp(&b.earliest));
+        events
+    }
+
+    /// Check for events that have not been executed before their latest time.
+    pub fn advance(&mut self, now: f32) -> Option<&ReplanReason> {
+        if self.replan.is_none() {
+            if let Some(event) = self
+                .pending_events()
+                .into_iter()
+                .find(|e| e.latest.map(|l| l + EPSILON < now).unwrap_or(false))
+            {
+                self.replan = Some(ReplanReason::DeadlineMissed {
+                    timeline: event.timeline,
+                    token_idx: event.token_idx,
+                });
+            }
+        }
+        self.replan.as_ref()
+    }
+
+    /// Record the actual time of an event and propagate it to the time
+    /// windows of the other events.
+    pub fn feedback(&mut self, feedback: Feedback) -> Option<&ReplanReason> {
+        let (timeline, token_idx, time, kind) = match feedback {
+            Feedback::Failed {
+                timeline,
+                token_idx,
+                ..
+            } => {
+                self.replan = Some(ReplanReason::Failed {
+                    timeline,
+                    token_idx,
+                });
+                return self.replan.as_ref();
+            }
+            Feedback::Started {
+                timeline,
+                token_idx,
+                time,
+            } => (timeline, token_idx, time, EventKind::Start),
+            Feedback::Ended {
+                timeline,
+                token_idx,
+                time,
+            } => (timeline, token_idx, time, EventKind::End),
+        };
+
+        let token = self
+            .plan
+            .timelines
+            .iter()
+            .find(|tl| tl.name == timeline)
+            .and_then(|tl| tl.tokens.get(token_idx));
+        let point = match (token, kind) {
+            (Some(token), EventKind::Start) => token.start_point,
+            (Some(token), EventKind::End) => token.end_point,
+            (None, _) => {
+                self.replan = Some(ReplanReason::UnknownToken {
+                    timeline,
+                    token_idx,
+                });
+                return self.replan.as_ref();
+            }
+        };
+
+        let (earliest, latest) = self.window(point);
+        if time + EPSILON < earliest || latest.map(|l| time > l + EPSILON).unwrap_or(false) {
+            self.replan = Some(ReplanReason::OutsideWindow {
+                timeline,
+                token_idx,
+            });
+            return self.replan.as_ref();
+        }
+
+        // Time points that are rigidly connected to this one, such as the end of
+        // a token and the start of the next token on the same timeline, happen now too.
+        let d = &self.plan.distances;
+        let same_time = (0..d.len())
+            .filter(|q| *q != END_OF_TIME)
+            .filter(|q| {
+                d[point][*q].map(|x| x.abs() < EPSILON).unwrap_or(false)
+                    && d[*q][point].map(|x| x.abs() < EPSILON).unwrap_or(false)
+            })
+            .filter(
+                |q| match (self.point_tokens[point], self.point_tokens[*q]) {
+                    (Some((tl1, _, _)), Some((tl2, _, _))) => tl1 == tl2,
+                    _ => false,
+                },
+            )
+            .collect::<Vec<_>>();
+        for q in same_time {
+            self.executed[q] = Some(time);
+        }
+        self.executed[point] = Some(time);
+
+        // A pending event whose time window has become empty cannot be executed.
+        if let Some(event) = self
+            .pending_events()
+            .into_iter()
+            .find(|e| e.latest.map(|l| e.earliest > l + EPSILON).unwrap_or(false))
+        {
+            self.replan = Some(ReplanReason::OutsideWindow {
+                timeline: event.timeline,
+                token_idx: event.token_idx,
+            });
+        }
+
+        self.replan.as_ref()
+    }
+}
@@ -0,0 +1,209 @@
+//! Exporting solutions to other formats.
+//!
+//! A solution can be written as CSV, with one line for each token, or as an
+//! iCalendar file with one event for each token, to be opened in spreadsheets and
+//! calendar tools. Times in a solution have no unit, so the calendar needs the
+//! date and time of time 0 (the epoch) and the length of a time unit.
+
+use crate::benchmark::csv_field;
+use crate::problem::{Solution, SolutionToken};
+
+#[derive(Debug, Clone)]
+pub struct IcalOptions {
+    /// Seconds since 1970-01-01T00:00:00Z at time 0 of the solution.
+    pub epoch: i64,
+    /// Seconds in one time unit of the solution.
+    pub time_unit: f64,
+    /// Include the tokens that come from facts.
+    pub facts: bool,
+}
+
+impl Default for IcalOptions {
+    fn default() -> Self {
+        Self {
+            epoch: 0,
+            time_unit: 60.0,
+            facts: true,
+        }
+    }
+}
+
+/// The solution as CSV, with one line for each token. The dependencies of a token
+/// are the tokens its conditions are linked to, written as `timeline[index] value`
+/// and separated by `;`.
+pub fn to_csv(solution: &Solution) -> String {
+    let mut csv = String::from("timeline,token,value,start,end,duration,fact,dependencies\n");
+    for tl in solution.timelines.iter() {
+        for (idx, token) in tl.tokens.iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                csv_field(&tl.name),
+                idx,
+                csv_field(&token.value),
+                token.start_time,
+                token.end_time,
+                token.end_time - token.start_time,
+                token.fact,
+                csv_field(&dependencies(solution, token).join("; ")),
+            ));
+        }
+    }
+    csv
+}
+
+/// The solution as an iCalendar file, with one event for each token. The events
+/// are stamped with the epoch, so that the same solution gives the same file.
+pub fn to_ical(solution: &Solution, options: &IcalOptions) -> String {
+    let time = |t: f32| ical_time(options.epoch + (t as f64 * options.time_unit).round() as i64);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//SINTEF//paraspace//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for tl in solution.timelines.iter() {
+        for (idx, token) in tl.tokens.iter().enumerate() {
+            if token.fact && !options.facts {
+                continue;
+            }
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!(
+                "UID:{}-{}@paraspace",
+                ical_text(&tl.name).replace(' ', "_"),
+                idx
+            ));
+            lines.push(format!("DTSTAMP:{}", ical_time(options.epoch)));
+            lines.push(format!("DTSTART:{}", time(token.start_time)));
+            lines.push(format!("DTEND:{}", time(token.end_time)));
+            lines.push(format!(
+                "SUMMARY:{}",
+                ical_text(&format!("{} ({})", token.value, tl.name))
+            ));
+            lines.push(format!("CATEGORIES:{}", ical_text(&tl.name)));
+            let dependencies = dependencies(solution, token);
+            if !dependencies.is_empty() {
+                lines.push(format!(
+                    "DESCRIPTION:{}",
+                    ical_text(&format!("Depends on: {}", dependencies.join("\n")))
+                ));
+            }
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = String::new();
+    for line in lines {
+        ics.push_str(&fold(&line));
+        ics.push_str("\r\n");
+    }
+    ics
+}
+
+/// Parse a UTC date and time such as `2024-05-01T08:00:00Z`, or a date such as
+/// `2024-05-01`, into seconds since 1970-01-01T00:00:00Z.
+pub fn parse_epoch(s: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid date \"{}\", expected YYYY-MM-DD[THH:MM:SS[Z]]", s);
+    let (date, time) = match s.split_once('T') {
+        Some((date, time)) => (date, time.strip_suffix('Z').unwrap_or(time)),
+        None => (s, "00:00:00"),
+    };
+    let numbers = |s: &str, sep: char| {
+        s.split(sep)
+            .map(|x| x.parse::<i64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    match (
+        numbers(date, '-')?.as_slice(),
+        numbers(time, ':')?.as_slice(),
+    ) {
+        ([y, m, d], [hh, mm, ss])
+            if (1..=12).contains(m)
+                && (1..=31).contains(d)
+                && (0..24).contains(hh)
+                && (0..60).contains(mm)
+                && (0..61).contains(ss) =>
+        {
+            Ok(days_from_civil(*y, *m, *d) * 86400 + hh * 3600 + mm * 60 + ss)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn dependencies(solution: &Solution, token: &SolutionToken) -> Vec<String> {
+    token
+        .conditions
+        .iter()
+        .map(|c| {
+            let value = solution
+                .timelines
+                .iter()
+                .find(|tl| tl.name == c.timeline)
+                .and_then(|tl| tl.tokens.get(c.token_idx))
+                .map(|t| t.value.as_str())
+                .unwrap_or("?");
+            format!("{}[{}] {}", c.timeline, c.token_idx, value)
+        })
+        .collect()
+}
+
+/// A UTC date and time in the iCalendar format, such as `20240501T080000Z`.
+fn ical_time(seconds: i64) -> String {
+    let (days, secs) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (y, m, d) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        y,
+        m,
+        d,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn ical_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Lines longer than 75 bytes are continued on lines starting with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded
+}
+
+// Conversion between dates and days since 1970-01-01 in the proleptic Gregorian
+// calendar, from http://howardhinnant.github.io/date_algorithms.html.
+
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
@@ -0,0 +1,200 @@
+//! C API for embedding the planner in other languages.
+//!
+//! The problem and the settings are given as JSON, in the same format as for
+//! `solve_json`, and the result is returned as the JSON response described in
+//! `jsonapi`. Objects created by the library must be freed with the matching
+//! `paraspace_*_free` function. The header is in `include/paraspace.h`.
+
+use crate::jsonapi::{solve_json, solve_problem, SolveResponse};
+use crate::problem::Problem;
+use crate::transitionsolver::SolverSettings;
+use serde::de::DeserializeOwned;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A parsed planning problem.
+pub struct ParaspaceProblem(Problem);
+
+/// Solver settings.
+pub struct ParaspaceSettings(SolverSettings);
+
+/// Flag for stopping a solver that is running in another thread.
+pub struct ParaspaceCancelToken(AtomicBool);
+
+fn into_c_string(s: String) -> *mut c_char {
+    // JSON and error messages from serde do not contain NUL characters.
+    CString::new(s).unwrap_or_default().into_raw()
+}
+
+unsafe fn set_error(error: *mut *mut c_char, message: String) {
+    if !error.is_null() {
+        *error = into_c_string(message);
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err("null string".to_string());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| format!("invalid UTF-8: {}", e))
+}
+
+unsafe fn parse_json<T: DeserializeOwned>(
+    json: *const c_char,
+    error: *mut *mut c_char,
+) -> Option<T> {
+    let result =
+        read_str(json).and_then(|json| serde_json::from_str::<T>(json).map_err(|e| e.to_string()));
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            set_error(error, e);
+            None
+        }
+    }
+}
+
+fn response_json(response: &SolveResponse) -> *mut c_char {
+    into_c_string(serde_json::to_string(response).unwrap())
+}
+
+/// The version of the library, as a static string that must not be freed.
+#[no_mangle]
+pub extern "C" fn paraspace_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Parse a problem from JSON. Returns null if the JSON is not a valid problem.
+///
+/// # Safety
+/// `json` must be a NUL-terminated string. If `error` is not null, a message is
+/// stored in it on failure, which must be freed with `paraspace_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_problem_from_json(
+    json: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut ParaspaceProblem {
+    match parse_json::<Problem>(json, error) {
+        Some(problem) => Box::into_raw(Box::new(ParaspaceProblem(problem))),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `problem` must be null or come from `paraspace_problem_from_json`, and must not
+/// be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_problem_free(problem: *mut ParaspaceProblem) {
+    if !problem.is_null() {
+        drop(Box::from_raw(problem));
+    }
+}
+
+/// Parse solver settings from JSON. Missing fields take their default values, so
+/// `"{}"` gives the default settings. Returns null if the JSON is not valid settings.
+///
+/// # Safety
+/// As for `paraspace_problem_from_json`.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_settings_from_json(
+    json: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut ParaspaceSettings {
+    match parse_json::<SolverSettings>(json, error) {
+        Some(settings) => Box::into_raw(Box::new(ParaspaceSettings(settings))),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `settings` must be null or come from `paraspace_settings_from_json`, and must not
+/// be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_settings_free(settings: *mut ParaspaceSettings) {
+    if !settings.is_null() {
+        drop(Box::from_raw(settings));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn paraspace_cancel_token_new() -> *mut ParaspaceCancelToken {
+    Box::into_raw(Box::new(ParaspaceCancelToken(AtomicBool::new(false))))
+}
+
+/// Stop the solver runs that use this token. This can be called from any thread.
+///
+/// # Safety
+/// `token` must come from `paraspace_cancel_token_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_cancel(token: *const ParaspaceCancelToken) {
+    if let Some(token) = token.as_ref() {
+        token.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// # Safety
+/// `token` must be null or come from `paraspace_cancel_token_new`, and no solver
+/// may be using it.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_cancel_token_free(token: *mut ParaspaceCancelToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
+}
+
+/// Validate and solve the problem, and return the JSON response with the solution
+/// or the error. `settings` and `cancel` may be null, for the default settings and
+/// no cancellation. The result must be freed with `paraspace_string_free`.
+///
+/// # Safety
+/// The pointers must be null or come from the matching constructors.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_solve(
+    problem: *const ParaspaceProblem,
+    settings: *const ParaspaceSettings,
+    cancel: *const ParaspaceCancelToken,
+) -> *mut c_char {
+    let problem = match problem.as_ref() {
+        Some(problem) => &problem.0,
+        None => {
+            let response =
+                SolveResponse::error("InvalidProblem", "null problem".to_string(), vec![]);
+            return response_json(&response);
+        }
+    };
+    let default_settings = SolverSettings::default();
+    let settings = settings.as_ref().map(|s| &s.0).unwrap_or(&default_settings);
+    let cancel = cancel.as_ref().map(|c| &c.0);
+
+    response_json(&solve_problem(problem, settings, cancel))
+}
+
+/// Solve a JSON request, as `solve_json`. The result must be freed with
+/// `paraspace_string_free`.
+///
+/// # Safety
+/// `request` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_solve_json(request: *const c_char) -> *mut c_char {
+    let request = match read_str(request) {
+        Ok(request) => request,
+        Err(e) => return response_json(&SolveResponse::error("InvalidJson", e, vec![])),
+    };
+    into_c_string(solve_json(request))
+}
+
+/// Free a string returned by the library.
+///
+/// # Safety
+/// `s` must be null or a string returned by this library, and must not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn paraspace_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
@@ -0,0 +1,491 @@
+//! Flexible plans.
+//!
+//! A `Solution` has fixed start and end times taken from a single model. A
+//! `FlexibleSolution` keeps the tokens and causal links of the solution, and
+//! replaces the fixed times by the minimal Simple Temporal Network (STN) that is
+//! implied by the problem for the same choice of tokens and links. An executive
+//! can then dispatch the plan using the slack in the network.
+//!
+//! Choices that are not represented by causal links are fixed as in the solution:
+//! the order between tokens that use the same capacity resource, and the side
+//! chosen for negative conditions. Tokens that change or test reservoir or numeric
+//! timelines keep their times from the solution.
+
+use serde::{Deserialize, Serialize};
+
+use crate::problem::{
+    Condition, Problem, Solution, SolutionCondition, SolutionToken, TemporalRelationship, Timeline,
+    Token, TokenTime,
+};
+
+/// Time point 0 is the start of the plan (time zero).
+pub const ORIGIN: usize = 0;
+/// Time point 1 is the end of the plan, where all timelines have reached their goals.
+pub const END_OF_TIME: usize = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FlexibleSolution {
+    pub timelines: Vec<FlexibleTimeline>,
+    /// The minimal network: `distances[i][j]` is the largest possible value of
+    /// `t_j - t_i`, or `None` if there is no upper bound.
+    pub distances: Vec<Vec<Option<f32>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FlexibleTimeline {
+    pub name: String,
+    pub tokens: Vec<FlexibleToken>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FlexibleToken {
+    pub value: String,
+    pub start_point: usize,
+    pub end_point: usize,
+    /// Earliest and latest start time.
+    pub start: Bounds,
+    /// Earliest and latest end time.
+    pub end: Bounds,
+    pub conditions: Vec<SolutionCondition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub lower: f32,
+    pub upper: Option<f32>,
+}
+
+impl FlexibleSolution {
+    /// Compute the flexible plan for the tokens and links of a solution to the given
+    /// problem. Returns `None` if the solution is not consistent with the problem.
+    pub fn from_solution(problem: &Problem, solution: &Solution) -> Option<FlexibleSolution> {
+        let mut stn = Stn::new(2);
+
+        // Time points for the start and end of each token.
+        let mut points = Vec::new();
+        for tl in solution.timelines.iter() {
+            points.push(
+                tl.tokens
+                    .iter()
+                    .map(|_| (stn.add_point(), stn.add_point()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        for (tl_idx, tl) in solution.timelines.iter().enumerate() {
+            let tl_spec = problem.timelines.iter().find(|t| t.name == tl.name)?;
+            let facts_only = tl_spec.token_types.is_empty();
+            let static_tokens = static_tokens_of(tl_spec, &tl.tokens);
+
+            for (token_idx, token) in tl.tokens.iter().enumerate() {
+                let (start, end) = points[tl_idx][token_idx];
+                stn.add_bounds(ORIGIN, start, 0.0, None);
+                stn.add_bounds(start, end, 0.0, None);
+                stn.add_bounds(end, END_OF_TIME, 0.0, None);
+
+                if token.fact {
+                    let fact = static_tokens[token_idx]?;
+                    if let TokenTime::Fact(fixed_start, fixed_end) = fact.const_time {
+                        if let Some(t) = fixed_start {
+                            stn.add_bounds(ORIGIN, start, t as f32, Some(t as f32));
+                        }
+                        if let Some(t) = fixed_end {
+                            stn.add_bounds(ORIGIN, end, t as f32, Some(t as f32));
+                        }
+                    }
+                } else {
+                    let token_type = tl_spec
+                        .token_types
+                        .iter()
+                        .find(|t| t.value == token.value)?;
+                    let (min, max) = token_type.duration_limits;
+                    stn.add_bounds(start, end, min as f32, max.map(|m| m as f32));
+
+                    let affects_levels = !token_type.resource_effects.is_empty()
+                        || !token_type.numeric_rates.is_empty()
+                        || !token_type.numeric_conditions.is_empty();
+                    if affects_levels {
+                        stn.add_bounds(ORIGIN, start, token.start_time, Some(token.start_time));
+                        stn.add_bounds(ORIGIN, end, token.end_time, Some(token.end_time));
+                    }
+                }
+
+                if !facts_only {
+                    // The tokens of a timeline follow each other, and the last one
+                    // lasts until the end of time.
+                    let next = points[tl_idx]
+                        .get(token_idx + 1)
+                        .map(|(next_start, _)| *next_start)
+                        .unwrap_or(END_OF_TIME);
+                    stn.add_bounds(end, next, 0.0, Some(0.0));
+                }
+
+                for cond in token.conditions.iter() {
+                    let target_tl = solution
+                        .timelines
+                        .iter()
+                        .position(|t| t.name == cond.timeline)?;
+                    let target = *points[target_tl].get(cond.token_idx)?;
+                    // Solutions that leave out the relation get it from the condition
+                    // in the problem that the target token satisfies.
+                    let relation = match cond.temporal_relationship {
+                        Some(relation) => relation,
+                        None => {
+                            let target_value =
+                                &solution.timelines[target_tl].tokens[cond.token_idx].value;
+                            spec_conditions(tl_spec, token, static_tokens[token_idx])
+                                .iter()
+                                .flatten()
+                                .find(|c| {
+                                    !c.is_negative()
+                                        && c.timeline_ref == cond.timeline
+                                        && &c.value == target_value
+                                })?
+                                .temporal_relationship
+                        }
+                    };
+                    stn.add_relation(relation, (start, end), target);
+                }
+
+                for cond in spec_conditions(tl_spec, token, static_tokens[token_idx])
+                    .iter()
+                    .flatten()
+                    .filter(|c| c.is_negative())
+                {
+                    let target_tl = solution
+                        .timelines
+                        .iter()
+                        .position(|t| t.name == cond.timeline_ref)?;
+                    for (target_idx, target) in
+                        solution.timelines[target_tl].tokens.iter().enumerate()
+                    {
+                        if target.value != cond.value
+                            || (target_tl, target_idx) == (tl_idx, token_idx)
+                        {
+                            continue;
+                        }
+
+                        let (target_start, target_end) = points[target_tl][target_idx];
+                        // Keep the token on the same side of the target token as in the solution.
+                        let (a, b) = match cond.temporal_relationship {
+                            TemporalRelationship::NotOverlapping => {
+                                if token.end_time <= target.start_time {
+                                    (end, target_start)
+                                } else {
+                                    (target_end, start)
+                                }
+                            }
+                            _ => {
+                                if token.start_time < target.start_time {
+                                    (start, target_start)
+                                } else {
+                                    (target_end, end)
+                                }
+                            }
+                        };
+                        stn.add_bounds(a, b, 0.0, None);
+                    }
+                }
+            }
+        }
+
+        // Users of the same capacity resource keep their order from the solution.
+        let mut users = Vec::new();
+        for (tl_idx, tl) in solution.timelines.iter().enumerate() {
+            for (token_idx, token) in tl.tokens.iter().enumerate() {
+                for cond in token.conditions.iter().filter(|c| c.amount > 0) {
+                    users.push(((cond.timeline.as_str(), cond.token_idx), tl_idx, token_idx));
+                }
+            }
+        }
+        for (i, (resource1, tl1, token1)) in users.iter().enumerate() {
+            for (resource2, tl2, token2) in users[i + 1..].iter() {
+                if resource1 != resource2 {
+                    continue;
+                }
+                let t1 = &solution.timelines[*tl1].tokens[*token1];
+                let t2 = &solution.timelines[*tl2].tokens[*token2];
+                if t1.end_time <= t2.start_time {
+                    stn.add_bounds(points[*tl1][*token1].1, points[*tl2][*token2].0, 0.0, None);
+                } else if t2.end_time <= t1.start_time {
+                    stn.add_bounds(points[*tl2][*token2].1, points[*tl1][*token1].0, 0.0, None);
+                }
+            }
+        }
+
+        Self::from_stn(solution, &points, stn)
+    }
+
+    /// Compute a flexible plan from a solution without using the problem. The plan
+    /// only allows delays: each time point can happen later than in the solution,
+    /// as long as the order and the gaps between linked time points are kept.
+    pub fn from_fixed_times(solution: &Solution) -> Option<FlexibleSolution> {
+        let mut stn = Stn::new(2);
+        let mut points = Vec::new();
+        for tl in solution.timelines.iter() {
+            points.push(
+                tl.tokens
+                    .iter()
+                    .map(|_| (stn.add_point(), stn.add_point()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let mut planned = vec![0.0; 2];
+        for tl in solution.timelines.iter() {
+            for token in tl.tokens.iter() {
+                planned.push(token.start_time);
+                planned.push(token.end_time);
+            }
+        }
+        planned[END_OF_TIME] = solution.end_of_time;
+
+        // Keep the planned gap between two time points, or keep them together if
+        // they happen at the same time.
+        let keep_gap = |stn: &mut Stn, a: usize, b: usize| {
+            let gap = planned[b] - planned[a];
+            if gap.abs() < 1e-5 {
+                stn.add_bounds(a, b, 0.0, Some(0.0));
+            } else if gap > 0.0 {
+                stn.add_bounds(a, b, gap, None);
+            } else {
+                stn.add_bounds(b, a, -gap, None);
+            }
+        };
+
+        for (tl_idx, tl) in solution.timelines.iter().enumerate() {
+            for (token_idx, token) in tl.tokens.iter().enumerate() {
+                let (start, end) = points[tl_idx][token_idx];
+                stn.add_bounds(ORIGIN, start, token.start_time, None);
+                stn.add_bounds(start, end, token.end_time - token.start_time, None);
+                stn.add_bounds(end, END_OF_TIME, 0.0, None);
+
+                if let Some((next_start, _)) = points[tl_idx].get(token_idx + 1) {
+                    keep_gap(&mut stn, end, *next_start);
+                }
+
+                for cond in token.conditions.iter() {
+                    let target_tl = solution
+                        .timelines
+                        .iter()
+                        .position(|t| t.name == cond.timeline)?;
+                    let (target_start, target_end) = *points[target_tl].get(cond.token_idx)?;
+                    for a in [target_start, target_end] {
+                        for b in [start, end] {
+                            keep_gap(&mut stn, a, b);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self::from_stn(solution, &points, stn)
+    }
+
+    fn from_stn(
+        solution: &Solution,
+        points: &[Vec<(usize, usize)>],
+        stn: Stn,
+    ) -> Option<FlexibleSolution> {
+        let distances = stn.minimal()?;
+        let bounds = |point: usize| Bounds {
+            lower: distances[point][ORIGIN].map(|d| -d).unwrap_or(0.0),
+            upper: distances[ORIGIN][point],
+        };
+
+        let timelines = solution
+            .timelines
+            .iter()
+            .zip(points.iter())
+            .map(|(tl, points)| FlexibleTimeline {
+                name: tl.name.clone(),
+                tokens: tl
+                    .tokens
+                    .iter()
+                    .zip(points.iter())
+                    .map(|(token, (start, end))| FlexibleToken {
+                        value: token.value.clone(),
+                        start_point: *start,
+                        end_point: *end,
+                        start: bounds(*start),
+                        end: bounds(*end),
+                        conditions: token.conditions.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Some(FlexibleSolution {
+            timelines,
+            distances,
+        })
+    }
+
+    /// The smallest and largest possible value of `t_j - t_i`.
+    pub fn difference_bounds(&self, i: usize, j: usize) -> (Option<f32>, Option<f32>) {
+        (self.distances[j][i].map(|d| -d), self.distances[i][j])
+    }
+}
+
+pub(crate) fn spec_conditions<'a>(
+    tl_spec: &'a Timeline,
+    token: &SolutionToken,
+    static_token: Option<&'a Token>,
+) -> &'a [Vec<Condition>] {
+    if token.fact {
+        static_token.map(|t| t.conditions.as_slice()).unwrap_or(&[])
+    } else {
+        tl_spec
+            .token_types
+            .iter()
+            .find(|t| t.value == token.value)
+            .map(|t| t.conditions.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// The static token of each fact token of a solution timeline. Several facts can
+/// have the same value, such as time windows, so each fact token is matched to a
+/// different static token, preferring the one with the same fixed times.
+pub(crate) fn static_tokens_of<'a>(
+    tl_spec: &'a Timeline,
+    tokens: &[SolutionToken],
+) -> Vec<Option<&'a Token>> {
+    let same_time = |fixed: Option<usize>, time: f32| {
+        fixed
+            .map(|t| (t as f32 - time).abs() < 1e-3)
+            .unwrap_or(true)
+    };
+    let mut used = vec![false; tl_spec.static_tokens.len()];
+    tokens
+        .iter()
+        .map(|token| {
+            if !token.fact {
+                return None;
+            }
+            let candidates = tl_spec
+                .static_tokens
+                .iter()
+                .enumerate()
+                .filter(|(idx, t)| !used[*idx] && t.value == token.value)
+                .collect::<Vec<_>>();
+            let (idx, fact) = candidates
+                .iter()
+                .find(|(_, t)| match t.const_time {
+                    TokenTime::Fact(start, end) => {
+                        same_time(start, token.start_time) && same_time(end, token.end_time)
+                    }
+                    TokenTime::Goal => false,
+                })
+                .or_else(|| candidates.first())
+                .copied()?;
+            used[idx] = true;
+            Some(fact)
+        })
+        .collect()
+}
+
+/// A Simple Temporal Network stored as a distance matrix, where `d[i][j]`
+/// is an upper bound on `t_j - t_i`.
+struct Stn {
+    d: Vec<Vec<Option<f64>>>,
+}
+
+impl Stn {
+    fn new(n: usize) -> Self {
+        let mut stn = Stn { d: Vec::new() };
+        for _ in 0..n {
+            stn.add_point();
+        }
+        stn
+    }
+
+    fn add_point(&mut self) -> usize {
+        for row in self.d.iter_mut() {
+            row.push(None);
+        }
+        let n = self.d.len() + 1;
+        self.d.push(vec![None; n]);
+        self.d[n - 1][n - 1] = Some(0.0);
+        n - 1
+    }
+
+    fn add_edge(&mut self, i: usize, j: usize, w: f64) {
+        if self.d[i][j].map(|old| w < old).unwrap_or(true) {
+            self.d[i][j] = Some(w);
+        }
+    }
+
+    /// `lower <= t_j - t_i <= upper`
+    fn add_bounds(&mut self, i: usize, j: usize, lower: f32, upper: Option<f32>) {
+        self.add_edge(j, i, -lower as f64);
+        if let Some(upper) = upper {
+            self.add_edge(i, j, upper as f64);
+        }
+    }
+
+    /// Add the constraints of a causal link from the token `(start, end)` to the target token.
+    fn add_relation(
+        &mut self,
+        relation: TemporalRelationship,
+        (start, end): (usize, usize),
+        (target_start, target_end): (usize, usize),
+    ) {
+        match relation {
+            TemporalRelationship::MetBy | TemporalRelationship::MetByTransitionFrom => {
+                self.add_bounds(target_end, start, 0.0, Some(0.0))
+            }
+            TemporalRelationship::Starts => self.add_bounds(target_start, start, 0.0, Some(0.0)),
+            TemporalRelationship::StartsAfter => self.add_bounds(target_start, start, 0.0, None),
+            TemporalRelationship::Cover => {
+                self.add_bounds(target_start, start, 0.0, None);
+                self.add_bounds(end, target_end, 0.0, None);
+            }
+            TemporalRelationship::StartPrecond => {
+                self.add_bounds(target_start, start, 1.0, None);
+                self.add_bounds(start, target_end, 0.0, None);
+            }
+            TemporalRelationship::StartEffect => {
+                self.add_bounds(target_start, start, 0.0, None);
+                self.add_bounds(start, target_end, 1.0, None);
+            }
+            TemporalRelationship::Equal => {
+                self.add_bounds(start, target_start, 0.0, Some(0.0));
+                self.add_bounds(end, target_end, 0.0, Some(0.0));
+            }
+            TemporalRelationship::Meets => self.add_bounds(end, target_start, 0.0, Some(0.0)),
+            TemporalRelationship::NotOverlapping | TemporalRelationship::NotDuring => {}
+        }
+    }
+
+    /// All-pairs shortest paths (Floyd-Warshall). Returns `None` if the network
+    /// has a negative cycle, i.e. it is inconsistent.
+    fn minimal(mut self) -> Option<Vec<Vec<Option<f32>>>> {
+        let n = self.d.len();
+        for k in 0..n {
+            for i in 0..n {
+                let dik = match self.d[i][k] {
+                    Some(x) => x,
+                    None => continue,
+                };
+                for j in 0..n {
+                    if let Some(dkj) = self.d[k][j] {
+                        self.add_edge(i, j, dik + dkj);
+                    }
+                }
+            }
+        }
+
+        const EPSILON: f64 = 1e-4;
+        if (0..n).any(|i| self.d[i][i].unwrap() < -EPSILON) {
+            return None;
+        }
+
+        Some(
+            self.d
+                .into_iter()
+                .map(|row| row.into_iter().map(|d| d.map(|d| d as f32)).collect())
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,235 @@
+//! Gantt charts of solutions.
+//!
+//! A solution is drawn as a standalone SVG image, or an HTML page containing it,
+//! with one row for each timeline and a bar for each token. Hovering over a bar
+//! shows the value and times of the token. The causal links of the tokens, given by
+//! their `SolutionCondition`s, can be drawn as arrows from the token that is
+//! depended on to the start of the token that depends on it.
+
+use crate::problem::Solution;
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct GanttOptions {
+    /// Width of the image in pixels.
+    pub width: f32,
+    /// Height of each timeline row in pixels.
+    pub row_height: f32,
+    /// Draw arrows for the conditions of the tokens.
+    pub links: bool,
+    /// Title above the chart.
+    pub title: Option<String>,
+}
+
+impl Default for GanttOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200.0,
+            row_height: 24.0,
+            links: false,
+            title: None,
+        }
+    }
+}
+
+const COLORS: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac",
+];
+const CHAR_WIDTH: f32 = 7.0;
+const MARGIN: f32 = 10.0;
+const AXIS_HEIGHT: f32 = 24.0;
+const TITLE_HEIGHT: f32 = 28.0;
+
+/// The solution as an SVG image.
+pub fn to_svg(solution: &Solution, options: &GanttOptions) -> String {
+    let tokens = solution.timelines.iter().flat_map(|tl| tl.tokens.iter());
+    let start = tokens.clone().map(|t| t.start_time).fold(0.0f32, f32::min);
+    let end = tokens
+        .map(|t| t.end_time)
+        .fold(solution.end_of_time, f32::max);
+    let span = if end > start { end - start } else { 1.0 };
+
+    let label_width = solution
+        .timelines
+        .iter()
+        .map(|tl| tl.name.chars().count())
+        .max()
+        .unwrap_or(0) as f32
+        * CHAR_WIDTH
+        + 2.0 * MARGIN;
+    let top = MARGIN
+        + if options.title.is_some() {
+            TITLE_HEIGHT
+        } else {
+            0.0
+        };
+    let chart_width = (options.width - label_width - MARGIN).max(100.0);
+    let width = label_width + chart_width + MARGIN;
+    let height = top + solution.timelines.len() as f32 * options.row_height + AXIS_HEIGHT + MARGIN;
+    let x = |time: f32| label_width + (time - start) / span * chart_width;
+    let row_y = |row: usize| top + row as f32 * options.row_height;
+    let bar_height = options.row_height * 0.7;
+    let bar_y = |row: usize| row_y(row) + (options.row_height - bar_height) / 2.0;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+        w = width,
+        h = height
+    );
+    svg.push_str(concat!(
+        r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse">"#,
+        r##"<path d="M 0 0 L 10 5 L 0 10 z" fill="#444"/></marker></defs>"##,
+        "\n"
+    ));
+    if let Some(title) = options.title.as_ref() {
+        let _ = writeln!(
+            svg,
+            r#"<text class="title" x="{}" y="{}" font-size="16">{}</text>"#,
+            MARGIN,
+            MARGIN + 16.0,
+            escape(title)
+        );
+    }
+
+    // Rows with the timeline names, and the time axis below them.
+    let chart_bottom = row_y(solution.timelines.len());
+    for (row, tl) in solution.timelines.iter().enumerate() {
+        if row % 2 == 1 {
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#f4f4f4"/>"##,
+                label_width,
+                row_y(row),
+                chart_width,
+                options.row_height
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text class="timeline" x="{}" y="{}" dominant-baseline="middle">{}</text>"#,
+            MARGIN,
+            row_y(row) + options.row_height / 2.0,
+            escape(&tl.name)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{x1}" y1="{y}" x2="{x2}" y2="{y}" stroke="#444"/>"##,
+        x1 = label_width,
+        x2 = label_width + chart_width,
+        y = chart_bottom
+    );
+    let step = tick_step(span);
+    let mut tick = (start / step).ceil() * step;
+    while tick <= end + step * 1e-3 {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x}" y1="{y1}" x2="{x}" y2="{y2}" stroke="#ddd"/><text x="{x}" y="{ty}" text-anchor="middle">{t}</text>"##,
+            x = x(tick),
+            y1 = top,
+            y2 = chart_bottom + 4.0,
+            ty = chart_bottom + 16.0,
+            t = tick
+        );
+        tick += step;
+    }
+
+    // The tokens, with the value written inside the bars that are wide enough.
+    for (row, tl) in solution.timelines.iter().enumerate() {
+        for token in tl.tokens.iter() {
+            let x0 = x(token.start_time);
+            let bar_width = (x(token.end_time) - x0).max(1.0);
+            let _ = writeln!(
+                svg,
+                r#"<rect class="token" x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}"><title>{} [{}, {}]</title></rect>"#,
+                x0,
+                bar_y(row),
+                bar_width,
+                bar_height,
+                color(&token.value),
+                if token.fact { "#000" } else { "none" },
+                escape(&token.value),
+                token.start_time,
+                token.end_time
+            );
+            if token.value.chars().count() as f32 * CHAR_WIDTH + 4.0 < bar_width {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" dominant-baseline="middle" fill="white" pointer-events="none">{}</text>"#,
+                    x0 + 2.0,
+                    row_y(row) + options.row_height / 2.0,
+                    escape(&token.value)
+                );
+            }
+        }
+    }
+
+    if options.links {
+        for (row, tl) in solution.timelines.iter().enumerate() {
+            for token in tl.tokens.iter() {
+                for cond in token.conditions.iter() {
+                    let target = solution
+                        .timelines
+                        .iter()
+                        .enumerate()
+                        .find(|(_, t)| t.name == cond.timeline)
+                        .and_then(|(r, t)| t.tokens.get(cond.token_idx).map(|t| (r, t)));
+                    let (target_row, target) = match target {
+                        Some(t) => t,
+                        None => continue,
+                    };
+                    let _ = writeln!(
+                        svg,
+                        r##"<line class="link" x1="{}" y1="{}" x2="{}" y2="{}" stroke="#444" stroke-opacity="0.6" marker-end="url(#arrow)"/>"##,
+                        x((target.start_time + target.end_time) / 2.0),
+                        row_y(target_row) + options.row_height / 2.0,
+                        x(token.start_time),
+                        row_y(row) + options.row_height / 2.0
+                    );
+                }
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The solution as an HTML page with the SVG image.
+pub fn to_html(solution: &Solution, options: &GanttOptions) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(options.title.as_deref().unwrap_or("Plan")),
+        to_svg(solution, options)
+    )
+}
+
+/// A color for each token value, the same in every chart.
+fn color(value: &str) -> &'static str {
+    // FNV-1a, which does not depend on the Rust version like `DefaultHasher` may.
+    let hash = value.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    COLORS[(hash % COLORS.len() as u64) as usize]
+}
+
+/// A round distance between the ticks of the time axis, giving about ten ticks.
+fn tick_step(span: f32) -> f32 {
+    let rough = span / 10.0;
+    let magnitude = 10f32.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
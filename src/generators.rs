@@ -0,0 +1,499 @@
+//! Generators for the benchmark problem families.
+//!
+//! These are ports of the Python scripts in `src/frontend/`, which write an older
+//! JSON format with object groups. Here, a condition on any member of a group
+//! becomes a condition with one alternative for each member. The instances are
+//! named as in the earlier benchmarks, e.g. `carbonara_1p_5c`, `ceramic_2m_4j` and
+//! `goac_3pics_2wind`, and `by_name` generates an instance from its name.
+
+use std::collections::HashMap;
+
+use crate::problem::{
+    Condition, Problem, TemporalRelationship, Timeline, Token, TokenTime, TokenType,
+};
+
+/// The carbonara cooking problem with `plates` plates that are needed for heating
+/// water and oil and for cooking each of the `n_carbonaras` dishes.
+pub fn carbonara(plates: usize, n_carbonaras: usize) -> Problem {
+    let mut p = Builder::default();
+    for _ in 0..plates {
+        p.resource("Plate", None, 1);
+    }
+
+    let water = p.timeline("Water", None);
+    p.state(
+        water,
+        "Heating",
+        (10, Some(10)),
+        vec![use_resource(any("Plate"), 1)],
+    );
+    p.state(
+        water,
+        "HotWater",
+        (1, None),
+        vec![transition_from("Heating")],
+    );
+
+    let oil = p.timeline("Oil", None);
+    p.state(
+        oil,
+        "Heating",
+        (10, Some(10)),
+        vec![use_resource(any("Plate"), 1)],
+    );
+    p.state(oil, "HotOil", (1, None), vec![transition_from("Heating")]);
+
+    for i in 0..n_carbonaras {
+        let spaghetti = p.timeline("Spaghetti", Some(format!("spaghetti_{}", i)));
+        p.state(
+            spaghetti,
+            "Cooking",
+            (5, Some(5)),
+            vec![during(any("Water"), "HotWater", 0)],
+        );
+        p.state(
+            spaghetti,
+            "Cooked",
+            (1, None),
+            vec![transition_from("Cooking")],
+        );
+
+        let lardon = p.timeline("Lardon", Some(format!("lardon_{}", i)));
+        p.state(
+            lardon,
+            "Cooking",
+            (5, Some(5)),
+            vec![during(any("Oil"), "HotOil", 0)],
+        );
+        p.state(
+            lardon,
+            "Cooked",
+            (1, None),
+            vec![transition_from("Cooking")],
+        );
+
+        let eggs = p.timeline("Eggs", Some(format!("eggs_{}", i)));
+        p.state(eggs, "Beating", (5, Some(5)), vec![]);
+        p.state(eggs, "Beaten", (1, None), vec![transition_from("Beating")]);
+
+        let carbonara = p.timeline("Carbonara", Some(format!("carbonara_{}", i)));
+        p.state(
+            carbonara,
+            "Cooking",
+            (3, Some(3)),
+            vec![
+                starts_after(object(&format!("spaghetti_{}", i)), "Cooked"),
+                starts_after(object(&format!("lardon_{}", i)), "Cooked"),
+                starts_after(object(&format!("eggs_{}", i)), "Beaten"),
+                use_resource(any("Plate"), 1),
+            ],
+        );
+        p.state(
+            carbonara,
+            "Cooked",
+            (1, None),
+            vec![transition_from("Cooking")],
+        );
+        p.state(
+            carbonara,
+            "Eating",
+            (5, Some(5)),
+            vec![transition_from("Cooked")],
+        );
+        p.state(
+            carbonara,
+            "Eaten",
+            (1, None),
+            vec![transition_from("Eating")],
+        );
+
+        p.goal(&format!("carbonara_{}", i), "Eaten");
+    }
+
+    p.finish()
+}
+
+/// The ceramic production problem with `n_kilns` kilns that share the electricity,
+/// and `n_pieces` pieces that are baked, treated, assembled in pairs into structures
+/// and baked again.
+pub fn ceramic(n_kilns: usize, n_pieces: usize) -> Problem {
+    let mut p = Builder::default();
+    p.resource("Electricity", None, 1);
+
+    for kiln_idx in 0..n_kilns {
+        let name = format!("kiln_{}", kiln_idx);
+        let kiln = p.timeline("Kiln", Some(name.clone()));
+        p.state(kiln, "Ready", (5, None), vec![transition_from("Fire")]);
+        p.state(
+            kiln,
+            "Fire",
+            (20, Some(20)),
+            vec![
+                transition_from("Ready"),
+                use_resource(any("Electricity"), 1),
+            ],
+        )
+        .capacity = 2;
+        p.fact(&name, "Ready", None, None);
+    }
+
+    let piece_param_types = [(5, 2), (8, 3), (11, 1)];
+    for piece_idx in 0..n_pieces {
+        let (bake_time, treat_time) = piece_param_types[piece_idx % piece_param_types.len()];
+        let name = format!("piece_{}", piece_idx);
+        let piece = p.timeline("Piece", Some(name.clone()));
+        p.state(
+            piece,
+            "Baking",
+            (bake_time, Some(bake_time)),
+            vec![during(any("Kiln"), "Fire", 1)],
+        );
+        p.state(piece, "Baked", (1, None), vec![transition_from("Baking")]);
+        p.state(
+            piece,
+            "Treating",
+            (treat_time, Some(treat_time)),
+            vec![transition_from("Baked")],
+        );
+        p.state(
+            piece,
+            "Treated",
+            (1, None),
+            vec![transition_from("Treating")],
+        );
+
+        // A piece that is not part of a structure only needs to be baked.
+        if piece_idx >= 2 * (n_pieces / 2) {
+            p.goal(&name, "Baked");
+        }
+    }
+
+    for structure_idx in 0..n_pieces / 2 {
+        let name = format!("structure_{}", structure_idx);
+        let structure = p.timeline("Structure", Some(name.clone()));
+        p.state(
+            structure,
+            "Assembling",
+            (1, Some(1)),
+            vec![
+                during(
+                    object(&format!("piece_{}", 2 * structure_idx)),
+                    "Treated",
+                    0,
+                ),
+                during(
+                    object(&format!("piece_{}", 2 * structure_idx + 1)),
+                    "Treated",
+                    0,
+                ),
+            ],
+        );
+        p.state(
+            structure,
+            "Assembled",
+            (1, None),
+            vec![transition_from("Assembling")],
+        );
+        p.state(
+            structure,
+            "Baking",
+            (3, Some(3)),
+            vec![during(any("Kiln"), "Fire", 1), transition_from("Assembled")],
+        );
+        p.state(
+            structure,
+            "Baked",
+            (1, None),
+            vec![transition_from("Baking")],
+        );
+        p.goal(&name, "Baked");
+    }
+
+    p.finish()
+}
+
+/// The GOAC rover problem: take `n_pics` pictures at different locations and
+/// download them through the antenna during the first `n_windows` visibility windows.
+pub fn goac(n_pics: usize, n_windows: usize) -> Problem {
+    const TIME_WINDOWS: [(usize, usize); 5] = [
+        (280, 4000),
+        (8000, 15000),
+        (20000, 25000),
+        (30000, 35000),
+        (40000, 45000),
+    ];
+    const N_LOCATIONS: usize = 10;
+    const DOWNLOAD_TIME: usize = 6 * 40;
+    const TAKE_PIC_TIME: usize = 50;
+    const EDGES: [(usize, usize, usize); 21] = [
+        (0, 1, 100),
+        (0, 2, 200),
+        (0, 4, 100),
+        (0, 5, 200),
+        (0, 9, 300),
+        (1, 2, 100),
+        (1, 6, 200),
+        (2, 3, 100),
+        (2, 7, 200),
+        (2, 8, 300),
+        (3, 4, 200),
+        (3, 7, 100),
+        (3, 8, 200),
+        (4, 5, 100),
+        (4, 9, 200),
+        (5, 6, 100),
+        (5, 7, 200),
+        (5, 9, 100),
+        (6, 7, 100),
+        (7, 8, 100),
+        (8, 9, 200),
+    ];
+
+    let mut p = Builder::default();
+    p.resource("Antenna", Some("Antenna".to_string()), 1);
+    for (start, end) in TIME_WINDOWS.iter().take(n_windows) {
+        p.fact("Visibility", "Available", Some(*start), Some(*end));
+    }
+
+    let loc = p.timeline("Location", Some("loc".to_string()));
+    p.fact("loc", "At(0)", Some(0), None);
+    for l in 0..N_LOCATIONS {
+        p.state(loc, &format!("At({})", l), (1, None), vec![]);
+    }
+    let edges = EDGES
+        .iter()
+        .copied()
+        .chain(EDGES.iter().map(|(a, b, l)| (*b, *a, *l)));
+    for (a, b, l) in edges {
+        p.state(
+            loc,
+            &format!("Going({},{})", a, b),
+            (l, Some(l)),
+            vec![
+                transition_from(&format!("At({})", a)),
+                transition_to(&format!("At({})", b)),
+            ],
+        );
+    }
+
+    for loc_idx in 0..n_pics {
+        let pic_name = format!("HavePicture{}", loc_idx);
+        let pic = p.timeline("HavePicture", Some(pic_name.clone()));
+        p.state(
+            pic,
+            "Taking",
+            (TAKE_PIC_TIME, Some(TAKE_PIC_TIME)),
+            vec![during(object("loc"), &format!("At({})", loc_idx), 0)],
+        );
+        p.state(pic, "Done", (1, None), vec![transition_from("Taking")]);
+
+        let dl_name = format!("Download{}", loc_idx);
+        let dl = p.timeline("Download", Some(dl_name.clone()));
+        p.state(
+            dl,
+            "Downloading",
+            (DOWNLOAD_TIME, Some(DOWNLOAD_TIME)),
+            vec![
+                during(object(&pic_name), "Done", 0),
+                during(object("Visibility"), "Available", 0),
+                during(object("Antenna"), "Available", 1),
+            ],
+        );
+        p.state(dl, "Done", (1, None), vec![transition_from("Downloading")]);
+
+        p.goal(&dl_name, "Done");
+    }
+
+    p.finish()
+}
+
+/// Generate an instance from its name, such as `carbonara_1p_5c`, `ceramic_2m_4j`
+/// or `goac_3pics_2wind`.
+pub fn by_name(name: &str) -> Option<Problem> {
+    let number = |s: &str, suffix: &str| s.strip_suffix(suffix)?.parse::<usize>().ok();
+    let parts = name.split('_').collect::<Vec<_>>();
+    match parts.as_slice() {
+        ["carbonara", p, c] => Some(carbonara(number(p, "p")?, number(c, "c")?)),
+        ["ceramic", m, j] => Some(ceramic(number(m, "m")?, number(j, "j")?)),
+        ["goac", pics, wind] => Some(goac(number(pics, "pics")?, number(wind, "wind")?)),
+        _ => None,
+    }
+}
+
+/// The names of the instances in the earlier benchmarks.
+pub fn benchmark_names() -> Vec<String> {
+    let mut names = Vec::new();
+    for plates in [1, 2] {
+        for n_carbonaras in [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 15, 20, 25, 30, 40, 50, 75, 100,
+        ] {
+            names.push(format!("carbonara_{}p_{}c", plates, n_carbonaras));
+        }
+    }
+    for (n_kilns, n_pieces) in [(1, 2), (2, 4), (2, 6), (4, 6), (5, 10)] {
+        names.push(format!("ceramic_{}m_{}j", n_kilns, n_pieces));
+    }
+    for n_pics in 1..=9 {
+        for n_windows in 1..=5 {
+            names.push(format!("goac_{}pics_{}wind", n_pics, n_windows));
+        }
+    }
+    names
+}
+
+/// The timeline of a condition: a given timeline, or any member of a group.
+enum Ref {
+    Object(String),
+    Any(String),
+}
+
+fn object(name: &str) -> Ref {
+    Ref::Object(name.to_string())
+}
+
+fn any(group: &str) -> Ref {
+    Ref::Any(group.to_string())
+}
+
+/// A condition as in the Python scripts. Transitions are on the timeline itself.
+enum Cond {
+    TransitionFrom(String),
+    TransitionTo(String),
+    StartsAfter(Ref, String),
+    During(Ref, String, u32),
+}
+
+fn use_resource(resource: Ref, amount: u32) -> Cond {
+    Cond::During(resource, "Available".to_string(), amount)
+}
+
+fn transition_from(value: &str) -> Cond {
+    Cond::TransitionFrom(value.to_string())
+}
+
+fn transition_to(value: &str) -> Cond {
+    Cond::TransitionTo(value.to_string())
+}
+
+fn starts_after(timeline: Ref, value: &str) -> Cond {
+    Cond::StartsAfter(timeline, value.to_string())
+}
+
+fn during(timeline: Ref, value: &str, amount: u32) -> Cond {
+    Cond::During(timeline, value.to_string(), amount)
+}
+
+/// Builds a problem in the same way as `timelinedsl.py`. The members of a group
+/// must be added before a condition refers to the group.
+#[derive(Default)]
+struct Builder {
+    timelines: Vec<Timeline>,
+    groups: HashMap<String, Vec<String>>,
+    n_static_tokens: usize,
+}
+
+impl Builder {
+    /// A timeline without token types that has a single resource token for the whole plan.
+    fn resource(&mut self, group: &str, name: Option<String>, capacity: u32) {
+        let name = name.unwrap_or_else(|| format!("{}_{}", group, self.n_static_tokens));
+        self.static_token(&name, "Available", TokenTime::Fact(None, None), capacity);
+        self.groups.entry(group.to_string()).or_default().push(name);
+    }
+
+    fn timeline(&mut self, group: &str, name: Option<String>) -> usize {
+        let name = name.unwrap_or_else(|| format!("{}_{}", group, self.timelines.len()));
+        self.groups
+            .entry(group.to_string())
+            .or_default()
+            .push(name.clone());
+        self.timeline_idx(&name)
+    }
+
+    fn timeline_idx(&mut self, name: &str) -> usize {
+        match self.timelines.iter().position(|t| t.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.timelines.push(Timeline {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.timelines.len() - 1
+            }
+        }
+    }
+
+    fn state(
+        &mut self,
+        timeline: usize,
+        value: &str,
+        duration_limits: (usize, Option<usize>),
+        conditions: Vec<Cond>,
+    ) -> &mut TokenType {
+        let name = self.timelines[timeline].name.clone();
+        let conditions = conditions
+            .into_iter()
+            .map(|c| self.condition(&name, c))
+            .collect();
+        self.timelines[timeline].token_types.push(TokenType {
+            value: value.to_string(),
+            duration_limits,
+            conditions,
+            ..Default::default()
+        });
+        self.timelines[timeline].token_types.last_mut().unwrap()
+    }
+
+    /// The alternatives for a condition.
+    fn condition(&self, timeline: &str, cond: Cond) -> Vec<Condition> {
+        let (relation, timelines, value, amount) = match cond {
+            Cond::TransitionFrom(value) => {
+                (TemporalRelationship::MetBy, object(timeline), value, 0)
+            }
+            Cond::TransitionTo(value) => (TemporalRelationship::Meets, object(timeline), value, 0),
+            Cond::StartsAfter(target, value) => {
+                (TemporalRelationship::StartsAfter, target, value, 0)
+            }
+            Cond::During(target, value, amount) => {
+                (TemporalRelationship::Cover, target, value, amount)
+            }
+        };
+        let timelines = match timelines {
+            Ref::Object(name) => vec![name],
+            Ref::Any(group) => self.groups.get(&group).cloned().unwrap_or_default(),
+        };
+        timelines
+            .into_iter()
+            .map(|timeline_ref| Condition {
+                timeline_ref,
+                temporal_relationship: relation,
+                value: value.clone(),
+                amount,
+            })
+            .collect()
+    }
+
+    fn goal(&mut self, timeline: &str, value: &str) {
+        self.static_token(timeline, value, TokenTime::Goal, 0);
+    }
+
+    fn fact(&mut self, timeline: &str, value: &str, start: Option<usize>, end: Option<usize>) {
+        self.static_token(timeline, value, TokenTime::Fact(start, end), 0);
+    }
+
+    fn static_token(&mut self, timeline: &str, value: &str, const_time: TokenTime, capacity: u32) {
+        let idx = self.timeline_idx(timeline);
+        self.timelines[idx].static_tokens.push(Token {
+            value: value.to_string(),
+            capacity,
+            const_time,
+            conditions: vec![],
+        });
+        self.n_static_tokens += 1;
+    }
+
+    fn finish(self) -> Problem {
+        Problem {
+            timelines: self.timelines,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! A string interface to the solver, so that bindings for other languages only need
+//! to pass JSON strings.
+//!
+//! The request is an object with the `problem` and, optionally, the `settings`:
+//!
+//! ```json
+//! { "problem": { "timelines": [...] }, "settings": { "optimize": "Makespan" } }
+//! ```
+//!
+//! The response is tagged by its `status`, and is either
+//! `{ "status": "Solved", "solution": {...}, "stats": {...} }` or
+//! `{ "status": "Failed", "error": { "kind": ..., "message": ..., "details": [...] } }`.
+
+use crate::problem::{Problem, Solution};
+use crate::transitionsolver::{solve_with_stats, SolveStats, SolverSettings};
+use serde::{Deserialize, Serialize};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SolveRequest {
+    pub problem: Problem,
+    #[serde(default)]
+    pub settings: SolverSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status")]
+pub enum SolveResponse {
+    Solved {
+        solution: Solution,
+        stats: SolveStats,
+    },
+    Failed {
+        error: ErrorInfo,
+        /// Statistics are only present if the solver was started.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stats: Option<SolveStats>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorInfo {
+    /// `InvalidJson`, `InvalidProblem`, `Panic`, or the name of a `SolverError` variant.
+    pub kind: String,
+    pub message: String,
+    /// The individual problems found when validating the problem.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+impl SolveResponse {
+    pub(crate) fn error(kind: &str, message: String, details: Vec<String>) -> Self {
+        SolveResponse::Failed {
+            error: ErrorInfo {
+                kind: kind.to_string(),
+                message,
+                details,
+            },
+            stats: None,
+        }
+    }
+
+    pub(crate) fn panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        SolveResponse::error("Panic", message, Vec::new())
+    }
+}
+
+pub fn solve_request(request: &SolveRequest) -> SolveResponse {
+    solve_problem(&request.problem, &request.settings, None)
+}
+
+/// Validate and solve the problem. The solver can be stopped by setting `cancel`.
+/// A panic in the solver is returned as an error of kind `Panic`.
+pub fn solve_problem(
+    problem: &Problem,
+    settings: &SolverSettings,
+    cancel: Option<&AtomicBool>,
+) -> SolveResponse {
+    if let Err(details) = problem.validate() {
+        return SolveResponse::error(
+            "InvalidProblem",
+            format!("The problem has {} errors", details.len()),
+            details,
+        );
+    }
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        solve_with_stats(problem, settings, cancel)
+    }));
+    match result {
+        Err(payload) => SolveResponse::panic(payload),
+        Ok((Ok(solution), stats)) => SolveResponse::Solved { solution, stats },
+        Ok((Err(e), stats)) => SolveResponse::Failed {
+            error: ErrorInfo {
+                kind: e.kind().to_string(),
+                message: e.to_string(),
+                details: Vec::new(),
+            },
+            stats: Some(stats),
+        },
+    }
+}
+
+/// Parse the request, validate the problem, solve it and return the JSON response.
+pub fn solve_json(input: &str) -> String {
+    let response = match serde_json::from_str::<SolveRequest>(input) {
+        Ok(request) => solve_request(&request),
+        Err(e) => SolveResponse::error("InvalidJson", e.to_string(), Vec::new()),
+    };
+    serde_json::to_string(&response).unwrap()
+}
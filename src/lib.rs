@@ -1,39 +1,130 @@
+pub mod backend;
+pub mod batch;
+pub mod benchmark;
 pub mod problem;
 pub mod transitionsolver;
 pub mod cores;
+pub mod dispatch;
+pub mod export;
+pub mod ffi;
+pub mod flexible;
+pub mod gantt;
+pub mod generators;
+pub mod jsonapi;
+pub mod lifted;
+pub mod plancheck;
+pub mod portfolio;
+pub mod server;
+pub mod smtlibbackend;
+#[cfg(feature = "z3")]
+pub mod z3backend;
 
+/// Solve a problem given as a JSON string and return the result as a JSON string.
+/// See `jsonapi` for the format of the request and the response.
 pub fn solve_json(input :String) -> String {
-    let problem = serde_json::de::from_str::<problem::Problem>(&input).unwrap();
-    println!("{:#?}", problem);
-    "".to_string()
+    jsonapi::solve_json(&input)
 }
 
 pub fn to_json(input :&problem::Problem) -> String {
     serde_json::to_string_pretty(input).unwrap()
 }
 
-pub fn print_calc_time<T>(name: &str, f: impl FnOnce() -> T) -> T{
-    use std::time::Instant;
-    let now = Instant::now();
-
-    let result = {
-        f()
-    };
-
-    let elapsed = now.elapsed();
-    println!("{} took {:.2?}", name, elapsed);
-    result
-}
-
 #[derive(Clone, Debug)]
 pub enum SolverError {
     NoSolution,
     GoalValueDurationLimit,
     GoalStateMissing,
+    /// Strong controllability requires contingent token types to have a maximum duration.
+    UnboundedContingentDuration(String),
+    /// The problem refers to a timeline that does not exist or has the wrong kind.
+    InvalidProblem(String),
+    /// The solver was stopped before it found a plan.
+    Cancelled,
+    /// The time limit was reached before a plan was found.
+    Timeout,
+    /// The external SMT solver process could not be started, or failed.
+    SolverProcess(String),
+    /// The SMT solver could not decide whether the formulation is satisfiable.
+    Unknown,
 }
 
+impl SolverError {
+    /// The name of the error variant, for reporting errors in a structured way.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SolverError::NoSolution => "NoSolution",
+            SolverError::GoalValueDurationLimit => "GoalValueDurationLimit",
+            SolverError::GoalStateMissing => "GoalStateMissing",
+            SolverError::UnboundedContingentDuration(_) => "UnboundedContingentDuration",
+            SolverError::InvalidProblem(_) => "InvalidProblem",
+            SolverError::Cancelled => "Cancelled",
+            SolverError::Timeout => "Timeout",
+            SolverError::SolverProcess(_) => "SolverProcess",
+            SolverError::Unknown => "Unknown",
+        }
+    }
+}
 
+impl std::fmt::Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::NoSolution => write!(f, "No solution found"),
+            SolverError::GoalValueDurationLimit => write!(f, "Goal value duration limit error"),
+            SolverError::GoalStateMissing => write!(f, "Goal state missing"),
+            SolverError::UnboundedContingentDuration(v) => {
+                write!(f, "Contingent token type {} has no maximum duration", v)
+            }
+            SolverError::InvalidProblem(e) => write!(f, "Invalid problem: {}", e),
+            SolverError::Cancelled => write!(f, "Cancelled"),
+            SolverError::Timeout => write!(f, "Timeout"),
+            SolverError::SolverProcess(e) => write!(f, "The SMT solver process failed: {}", e),
+            SolverError::Unknown => write!(f, "The SMT solver is undecided"),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+#[cfg(feature = "z3")]
 pub fn z3real_value(real: &z3::ast::Real) -> f32 {
     let (num, den) = real.as_real().unwrap();
     num as f32 / den as f32
 }
+
+#[cfg(feature = "z3")]
+pub fn z3real_from_f32<'ctx>(ctx: &'ctx z3::Context, value: f32) -> z3::ast::Real<'ctx> {
+    use z3::ast::{Ast, Real};
+    // A finite float is exactly mantissa * 2^exponent. The power of two is
+    // multiplied in as factors that fit in an i32.
+    let bits = value.to_bits();
+    let biased_exponent = ((bits >> 23) & 0xff) as i32;
+    let fraction = (bits & 0x7f_ffff) as i32;
+    let (mut mantissa, mut exponent) = if biased_exponent == 0 {
+        (fraction, -149)
+    } else {
+        (fraction | 0x80_0000, biased_exponent - 150)
+    };
+    if mantissa == 0 {
+        return Real::from_real(ctx, 0, 1);
+    }
+    while mantissa % 2 == 0 {
+        mantissa /= 2;
+        exponent += 1;
+    }
+    if value < 0.0 {
+        mantissa = -mantissa;
+    }
+
+    let mut real = Real::from_real(ctx, mantissa, 1);
+    while exponent != 0 {
+        let step = exponent.clamp(-30, 30);
+        let factor = if step > 0 {
+            Real::from_real(ctx, 1 << step, 1)
+        } else {
+            Real::from_real(ctx, 1, 1 << -step)
+        };
+        real = Real::mul(ctx, &[&real, &factor]);
+        exponent -= step;
+    }
+    real.simplify()
+}
@@ -0,0 +1,429 @@
+//! Lifted problem descriptions.
+//!
+//! A lifted problem describes timeline classes with typed parameters instead of
+//! listing every timeline separately. Strings in a class (token type values, timeline
+//! references and static token values) can refer to parameters as `{name}`, and
+//! `{self}` refers to the name of the timeline itself. Conditions can be quantified
+//! over all objects of a type. The `ground` function expands a lifted problem into
+//! an ordinary `problem::Problem`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::problem::{self, Cost, TemporalRelationship, TimelineKind};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LiftedProblem {
+    /// Objects that are not timelines, such as locations.
+    #[serde(default)]
+    pub objects: Vec<Object>,
+    pub classes: Vec<TimelineClass>,
+    /// The timelines of the problem. Each timeline is also an object whose type is
+    /// the name of its class.
+    pub timelines: Vec<TimelineInstance>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Object {
+    pub name: String,
+    pub object_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub object_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TimelineClass {
+    pub name: String,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub token_types: Vec<LiftedTokenType>,
+    #[serde(default)]
+    pub kind: TimelineKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TimelineInstance {
+    pub name: String,
+    pub class: String,
+    /// Objects given for the class parameters, in the same order.
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    #[serde(default)]
+    pub static_tokens: Vec<problem::Token>,
+}
+
+/// A token type that is grounded once for every combination of objects
+/// for its parameters.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LiftedTokenType {
+    pub value: String,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub duration_limits: (usize, Option<usize>),
+    pub conditions: Vec<Vec<LiftedCondition>>,
+    pub capacity: u32,
+    #[serde(default)]
+    pub cost: Cost,
+    #[serde(default)]
+    pub resource_effects: Vec<problem::ResourceEffect>,
+    #[serde(default)]
+    pub numeric_rates: Vec<problem::NumericRate>,
+    #[serde(default)]
+    pub numeric_conditions: Vec<problem::NumericCondition>,
+    #[serde(default)]
+    pub contingent: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LiftedCondition {
+    #[serde(default)]
+    pub quantifier: Option<Quantifier>,
+    pub timeline_ref: String,
+    pub temporal_relationship: TemporalRelationship,
+    pub value: String,
+    pub amount: u32,
+}
+
+/// Binds `variable` to each object of `object_type` in turn. With `Any`, the
+/// condition holds if it holds for one of the objects; with `All`, it must
+/// hold for every object.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quantifier {
+    pub kind: QuantifierKind,
+    pub variable: String,
+    pub object_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantifierKind {
+    Any,
+    All,
+}
+
+#[derive(Clone, Debug)]
+pub enum GroundingError {
+    DuplicateObject(String),
+    UnknownClass(String),
+    UnknownType(String),
+    UnknownParameter(String),
+    WrongNumberOfArguments(String),
+    TypeMismatch {
+        object: String,
+        expected: String,
+    },
+    /// A condition quantified over all objects of a type was used
+    /// as one of several alternatives.
+    AllQuantifierInAlternatives(String),
+    /// A condition of the token type has no alternatives left, because they are
+    /// quantified over a type that has no objects.
+    EmptyQuantifier {
+        token_type: String,
+        object_type: String,
+    },
+}
+
+impl std::fmt::Display for GroundingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroundingError::DuplicateObject(o) => write!(f, "Object {} is defined twice", o),
+            GroundingError::UnknownClass(c) => write!(f, "Unknown timeline class {}", c),
+            GroundingError::UnknownType(t) => write!(f, "Unknown object type {}", t),
+            GroundingError::UnknownParameter(p) => write!(f, "Unknown parameter {}", p),
+            GroundingError::WrongNumberOfArguments(i) => {
+                write!(f, "Timeline {} has the wrong number of arguments", i)
+            }
+            GroundingError::TypeMismatch { object, expected } => {
+                write!(f, "Object {} is not of type {}", object, expected)
+            }
+            GroundingError::AllQuantifierInAlternatives(v) => write!(
+                f,
+                "Token type {} quantifies over all objects in one of several alternatives",
+                v
+            ),
+            GroundingError::EmptyQuantifier {
+                token_type,
+                object_type,
+            } => write!(
+                f,
+                "Token type {} has a condition quantified over type {}, which has no objects",
+                token_type, object_type
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GroundingError {}
+
+type Bindings<'a> = Vec<(&'a str, &'a str)>;
+
+pub fn ground(lifted: &LiftedProblem) -> Result<problem::Problem, GroundingError> {
+    let classes = lifted
+        .classes
+        .iter()
+        .map(|c| (c.name.as_str(), c))
+        .collect::<HashMap<_, _>>();
+
+    let mut object_types: HashMap<&str, &str> = HashMap::new();
+    let mut objects_by_type: HashMap<&str, Vec<&str>> = HashMap::new();
+    let all_objects = lifted
+        .objects
+        .iter()
+        .map(|o| (o.name.as_str(), o.object_type.as_str()))
+        .chain(
+            lifted
+                .timelines
+                .iter()
+                .map(|t| (t.name.as_str(), t.class.as_str())),
+        );
+    for (name, object_type) in all_objects {
+        if object_types.insert(name, object_type).is_some() {
+            return Err(GroundingError::DuplicateObject(name.to_string()));
+        }
+        objects_by_type.entry(object_type).or_default().push(name);
+    }
+
+    let known_types = objects_by_type
+        .keys()
+        .copied()
+        .chain(classes.keys().copied())
+        .collect::<HashSet<_>>();
+    let objects_of_type = |object_type: &str| -> Result<&[&str], GroundingError> {
+        if !known_types.contains(object_type) {
+            return Err(GroundingError::UnknownType(object_type.to_string()));
+        }
+        Ok(objects_by_type
+            .get(object_type)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[]))
+    };
+
+    let mut timelines = Vec::new();
+    for instance in lifted.timelines.iter() {
+        let class = classes
+            .get(instance.class.as_str())
+            .ok_or_else(|| GroundingError::UnknownClass(instance.class.clone()))?;
+
+        if class.parameters.len() != instance.arguments.len() {
+            return Err(GroundingError::WrongNumberOfArguments(
+                instance.name.clone(),
+            ));
+        }
+
+        let mut bindings: Bindings = vec![("self", instance.name.as_str())];
+        for (param, arg) in class.parameters.iter().zip(instance.arguments.iter()) {
+            objects_of_type(&param.object_type)?;
+            if object_types.get(arg.as_str()) != Some(&param.object_type.as_str()) {
+                return Err(GroundingError::TypeMismatch {
+                    object: arg.clone(),
+                    expected: param.object_type.clone(),
+                });
+            }
+            bindings.push((param.name.as_str(), arg.as_str()));
+        }
+
+        let mut token_types = Vec::new();
+        for token_type in class.token_types.iter() {
+            for token_bindings in all_bindings(&bindings, &token_type.parameters, &objects_of_type)?
+            {
+                token_types.push(ground_token_type(
+                    token_type,
+                    &token_bindings,
+                    &objects_of_type,
+                )?);
+            }
+        }
+
+        let static_tokens = instance
+            .static_tokens
+            .iter()
+            .map(|t| {
+                Ok(problem::Token {
+                    value: substitute(&t.value, &bindings)?,
+                    conditions: t
+                        .conditions
+                        .iter()
+                        .map(|alternatives| {
+                            alternatives
+                                .iter()
+                                .map(|c| {
+                                    Ok(problem::Condition {
+                                        timeline_ref: substitute(&c.timeline_ref, &bindings)?,
+                                        value: substitute(&c.value, &bindings)?,
+                                        ..c.clone()
+                                    })
+                                })
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    ..t.clone()
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        timelines.push(problem::Timeline {
+            name: instance.name.clone(),
+            token_types,
+            static_tokens,
+            kind: class.kind.clone(),
+        });
+    }
+
+    Ok(problem::Problem { timelines })
+}
+
+/// Extends the bindings with every combination of objects for the parameters.
+fn all_bindings<'a>(
+    bindings: &Bindings<'a>,
+    parameters: &'a [Parameter],
+    objects_of_type: &impl Fn(&str) -> Result<&'a [&'a str], GroundingError>,
+) -> Result<Vec<Bindings<'a>>, GroundingError> {
+    let mut result = vec![bindings.clone()];
+    for param in parameters.iter() {
+        let objects = objects_of_type(&param.object_type)?;
+        result = result
+            .into_iter()
+            .flat_map(|b| {
+                objects.iter().map(move |o| {
+                    let mut b = b.clone();
+                    b.push((param.name.as_str(), *o));
+                    b
+                })
+            })
+            .collect();
+    }
+    Ok(result)
+}
+
+fn ground_token_type<'a>(
+    token_type: &LiftedTokenType,
+    bindings: &Bindings<'a>,
+    objects_of_type: &impl Fn(&str) -> Result<&'a [&'a str], GroundingError>,
+) -> Result<problem::TokenType, GroundingError> {
+    let mut conditions = Vec::new();
+    for alternatives in token_type.conditions.iter() {
+        let mut ground_alternatives = Vec::new();
+        for cond in alternatives.iter() {
+            let quantifier = match cond.quantifier.as_ref() {
+                Some(q) => q,
+                None => {
+                    ground_alternatives.push(ground_condition(cond, bindings)?);
+                    continue;
+                }
+            };
+
+            let objects = objects_of_type(&quantifier.object_type)?;
+            let mut ground = Vec::new();
+            for object in objects.iter() {
+                let mut bindings = bindings.clone();
+                bindings.push((quantifier.variable.as_str(), object));
+                ground.push(ground_condition(cond, &bindings)?);
+            }
+
+            match quantifier.kind {
+                QuantifierKind::Any => ground_alternatives.extend(ground),
+                QuantifierKind::All => {
+                    if alternatives.len() > 1 {
+                        return Err(GroundingError::AllQuantifierInAlternatives(
+                            token_type.value.clone(),
+                        ));
+                    }
+                    conditions.extend(ground.into_iter().map(|c| vec![c]));
+                }
+            }
+        }
+
+        if !ground_alternatives.is_empty() {
+            conditions.push(ground_alternatives);
+        } else if let Some(quantifier) = alternatives
+            .iter()
+            .filter_map(|c| c.quantifier.as_ref())
+            .find(|q| q.kind == QuantifierKind::Any)
+        {
+            // Every alternative was quantified over an empty type, so the condition
+            // can never hold.
+            return Err(GroundingError::EmptyQuantifier {
+                token_type: token_type.value.clone(),
+                object_type: quantifier.object_type.clone(),
+            });
+        }
+    }
+
+    Ok(problem::TokenType {
+        value: substitute(&token_type.value, bindings)?,
+        duration_limits: token_type.duration_limits,
+        conditions,
+        capacity: token_type.capacity,
+        cost: token_type.cost,
+        resource_effects: token_type
+            .resource_effects
+            .iter()
+            .map(|e| {
+                Ok(problem::ResourceEffect {
+                    timeline_ref: substitute(&e.timeline_ref, bindings)?,
+                    ..e.clone()
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        numeric_rates: token_type
+            .numeric_rates
+            .iter()
+            .map(|r| {
+                Ok(problem::NumericRate {
+                    timeline_ref: substitute(&r.timeline_ref, bindings)?,
+                    rate: r.rate,
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        numeric_conditions: token_type
+            .numeric_conditions
+            .iter()
+            .map(|c| {
+                Ok(problem::NumericCondition {
+                    timeline_ref: substitute(&c.timeline_ref, bindings)?,
+                    ..c.clone()
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        contingent: token_type.contingent,
+    })
+}
+
+fn ground_condition(
+    cond: &LiftedCondition,
+    bindings: &Bindings,
+) -> Result<problem::Condition, GroundingError> {
+    Ok(problem::Condition {
+        timeline_ref: substitute(&cond.timeline_ref, bindings)?,
+        temporal_relationship: cond.temporal_relationship,
+        value: substitute(&cond.value, bindings)?,
+        amount: cond.amount,
+    })
+}
+
+/// Replaces each `{name}` in the template with the object bound to `name`.
+/// Later bindings shadow earlier ones.
+fn substitute(template: &str, bindings: &Bindings) -> Result<String, GroundingError> {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| GroundingError::UnknownParameter(rest[start..].to_string()))?;
+        let name = &rest[start + 1..start + end];
+        let (_, object) = bindings
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .ok_or_else(|| GroundingError::UnknownParameter(name.to_string()))?;
+        result.push_str(object);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
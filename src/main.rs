@@ -1,125 +1,862 @@
-use paraspace::{print_calc_time, problem, transitionsolver};
-use std::path::PathBuf;
+use paraspace::{
+    batch,
+    benchmark::{self, Manifest, RunResult},
+    export::{self, IcalOptions},
+    flexible::FlexibleSolution,
+    gantt::{self, GanttOptions},
+    generators,
+    jsonapi::{self, SolveResponse},
+    lifted::{self, LiftedProblem},
+    plancheck, problem,
+    server::Server,
+    transitionsolver::{self, Objective, ResourceEncoding, SolverSettings},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "timelinemodel", about = "Timelines SMT-based solver.")]
 struct Opt {
+    /// Diagnostics at this level and above are written to stderr: off, error, warn, info, debug or trace
+    #[structopt(long = "log-level", default_value = "info", global = true)]
+    log_level: log::LevelFilter,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+/// Writes the log to stderr, so that stdout only has the output of the command.
+/// Other crates, such as the Z3 bindings, only log warnings and errors.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && (metadata.target().starts_with("paraspace") || metadata.level() <= log::Level::Warn)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Solve a problem
+    Solve(SolveOpt),
+
+    /// Check that a problem is well-formed without solving it
+    Validate {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+
+    /// Check that a solution is a valid plan for a problem
+    CheckPlan {
+        #[structopt(parse(from_os_str))]
+        problem: PathBuf,
+        #[structopt(parse(from_os_str))]
+        plan: PathBuf,
+    },
+
+    /// Print the size of a problem and the values that can be reached on each timeline
+    Stats {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+
+    /// Convert a file from one format to another
+    Convert(ConvertOpt),
+
+    /// Draw a solution as a Gantt chart in SVG or HTML
+    Gantt(GanttOpt),
+
+    /// Solve all the problem files in directories or matching patterns
+    Batch(BatchOpt),
+
+    /// Run the benchmark instances listed in a manifest
+    Benchmark(BenchmarkOpt),
+
+    /// Run a local HTTP server that solves problems
+    Serve(ServeOpt),
+
+    /// Generate instances of the carbonara, ceramic and goac benchmark families
+    Generate {
+        /// Instance names, such as carbonara_1p_5c, ceramic_2m_4j or goac_3pics_2wind
+        names: Vec<String>,
+
+        /// Generate all the instances of the earlier benchmarks
+        #[structopt(long = "all")]
+        all: bool,
+
+        /// Write each instance to <name>.json in this directory instead of stdout
+        #[structopt(long = "output-dir", parse(from_os_str))]
+        output_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct SolveOpt {
     /// Input file
     #[structopt(parse(from_os_str))]
-    input: Option<PathBuf>,
+    input: PathBuf,
 
-    /// Output file, stdout if not present
+    /// Output file
     #[structopt(parse(from_os_str))]
     output: Option<PathBuf>,
 
-    #[structopt(long = "benchmark")]
-    perftest: bool,
+    #[structopt(flatten)]
+    settings: SettingsOpt,
+
+    /// Write a flexible plan with the minimal temporal network instead of fixed times
+    #[structopt(long = "flexible")]
+    flexible: bool,
+}
 
+#[derive(Debug, StructOpt)]
+struct SettingsOpt {
+    /// Solver settings as JSON. The other solver options override the settings in the file
+    #[structopt(long = "settings", parse(from_os_str))]
+    settings: Option<PathBuf>,
+
+    /// Minimize the unsatisfiable cores before refining the problem
     #[structopt(long = "minimizecores")]
     minimizecores: bool,
+
+    /// Stop after this many seconds
+    #[structopt(long = "timeout")]
+    timeout: Option<f64>,
+
+    /// Random seed for the SMT solver
+    #[structopt(long = "seed")]
+    seed: Option<u32>,
+
+    /// Keep searching for better plans: cost, makespan or weighted:<cost>,<makespan>
+    #[structopt(long = "optimize")]
+    optimize: Option<Objective>,
+
+    /// How much each new plan must improve on the objective
+    #[structopt(long = "min-improvement")]
+    min_improvement: Option<f32>,
+
+    /// Refinements without a better plan before giving up optimizing
+    #[structopt(long = "optimization-patience")]
+    optimization_patience: Option<usize>,
+
+    /// Only accept strongly controllable plans
+    #[structopt(long = "strong-controllability")]
+    strong_controllability: bool,
+
+    /// Encoding of resource capacities: pairwise or pseudo-boolean
+    #[structopt(long = "resource-encoding")]
+    resource_encoding: Option<ResourceEncoding>,
+
+    /// Largest unsatisfiable core for which states are expanded together with links
+    #[structopt(long = "expansion-core-size")]
+    expansion_core_size: Option<usize>,
+
+    /// Write every call to the SMT solver to a .smt2 file in this directory
+    #[structopt(long = "smt2-dump-dir", parse(from_os_str))]
+    smt2_dump_dir: Option<PathBuf>,
+
+    /// Run this SMT-LIB2 solver command instead of the built-in Z3, e.g. "z3 -in"
+    #[structopt(long = "smt-solver")]
+    smt_solver: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct BatchOpt {
+    /// Directories, problem files, or file names with the wildcards * and ?
+    #[structopt(parse(from_os_str), required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Number of problems that are solved at the same time
+    #[structopt(long = "jobs", short = "j", default_value = "1")]
+    jobs: usize,
+
+    /// Also write the summary table to this file
+    #[structopt(long = "summary", parse(from_os_str))]
+    summary: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    settings: SettingsOpt,
+}
+
+#[derive(Debug, StructOpt)]
+struct ConvertOpt {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// Output file, stdout if not present
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Format of the input: lifted, problem or solution
+    #[structopt(long = "from", default_value = "problem")]
+    from: Format,
+
+    /// Format of the output: problem, solution, flexible, csv or ical
+    #[structopt(long = "to", default_value = "problem")]
+    to: Format,
+
+    /// Date and time of time 0 in a calendar, such as 2024-05-01T08:00:00Z
+    #[structopt(long = "epoch", default_value = "1970-01-01", parse(try_from_str = export::parse_epoch))]
+    epoch: i64,
+
+    /// Length of a time unit in a calendar: s, min, h, d, or a number of seconds
+    #[structopt(long = "time-unit", default_value = "min", parse(try_from_str = parse_time_unit))]
+    time_unit: f64,
+
+    /// The problem of a solution that is converted to a flexible plan. Without it, the
+    /// flexible plan only allows delays that keep the gaps between linked tokens
+    #[structopt(long = "problem", parse(from_os_str))]
+    problem: Option<PathBuf>,
+
+    /// Write compact JSON instead of pretty-printed JSON
+    #[structopt(long = "compact")]
+    compact: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct GanttOpt {
+    /// Solution file, or - for stdin
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// Output file, stdout if not present
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Write an HTML page instead of an SVG image
+    #[structopt(long = "html")]
+    html: bool,
+
+    /// Draw arrows for the causal links between tokens
+    #[structopt(long = "links")]
+    links: bool,
+
+    /// Width of the chart in pixels
+    #[structopt(long = "width", default_value = "1200")]
+    width: f32,
+
+    /// Title above the chart
+    #[structopt(long = "title")]
+    title: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ServeOpt {
+    /// Port to listen on
+    #[structopt(long = "port", default_value = "8080")]
+    port: u16,
+
+    /// Address to listen on
+    #[structopt(long = "host", default_value = "127.0.0.1")]
+    host: String,
+
+    /// Listen on this Unix socket instead of a TCP port
+    #[structopt(long = "socket", parse(from_os_str))]
+    socket: Option<PathBuf>,
+
+    /// Timeout in seconds for the jobs that do not set their own
+    #[structopt(long = "timeout")]
+    timeout: Option<f64>,
+
+    /// Number of jobs that can run at the same time
+    #[structopt(long = "max-jobs", default_value = "4")]
+    max_jobs: usize,
+
+    /// Number of finished background jobs whose status is kept. The earliest are
+    /// removed first
+    #[structopt(long = "keep-jobs", default_value = "100")]
+    keep_jobs: usize,
+}
+
+#[derive(Debug, StructOpt)]
+struct BenchmarkOpt {
+    /// Manifest listing the instance sets
+    #[structopt(parse(from_os_str))]
+    manifest: PathBuf,
+
+    /// Write the results as CSV to this file
+    #[structopt(long = "csv", parse(from_os_str))]
+    csv: Option<PathBuf>,
+
+    /// Write the results as JSON to this file, which can be used as a baseline later
+    #[structopt(long = "json", parse(from_os_str))]
+    json: Option<PathBuf>,
+
+    /// Compare the results with the JSON results of an earlier run
+    #[structopt(long = "baseline", parse(from_os_str))]
+    baseline: Option<PathBuf>,
+
+    /// Allowed increase of the median solve time over the baseline, as a fraction
+    #[structopt(long = "tolerance", default_value = "0.5")]
+    tolerance: f64,
+
+    /// Solve each instance this many times, overriding the manifest
+    #[structopt(long = "repetitions")]
+    repetitions: Option<usize>,
+
+    /// Stop each run after this many seconds, overriding the manifest
+    #[structopt(long = "timeout")]
+    timeout: Option<f64>,
+}
+
+/// The file formats known to the `convert` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Lifted,
+    Problem,
+    Solution,
+    Flexible,
+    Csv,
+    Ical,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lifted" => Ok(Format::Lifted),
+            "problem" => Ok(Format::Problem),
+            "solution" => Ok(Format::Solution),
+            "flexible" => Ok(Format::Flexible),
+            "csv" => Ok(Format::Csv),
+            "ical" => Ok(Format::Ical),
+            _ => Err(format!(
+                "unknown format \"{}\", expected lifted, problem, solution, flexible, csv or ical",
+                s
+            )),
+        }
+    }
+}
+
+fn parse_time_unit(s: &str) -> Result<f64, String> {
+    match s {
+        "s" => Ok(1.0),
+        "min" => Ok(60.0),
+        "h" => Ok(3600.0),
+        "d" => Ok(86400.0),
+        _ => s.parse::<f64>().ok().filter(|x| *x > 0.0).ok_or_else(|| {
+            format!(
+                "invalid time unit \"{}\", expected s, min, h, d or a number of seconds",
+                s
+            )
+        }),
+    }
 }
 
 fn main() {
     let opt = Opt::from_args();
-    println!("{:?}", opt);
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(opt.log_level);
 
-    if opt.perftest {
-        perftest();
+    match opt.command {
+        Command::Solve(opt) => solve(opt),
+        Command::Validate { input } => validate(&input),
+        Command::CheckPlan { problem, plan } => check_plan(&problem, &plan),
+        Command::Stats { input } => stats(&input),
+        Command::Convert(opt) => convert(opt),
+        Command::Gantt(opt) => gantt(opt),
+        Command::Batch(opt) => batch(opt),
+        Command::Benchmark(opt) => benchmark(opt),
+        Command::Serve(opt) => serve(opt),
+        Command::Generate {
+            names,
+            all,
+            output_dir,
+        } => generate(names, all, output_dir),
     }
+}
 
-    let solver_func = transitionsolver::solve;
+fn solve(opt: SolveOpt) {
+    log::debug!("{:?}", opt);
 
-    if let Some(filename) = opt.input {
-        let problem = {
-            let _p = hprof::enter("load_problem");
-            let contents = std::fs::read_to_string(&filename).unwrap();
-            serde_json::de::from_str::<problem::Problem>(&contents).unwrap()
-        };
+    let settings = solver_settings(&opt.settings);
+    let problem = {
+        let _p = hprof::enter("load_problem");
+        let contents = read_input(&opt.input);
+        match serde_json::from_str::<problem::Problem>(&contents) {
+            Ok(problem) => problem,
+            Err(e) => solve_failed(
+                &opt,
+                jsonapi::ErrorInfo {
+                    kind: "InvalidJson".to_string(),
+                    message: format!("invalid problem {}: {}", opt.input.display(), e),
+                    details: vec![],
+                },
+            ),
+        }
+    };
 
-        let minimizecores = opt.minimizecores;
-        let result = print_calc_time(filename.to_str().unwrap(), || {
-            solver_func(&problem, &Default::default())
-        });
-        match result {
-            Ok(solution) => {
-                println!("Solved.  (end of time = {})", solution.end_of_time);
-                for timeline in solution.timelines.iter() {
-                    println!(
-                        "Timeline \"{}\": {}",
-                        timeline.name,
-                        timeline
-                            .tokens
-                            .iter()
-                            .map(|t| format!("({},{},{})", t.value, t.start_time, t.end_time))
-                            .collect::<Vec<_>>()
-                            .join(", ")
-                    )
-                }
+    let start = std::time::Instant::now();
+    let response = jsonapi::solve_problem(&problem, &settings, None);
+    log::info!("{} took {:.2?}", opt.input.display(), start.elapsed());
+    for node in hprof::profiler().root().children.borrow().iter() {
+        log_timing(node, 0);
+    }
 
-                if let Some(output) = opt.output {
-                    std::fs::write(&output, serde_json::to_string_pretty(&solution).unwrap())
-                        .unwrap();
+    let json = match response {
+        SolveResponse::Solved { solution, stats } => {
+            log::info!("Solved.  (end of time = {})", solution.end_of_time);
+            log::debug!("{:?}", stats);
+            for timeline in solution.timelines.iter() {
+                log::info!(
+                    "Timeline \"{}\": {}",
+                    timeline.name,
+                    timeline
+                        .tokens
+                        .iter()
+                        .map(|t| format!("({},{},{})", t.value, t.start_time, t.end_time))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
 
-                    println!("Wrote to file '{}'", output.to_str().unwrap());
+            if opt.flexible {
+                match FlexibleSolution::from_solution(&problem, &solution) {
+                    Some(flexible) => serde_json::to_string_pretty(&flexible).unwrap(),
+                    None => solve_failed(
+                        &opt,
+                        jsonapi::ErrorInfo {
+                            kind: "InconsistentSolution".to_string(),
+                            message: "the solution is not consistent with the problem".to_string(),
+                            details: vec![],
+                        },
+                    ),
                 }
+            } else {
+                serde_json::to_string_pretty(&solution).unwrap()
+            }
+        }
+        SolveResponse::Failed { error, .. } => solve_failed(&opt, error),
+    };
+
+    match opt.output {
+        Some(output) => {
+            write_file(&output, &json);
+            log::info!("Wrote to file '{}'", output.display());
+        }
+        None => println!("{}", json),
+    }
+}
+
+/// Log the time spent in each part of the solver, as measured by hprof.
+fn log_timing(node: &hprof::ProfileNode, indent: usize) {
+    log::debug!(
+        "{:indent$}{} - {} calls, {:.2?}",
+        "",
+        node.name,
+        node.calls.get(),
+        std::time::Duration::from_nanos(node.total_time.get()),
+        indent = indent
+    );
+    for child in node.children.borrow().iter() {
+        log_timing(child, indent + 2);
+    }
+}
+
+fn validate(input: &Path) {
+    let problem = read_json::<problem::Problem>(input);
+    match problem.validate() {
+        Ok(()) => println!("Problem is valid."),
+        Err(errors) => {
+            for error in errors.iter() {
+                eprintln!("[ERROR] {}", error);
             }
-            Err(err) => {
-                println!("Error: {:#?}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn check_plan(problem: &Path, plan: &Path) {
+    let problem = read_json::<problem::Problem>(problem);
+    let solution = read_json::<problem::Solution>(plan);
+    match plancheck::check_plan(&problem, &solution) {
+        Ok(()) => println!("Plan is valid."),
+        Err(errors) => {
+            for error in errors.iter() {
+                eprintln!("[ERROR] {}", error);
             }
+            std::process::exit(1);
+        }
+    }
+}
+
+fn stats(input: &Path) {
+    let problem = read_json::<problem::Problem>(input);
+    let n_conditions = |tl: &problem::Timeline| {
+        let token_types = tl.token_types.iter().map(|tt| &tt.conditions);
+        let static_tokens = tl.static_tokens.iter().map(|t| &t.conditions);
+        token_types
+            .chain(static_tokens)
+            .flatten()
+            .map(|c| c.len())
+            .sum::<usize>()
+    };
+    let total =
+        |f: &dyn Fn(&problem::Timeline) -> usize| problem.timelines.iter().map(f).sum::<usize>();
+
+    println!("Timelines:     {}", problem.timelines.len());
+    println!("Token types:   {}", total(&|tl| tl.token_types.len()));
+    println!("Static tokens: {}", total(&|tl| tl.static_tokens.len()));
+    println!("Conditions:    {}", total(&n_conditions));
+
+    for tl in problem.timelines.iter() {
+        let kind = match tl.kind {
+            problem::TimelineKind::Symbolic => "symbolic",
+            problem::TimelineKind::Reservoir(_) => "reservoir",
+            problem::TimelineKind::Numeric(_) => "numeric",
+        };
+        println!();
+        println!(
+            "Timeline \"{}\" ({}): {} token types, {} static tokens, {} conditions",
+            tl.name,
+            kind,
+            tl.token_types.len(),
+            tl.static_tokens.len(),
+            n_conditions(tl)
+        );
+        if tl.token_types.is_empty() {
+            continue;
+        }
+
+        // The number of tokens needed to reach each value from the facts.
+        let reachable = transitionsolver::reachable_values(tl);
+        println!(
+            "  reachable: {}",
+            reachable
+                .iter()
+                .map(|(value, steps)| format!("{} ({})", value, steps))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let unreachable = tl
+            .token_types
+            .iter()
+            .filter(|tt| !reachable.contains_key(tt.value.as_str()))
+            .map(|tt| tt.value.as_str())
+            .collect::<Vec<_>>();
+        if !unreachable.is_empty() {
+            println!("  unreachable: {}", unreachable.join(", "));
+        }
+        for goal in tl
+            .static_tokens
+            .iter()
+            .filter(|t| matches!(t.const_time, problem::TokenTime::Goal))
+        {
+            match reachable.get(goal.value.as_str()) {
+                Some(steps) => println!("  goal \"{}\": reachable in {} tokens", goal.value, steps),
+                None => println!("  goal \"{}\": unreachable", goal.value),
+            }
+        }
+    }
+}
+
+fn convert(opt: ConvertOpt) {
+    let json = match (opt.from, opt.to) {
+        (Format::Lifted, Format::Lifted) => {
+            write_json(opt.compact, &read_json::<LiftedProblem>(&opt.input))
+        }
+        (Format::Lifted, Format::Problem) => {
+            let lifted = read_json::<LiftedProblem>(&opt.input);
+            let problem = lifted::ground(&lifted).unwrap_or_else(|e| {
+                fatal(format!("Could not ground {}: {}", opt.input.display(), e));
+            });
+            write_json(opt.compact, &problem)
+        }
+        (Format::Problem, Format::Problem) => {
+            write_json(opt.compact, &read_json::<problem::Problem>(&opt.input))
+        }
+        (Format::Solution, Format::Solution) => {
+            write_json(opt.compact, &read_json::<problem::Solution>(&opt.input))
+        }
+        (Format::Solution, Format::Flexible) => {
+            let solution = read_json::<problem::Solution>(&opt.input);
+            let flexible = match opt.problem.as_ref() {
+                Some(path) => {
+                    FlexibleSolution::from_solution(&read_json::<problem::Problem>(path), &solution)
+                }
+                None => FlexibleSolution::from_fixed_times(&solution),
+            };
+            let flexible = flexible.unwrap_or_else(|| {
+                fatal("The solution is not consistent with the problem.");
+            });
+            write_json(opt.compact, &flexible)
+        }
+        (Format::Solution, Format::Csv) => {
+            export::to_csv(&read_json::<problem::Solution>(&opt.input))
+        }
+        (Format::Solution, Format::Ical) => {
+            let options = IcalOptions {
+                epoch: opt.epoch,
+                time_unit: opt.time_unit,
+                ..Default::default()
+            };
+            export::to_ical(&read_json::<problem::Solution>(&opt.input), &options)
+        }
+        (from, to) => {
+            fatal(format!("Cannot convert from {:?} to {:?}.", from, to));
+        }
+    };
+
+    match opt.output {
+        Some(output) => write_file(&output, &json),
+        None if json.ends_with('\n') => print!("{}", json),
+        None => println!("{}", json),
+    }
+}
+
+fn generate(mut names: Vec<String>, all: bool, output_dir: Option<PathBuf>) {
+    if all {
+        names.extend(generators::benchmark_names());
+    }
+    if let Some(dir) = output_dir.as_ref() {
+        std::fs::create_dir_all(dir).unwrap_or_else(|e| {
+            fatal(format!("Could not create {}: {}", dir.display(), e));
+        });
+    }
+    for name in names.iter() {
+        let problem = generators::by_name(name).unwrap_or_else(|| {
+            fatal(format!("Unknown instance name \"{}\".", name));
+        });
+        let json = write_json(false, &problem);
+        match output_dir.as_ref() {
+            Some(dir) => write_file(&dir.join(format!("{}.json", name)), &json),
+            None => println!("{}", json),
         }
+    }
+}
+
+/// Write a file, or exit with an error message.
+fn write_file(path: &Path, contents: &str) {
+    std::fs::write(path, contents).unwrap_or_else(|e| {
+        fatal(format!("Could not write {}: {}", path.display(), e));
+    })
+}
+
+fn write_json<T: Serialize>(compact: bool, value: &T) -> String {
+    if compact {
+        serde_json::to_string(value).unwrap()
     } else {
-        println!("No problem files given.");
+        serde_json::to_string_pretty(value).unwrap()
+    }
+}
+
+/// Print an error message to stderr, whatever the log level, and exit.
+fn fatal(message: impl std::fmt::Display) -> ! {
+    eprintln!("[ERROR] {}", message);
+    std::process::exit(1);
+}
+
+/// Report a failed solve and exit. The error goes to stdout in place of the
+/// solution when there is no output file.
+fn solve_failed(opt: &SolveOpt, error: jsonapi::ErrorInfo) -> ! {
+    if opt.output.is_none() {
+        println!("{}", serde_json::to_string_pretty(&error).unwrap());
     }
+    for detail in error.details.iter() {
+        eprintln!("[ERROR] {}", detail);
+    }
+    fatal(format!("{}: {}", error.kind, error.message));
+}
 
-    hprof::profiler().print_timing();
+/// The contents of a file, or of stdin for `-`.
+fn read_input(path: &Path) -> String {
+    let contents = if path == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    contents.unwrap_or_else(|e| {
+        fatal(format!("Could not read {}: {}", path.display(), e));
+    })
 }
 
-fn perftest() {
-    let mut problem_names = Vec::new();
-    for plates in [1, 2] {
-        for n_carbonaras in [
-            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 15, 20, 25, 30, 40, 50, 75, 100,
-        ] {
-            let problem_name = format!("carbonara_{}p_{}c", plates, n_carbonaras);
-            problem_names.push(problem_name);
+/// Read a JSON file, or stdin if the path is `-`, or exit with an error message.
+fn read_json<T: DeserializeOwned>(path: &Path) -> T {
+    let contents = read_input(path);
+    serde_json::from_str::<T>(&contents).unwrap_or_else(|e| {
+        fatal(format!("Invalid file {}: {}", path.display(), e));
+    })
+}
+
+/// The settings from the `--settings` file, or the defaults, with the
+/// command line options applied on top.
+fn solver_settings(opt: &SettingsOpt) -> SolverSettings {
+    let mut settings = match opt.settings.as_ref() {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                fatal(format!(
+                    "Could not read settings file {}: {}",
+                    path.display(),
+                    e
+                ));
+            });
+            serde_json::from_str::<SolverSettings>(&contents).unwrap_or_else(|e| {
+                fatal(format!("Invalid settings file {}: {}", path.display(), e));
+            })
         }
+        None => SolverSettings::default(),
+    };
+
+    if opt.minimizecores {
+        settings.minimize_cores = true;
+    }
+    if opt.strong_controllability {
+        settings.strong_controllability = true;
+    }
+    if let Some(timeout) = opt.timeout {
+        settings.timeout = Some(timeout);
+    }
+    if let Some(seed) = opt.seed {
+        settings.random_seed = seed;
+    }
+    if let Some(optimize) = opt.optimize {
+        settings.optimize = Some(optimize);
+    }
+    if let Some(min_improvement) = opt.min_improvement {
+        settings.min_improvement = min_improvement;
+    }
+    if let Some(patience) = opt.optimization_patience {
+        settings.optimization_patience = patience;
+    }
+    if let Some(encoding) = opt.resource_encoding {
+        settings.resource_encoding = encoding;
     }
+    if let Some(size) = opt.expansion_core_size {
+        settings.expansion_core_size = size;
+    }
+    if let Some(dir) = opt.smt2_dump_dir.as_ref() {
+        settings.smt2_dump_dir = Some(dir.clone());
+    }
+    if let Some(command) = opt.smt_solver.as_ref() {
+        settings.smt_solver_command = Some(command.clone());
+    }
+    settings
+}
 
-    for (n_kilns, n_pieces) in [(1, 2), (2, 4), (2, 6), (4, 6), (5, 10)] {
-        let problem_name = format!("ceramic_{}m_{}j", n_kilns, n_pieces);
-        problem_names.push(problem_name);
+fn batch(opt: BatchOpt) {
+    let settings = solver_settings(&opt.settings);
+    let mut files = Vec::new();
+    for input in opt.inputs.iter() {
+        let found = batch::input_files(input).unwrap_or_else(|e| {
+            fatal(e);
+        });
+        if found.is_empty() {
+            fatal(format!("No problem files found in {}.", input.display()));
+        }
+        files.extend(found);
     }
 
-    for n_pics in 1..=9 {
-        for n_windows in 1..=5 {
-            let problem_name = format!("goac_{}pics_{}wind", n_pics, n_windows);
-            problem_names.push(problem_name);
+    let results = batch::solve_files(&files, &settings, opt.jobs, |r| {
+        println!("{}: {} in {:.3}s", r.file.display(), r.status, r.time)
+    });
+
+    let summary = batch::summary(&results);
+    println!();
+    print!("{}", summary);
+    if let Some(path) = opt.summary.as_ref() {
+        write_file(path, &summary);
+    }
+}
+
+fn gantt(opt: GanttOpt) {
+    let solution = read_json::<problem::Solution>(&opt.input);
+    let options = GanttOptions {
+        width: opt.width,
+        links: opt.links,
+        title: opt.title,
+        ..Default::default()
+    };
+    let chart = if opt.html {
+        gantt::to_html(&solution, &options)
+    } else {
+        gantt::to_svg(&solution, &options)
+    };
+    match opt.output {
+        Some(output) => write_file(&output, &chart),
+        None => print!("{}", chart),
+    }
+}
+
+fn serve(opt: ServeOpt) {
+    let server = Server::new(opt.timeout, opt.max_jobs, opt.keep_jobs);
+    let result = match opt.socket.as_ref() {
+        #[cfg(unix)]
+        Some(socket) => std::os::unix::net::UnixListener::bind(socket).and_then(|listener| {
+            log::info!("Listening on {}", socket.display());
+            server.serve_unix(listener)
+        }),
+        #[cfg(not(unix))]
+        Some(_) => {
+            fatal("Unix sockets are not supported on this platform.");
         }
+        None => std::net::TcpListener::bind((opt.host.as_str(), opt.port)).and_then(|listener| {
+            log::info!("Listening on http://{}", listener.local_addr()?);
+            server.serve_tcp(listener)
+        }),
+    };
+    if let Err(e) = result {
+        fatal(format!("Server error: {}", e));
     }
+}
 
-    for problem_name in problem_names {
-        let contents = std::fs::read_to_string(&format!("examples/{}.json", problem_name)).unwrap();
-        let problem = serde_json::de::from_str::<problem::Problem>(&contents).unwrap();
+fn benchmark(opt: BenchmarkOpt) {
+    let mut manifest = read_json::<Manifest>(&opt.manifest);
+    if let Some(repetitions) = opt.repetitions {
+        manifest.repetitions = repetitions;
+    }
+    if let Some(timeout) = opt.timeout {
+        manifest.timeout = Some(timeout);
+    }
+    let baseline = opt
+        .baseline
+        .as_ref()
+        .map(|path| read_json::<Vec<RunResult>>(path));
 
-        // println!("Problem:\n{:#?}", problem);
-        // println!("Solving...");
-        let result = print_calc_time(&problem_name, || {
-            transitionsolver::solve(&problem, &Default::default())
+    let base_dir = opt.manifest.parent().unwrap_or_else(|| Path::new("."));
+    let results = manifest
+        .run(base_dir, |r| {
+            println!(
+                "{} {} #{}: {} in {:.3}s ({} SMT calls)",
+                r.set, r.instance, r.repetition, r.status, r.stats.solve_time, r.stats.smt_calls
+            )
+        })
+        .unwrap_or_else(|e| {
+            fatal(format!("Could not run the benchmark: {}", e));
         });
-        match result {
-            Ok(solution) => {
-                // println!("Success!");
-                std::fs::write(
-                    &format!("examples/{}.out.json", problem_name),
-                    serde_json::to_string_pretty(&solution).unwrap(),
-                )
-                .unwrap();
-            }
-            Err(err) => {
-                println!("Error: {:#?}", err);
-            }
+
+    if let Some(path) = opt.csv.as_ref() {
+        write_file(path, &benchmark::to_csv(&results));
+    }
+    if let Some(path) = opt.json.as_ref() {
+        write_file(path, &write_json(false, &results));
+    }
+
+    if let Some(baseline) = baseline {
+        let regressions = benchmark::compare(&results, &baseline, opt.tolerance);
+        for r in regressions.iter() {
+            println!("Regression: {} {}: {}", r.set, r.instance, r.message);
+        }
+        if !regressions.is_empty() {
+            std::process::exit(1);
         }
+        println!("No regressions.");
     }
 }
 
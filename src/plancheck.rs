@@ -0,0 +1,372 @@
+//! Checking a solution against a problem.
+//!
+//! The checker works on the fixed times of the solution and does not call the SMT
+//! solver, so it can be used to verify plans that come from other sources, such as
+//! an older version of the planner or a plan edited by hand. It checks the token
+//! sequences, the durations, the facts and goals, the positive and negative
+//! conditions, the capacity of the resources used through the causal links, and
+//! the levels of reservoir timelines. Numeric timelines are not checked.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::flexible::{spec_conditions, static_tokens_of};
+use crate::problem::{
+    Condition, NumericRelation, Problem, Solution, SolutionToken, TemporalRelationship,
+    TimelineKind, TokenPoint, TokenTime,
+};
+
+/// Tolerance for comparing the times in a solution.
+pub const EPSILON: f32 = 1e-3;
+
+/// Check that the solution is a valid plan for the problem.
+/// Returns a description of each violation found.
+pub fn check_plan(problem: &Problem, solution: &Solution) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    let solution_timelines = solution
+        .timelines
+        .iter()
+        .map(|tl| (tl.name.as_str(), tl))
+        .collect::<HashMap<_, _>>();
+
+    for tl in solution.timelines.iter() {
+        if !problem.timelines.iter().any(|t| t.name == tl.name) {
+            errors.push(format!("unknown timeline \"{}\" in the solution", tl.name));
+        }
+    }
+
+    for tl_spec in problem.timelines.iter() {
+        let tokens = solution_timelines
+            .get(tl_spec.name.as_str())
+            .map(|tl| tl.tokens.as_slice())
+            .unwrap_or(&[]);
+        let token_name = |idx: usize| {
+            format!(
+                "token {} \"{}\" on timeline \"{}\"",
+                idx, tokens[idx].value, tl_spec.name
+            )
+        };
+
+        // Facts are in the solution with their fixed times.
+        for fact in tl_spec.static_tokens.iter() {
+            if let TokenTime::Fact(start, end) = fact.const_time {
+                let found = tokens.iter().any(|t| {
+                    t.fact
+                        && t.value == fact.value
+                        && start
+                            .map(|s| approx_eq(t.start_time, s as f32))
+                            .unwrap_or(true)
+                        && end.map(|e| approx_eq(t.end_time, e as f32)).unwrap_or(true)
+                });
+                if !found {
+                    errors.push(format!(
+                        "fact \"{}\" on timeline \"{}\" is missing or has the wrong times",
+                        fact.value, tl_spec.name
+                    ));
+                }
+            }
+        }
+
+        // The goals are the last tokens of their timelines.
+        for goal in tl_spec.static_tokens.iter() {
+            if matches!(goal.const_time, TokenTime::Goal)
+                && tokens.last().map(|t| t.value != goal.value).unwrap_or(true)
+            {
+                errors.push(format!(
+                    "timeline \"{}\" does not end in its goal \"{}\"",
+                    tl_spec.name, goal.value
+                ));
+            }
+        }
+
+        let static_tokens = static_tokens_of(tl_spec, tokens);
+        for (idx, token) in tokens.iter().enumerate() {
+            if token.end_time < token.start_time - EPSILON {
+                errors.push(format!("{} ends before it starts", token_name(idx)));
+            }
+            if token.end_time > solution.end_of_time + EPSILON {
+                errors.push(format!(
+                    "{} ends after the end of the plan",
+                    token_name(idx)
+                ));
+            }
+
+            if !token.fact {
+                match tl_spec
+                    .token_types
+                    .iter()
+                    .find(|tt| tt.value == token.value)
+                {
+                    None => errors.push(format!("{} has no token type", token_name(idx))),
+                    Some(tt) => {
+                        let duration = token.end_time - token.start_time;
+                        let (min, max) = tt.duration_limits;
+                        if duration < min as f32 - EPSILON
+                            || max.map(|m| duration > m as f32 + EPSILON).unwrap_or(false)
+                        {
+                            errors.push(format!(
+                                "{} has duration {} outside the limits {:?}",
+                                token_name(idx),
+                                duration,
+                                tt.duration_limits
+                            ));
+                        }
+                    }
+                }
+            }
+
+            // The tokens of a timeline with token types follow each other.
+            if !tl_spec.token_types.is_empty() {
+                if let Some(next) = tokens.get(idx + 1) {
+                    if !approx_eq(token.end_time, next.start_time) {
+                        errors.push(format!(
+                            "{} is not followed immediately by the next token",
+                            token_name(idx)
+                        ));
+                    }
+                }
+            }
+
+            for group in spec_conditions(tl_spec, token, static_tokens[idx]) {
+                let (negative, positive): (Vec<&Condition>, Vec<&Condition>) =
+                    group.iter().partition(|c| c.is_negative());
+                if !positive.is_empty()
+                    && !positive.iter().any(|c| {
+                        targets(solution, c)
+                            .any(|(_, target)| holds(c.temporal_relationship, token, target))
+                    })
+                {
+                    errors.push(format!(
+                        "{} does not satisfy its condition on {}",
+                        token_name(idx),
+                        describe(&positive)
+                    ));
+                }
+                for cond in negative {
+                    let violated = targets(solution, cond).any(|(target_idx, target)| {
+                        let itself = cond.timeline_ref == tl_spec.name && target_idx == idx;
+                        !itself && !holds(cond.temporal_relationship, token, target)
+                    });
+                    if violated {
+                        errors.push(format!(
+                            "{} violates its condition on {}",
+                            token_name(idx),
+                            describe(&[cond])
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    check_capacities(problem, solution, &mut errors);
+    check_reservoirs(problem, solution, &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The total amount used of each capacity resource at any time must be below its
+/// capacity. The users of a resource are found through the causal links.
+fn check_capacities(problem: &Problem, solution: &Solution, errors: &mut Vec<String>) {
+    let mut users: BTreeMap<(&str, usize), Vec<(&SolutionToken, u32)>> = BTreeMap::new();
+    for tl in solution.timelines.iter() {
+        for token in tl.tokens.iter() {
+            for cond in token.conditions.iter().filter(|c| c.amount > 0) {
+                users
+                    .entry((cond.timeline.as_str(), cond.token_idx))
+                    .or_default()
+                    .push((token, cond.amount));
+            }
+        }
+    }
+
+    for ((timeline, token_idx), users) in users {
+        let target = match solution
+            .timelines
+            .iter()
+            .find(|t| t.name == timeline)
+            .and_then(|tl| tl.tokens.get(token_idx))
+        {
+            Some(t) => t,
+            None => {
+                errors.push(format!(
+                    "a condition refers to missing token {} on timeline \"{}\"",
+                    token_idx, timeline
+                ));
+                continue;
+            }
+        };
+        let tl_spec = problem.timelines.iter().find(|t| t.name == timeline);
+        let capacity = tl_spec.and_then(|tl_spec| {
+            if target.fact {
+                tl_spec
+                    .static_tokens
+                    .iter()
+                    .find(|t| t.value == target.value)
+                    .map(|t| t.capacity)
+            } else {
+                tl_spec
+                    .token_types
+                    .iter()
+                    .find(|t| t.value == target.value)
+                    .map(|t| t.capacity)
+            }
+        });
+        let capacity = capacity.unwrap_or(0);
+
+        for (user, _) in users.iter() {
+            let t = user.start_time;
+            let used = users
+                .iter()
+                .filter(|(u, _)| u.start_time <= t + EPSILON && u.end_time > t + EPSILON)
+                .map(|(_, amount)| amount)
+                .sum::<u32>();
+            if used > capacity {
+                errors.push(format!(
+                    "token {} \"{}\" on timeline \"{}\" is used {} at time {}, above its capacity {}",
+                    token_idx, target.value, timeline, used, t, capacity
+                ));
+                break;
+            }
+        }
+    }
+}
+
+/// The level of a reservoir timeline must stay within its limits, and the numeric
+/// conditions on it must hold.
+fn check_reservoirs(problem: &Problem, solution: &Solution, errors: &mut Vec<String>) {
+    for tl_spec in problem.timelines.iter() {
+        let reservoir = match &tl_spec.kind {
+            TimelineKind::Reservoir(r) => r,
+            _ => continue,
+        };
+
+        let mut effects = Vec::new();
+        let mut conditions = Vec::new();
+        for tl in solution.timelines.iter() {
+            let spec = match problem.timelines.iter().find(|t| t.name == tl.name) {
+                Some(s) => s,
+                None => continue,
+            };
+            for token in tl.tokens.iter().filter(|t| !t.fact) {
+                let tt = match spec.token_types.iter().find(|tt| tt.value == token.value) {
+                    Some(tt) => tt,
+                    None => continue,
+                };
+                let time_of = |point: TokenPoint| match point {
+                    TokenPoint::Start => token.start_time,
+                    TokenPoint::End => token.end_time,
+                };
+                for e in tt.resource_effects.iter() {
+                    if e.timeline_ref == tl_spec.name {
+                        effects.push((time_of(e.time), e.amount));
+                    }
+                }
+                for c in tt.numeric_conditions.iter() {
+                    if c.timeline_ref == tl_spec.name {
+                        conditions.push((time_of(c.time), c.relation, c.value, &token.value));
+                    }
+                }
+            }
+        }
+
+        // The level just before the given time, or just after it when the effects
+        // at that time are included.
+        let level_at = |time: f32, include_time: bool| {
+            reservoir.initial
+                + effects
+                    .iter()
+                    .filter(|(t, _)| {
+                        if include_time {
+                            *t <= time + EPSILON
+                        } else {
+                            *t < time - EPSILON
+                        }
+                    })
+                    .map(|(_, amount)| amount)
+                    .sum::<i32>()
+        };
+
+        for (time, _) in effects.iter() {
+            let level = level_at(*time, true);
+            if level < reservoir.min || level > reservoir.max {
+                errors.push(format!(
+                    "reservoir \"{}\" has level {} at time {}, outside [{}, {}]",
+                    tl_spec.name, level, time, reservoir.min, reservoir.max
+                ));
+                break;
+            }
+        }
+
+        for (time, relation, value, token_value) in conditions {
+            let level = level_at(time, false) as f32;
+            let ok = match relation {
+                NumericRelation::GreaterOrEqual => level >= value - EPSILON,
+                NumericRelation::LessOrEqual => level <= value + EPSILON,
+            };
+            if !ok {
+                errors.push(format!(
+                    "token \"{}\" needs reservoir \"{}\" to be {:?} {} at time {}, but it is {}",
+                    token_value, tl_spec.name, relation, value, time, level
+                ));
+            }
+        }
+    }
+}
+
+/// The tokens on the timeline of a condition that have the value of the condition.
+fn targets<'a>(
+    solution: &'a Solution,
+    cond: &'a Condition,
+) -> impl Iterator<Item = (usize, &'a SolutionToken)> + 'a {
+    solution
+        .timelines
+        .iter()
+        .filter(move |tl| tl.name == cond.timeline_ref)
+        .flat_map(|tl| tl.tokens.iter().enumerate())
+        .filter(move |(_, t)| t.value == cond.value)
+}
+
+/// Whether the temporal relation holds between a token and a target token. For the
+/// negative relations, whether the token is allowed next to the target.
+fn holds(relation: TemporalRelationship, token: &SolutionToken, target: &SolutionToken) -> bool {
+    let (start, end) = (token.start_time, token.end_time);
+    let (target_start, target_end) = (target.start_time, target.end_time);
+    let le = |a: f32, b: f32| a <= b + EPSILON;
+    match relation {
+        TemporalRelationship::MetBy | TemporalRelationship::MetByTransitionFrom => {
+            approx_eq(target_end, start)
+        }
+        TemporalRelationship::Meets => approx_eq(end, target_start),
+        TemporalRelationship::Starts => approx_eq(target_start, start),
+        TemporalRelationship::StartsAfter => le(target_start, start),
+        TemporalRelationship::Cover => le(target_start, start) && le(end, target_end),
+        TemporalRelationship::StartPrecond => {
+            le(target_start + 1.0, start) && le(start, target_end)
+        }
+        TemporalRelationship::StartEffect => le(target_start, start) && le(start + 1.0, target_end),
+        TemporalRelationship::Equal => approx_eq(start, target_start) && approx_eq(end, target_end),
+        TemporalRelationship::NotOverlapping => le(end, target_start) || le(target_end, start),
+        TemporalRelationship::NotDuring => le(start, target_start) || le(target_end, end),
+    }
+}
+
+fn describe(conds: &[&Condition]) -> String {
+    conds
+        .iter()
+        .map(|c| {
+            format!(
+                "{:?} \"{}\" on timeline \"{}\"",
+                c.temporal_relationship, c.value, c.timeline_ref
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() <= EPSILON
+}
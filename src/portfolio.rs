@@ -0,0 +1,96 @@
+//! Portfolio solving.
+//!
+//! The performance of the solver depends a lot on the settings of the SMT solver,
+//! and the best settings differ between domains. A portfolio runs several solver
+//! configurations in parallel threads, each with its own Z3 context, and returns
+//! the first plan found or the first proof that there is none. The other threads
+//! are cancelled. Errors that depend on the configuration, such as a failing
+//! external solver or a timeout, are only returned when all configurations fail.
+
+use crate::problem::{Problem, Solution};
+use crate::transitionsolver::{solve, solve_with_cancel, SolverSettings};
+use crate::SolverError;
+use log::debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+
+/// Solve the problem with each of the settings in parallel and return the first
+/// definitive result. An empty portfolio uses the default settings.
+pub fn solve_portfolio(
+    problem: &Problem,
+    portfolio: &[SolverSettings],
+) -> Result<Solution, SolverError> {
+    if portfolio.is_empty() {
+        return solve(problem, &Default::default());
+    }
+
+    let cancel = AtomicBool::new(false);
+    let (result_tx, result_rx) = mpsc::channel();
+    std::thread::scope(|s| {
+        for (idx, settings) in portfolio.iter().enumerate() {
+            let result_tx = result_tx.clone();
+            let cancel = &cancel;
+            s.spawn(move || {
+                let result = solve_with_cancel(problem, settings, cancel);
+                // The receiver is gone when another configuration has finished first.
+                let _ = result_tx.send((idx, result));
+            });
+        }
+        drop(result_tx);
+
+        let mut first_error = None;
+        for (idx, result) in result_rx.iter() {
+            match result {
+                Err(e) if !is_definitive(&e) => {
+                    debug!("Portfolio configuration {} failed: {}", idx, e);
+                    first_error.get_or_insert(e);
+                }
+                result => {
+                    debug!("Portfolio configuration {} finished first", idx);
+                    cancel.store(true, Ordering::Relaxed);
+                    return result;
+                }
+            }
+        }
+        Err(first_error.expect("portfolio threads finished without a result"))
+    })
+}
+
+/// Whether the error holds for the problem, and not only for the configuration
+/// that returned it.
+fn is_definitive(error: &SolverError) -> bool {
+    !matches!(
+        error,
+        SolverError::Cancelled
+            | SolverError::Timeout
+            | SolverError::SolverProcess(_)
+            | SolverError::Unknown
+    )
+}
+
+/// A portfolio of `n` variations of the given settings: with and without core
+/// minimization, with the given expansion core size and with a larger one that
+/// expands states more eagerly, and with different random seeds for the SMT solver.
+pub fn diversify(settings: &SolverSettings, n: usize) -> Vec<SolverSettings> {
+    (0..n)
+        .map(|i| SolverSettings {
+            random_seed: settings.random_seed.wrapping_add((i / 4) as u32),
+            minimize_cores: if i % 2 == 0 {
+                settings.minimize_cores
+            } else {
+                !settings.minimize_cores
+            },
+            expansion_core_size: if (i / 2) % 2 == 0 {
+                settings.expansion_core_size
+            } else {
+                settings.expansion_core_size.saturating_mul(4)
+            },
+            // Each configuration writes its SMT-LIB files to its own directory.
+            smt2_dump_dir: settings
+                .smt2_dump_dir
+                .as_ref()
+                .map(|dir| dir.join(format!("config_{}", i))),
+            ..settings.clone()
+        })
+        .collect()
+}
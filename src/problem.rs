@@ -5,7 +5,143 @@ pub struct Problem {
     pub timelines: Vec<Timeline>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Problem {
+    /// Check that the problem is well-formed before solving it: timeline names are
+    /// unique, conditions and effects refer to existing timelines of the right kind
+    /// and to values that exist on them, the limits are consistent, and the goals
+    /// can be reached through the transitions of their timelines.
+    /// Returns a description of each problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut timelines = std::collections::HashMap::new();
+        for tl in self.timelines.iter() {
+            if timelines.insert(tl.name.as_str(), tl).is_some() {
+                errors.push(format!("duplicate timeline name \"{}\"", tl.name));
+            }
+        }
+
+        let has_value = |tl: &Timeline, value: &str| {
+            tl.token_types.iter().any(|tt| tt.value == value)
+                || tl.static_tokens.iter().any(|t| t.value == value)
+        };
+
+        let check_conditions = |errors: &mut Vec<String>, owner: &str, conds: &[Vec<Condition>]| {
+            for cond in conds.iter().flatten() {
+                match timelines.get(cond.timeline_ref.as_str()) {
+                    None => errors.push(format!(
+                        "{} has a condition on unknown timeline \"{}\"",
+                        owner, cond.timeline_ref
+                    )),
+                    Some(tl) if !cond.is_negative() && !has_value(tl, &cond.value) => {
+                        errors.push(format!(
+                            "{} has a condition on unknown value \"{}\" of timeline \"{}\"",
+                            owner, cond.value, cond.timeline_ref
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        for tl in self.timelines.iter() {
+            match &tl.kind {
+                TimelineKind::Symbolic => {}
+                TimelineKind::Reservoir(r) => {
+                    if r.min > r.max || r.initial < r.min || r.initial > r.max {
+                        errors.push(format!(
+                            "reservoir timeline \"{}\" has inconsistent limits",
+                            tl.name
+                        ));
+                    }
+                }
+                TimelineKind::Numeric(n) => {
+                    if let (Some(min), Some(max)) = (n.min, n.max) {
+                        if min > max {
+                            errors.push(format!(
+                                "numeric timeline \"{}\" has inconsistent limits",
+                                tl.name
+                            ));
+                        }
+                    }
+                }
+            }
+            if !tl.is_symbolic() && !tl.token_types.is_empty() {
+                errors.push(format!(
+                    "timeline \"{}\" tracks a level and cannot have token types",
+                    tl.name
+                ));
+            }
+
+            if tl.is_symbolic() && !tl.token_types.is_empty() {
+                let reachable = crate::transitionsolver::reachable_values(tl);
+                for token in tl.static_tokens.iter() {
+                    if matches!(token.const_time, TokenTime::Goal)
+                        && !reachable.contains_key(token.value.as_str())
+                    {
+                        errors.push(format!(
+                            "goal \"{}\" on timeline \"{}\" cannot be reached from its facts",
+                            token.value, tl.name
+                        ));
+                    }
+                }
+            }
+
+            for token in tl.static_tokens.iter() {
+                let owner = format!(
+                    "static token \"{}\" on timeline \"{}\"",
+                    token.value, tl.name
+                );
+                check_conditions(&mut errors, &owner, &token.conditions);
+                if let TokenTime::Fact(Some(start), Some(end)) = token.const_time {
+                    if start > end {
+                        errors.push(format!("{} ends before it starts", owner));
+                    }
+                }
+            }
+
+            for tt in tl.token_types.iter() {
+                let owner = format!("token type \"{}\" on timeline \"{}\"", tt.value, tl.name);
+                check_conditions(&mut errors, &owner, &tt.conditions);
+                if let (min, Some(max)) = tt.duration_limits {
+                    if min > max {
+                        errors.push(format!(
+                            "{} has a minimum duration above its maximum",
+                            owner
+                        ));
+                    }
+                }
+
+                let level_refs = tt
+                    .resource_effects
+                    .iter()
+                    .map(|e| &e.timeline_ref)
+                    .chain(tt.numeric_rates.iter().map(|r| &r.timeline_ref))
+                    .chain(tt.numeric_conditions.iter().map(|c| &c.timeline_ref));
+                for timeline_ref in level_refs {
+                    match timelines.get(timeline_ref.as_str()) {
+                        None => errors.push(format!(
+                            "{} refers to unknown timeline \"{}\"",
+                            owner, timeline_ref
+                        )),
+                        Some(target) if target.is_symbolic() => errors.push(format!(
+                            "{} changes the level of symbolic timeline \"{}\"",
+                            owner, timeline_ref
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Token {
     pub value: String,
     pub capacity: u32,
@@ -13,28 +149,134 @@ pub struct Token {
     pub conditions: Vec<Vec<Condition>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TokenTime {
     Fact(Option<usize>, Option<usize>),
     Goal,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Timeline {
     pub name: String,
     pub token_types: Vec<TokenType>,
     pub static_tokens: Vec<Token>,
+    #[serde(default)]
+    pub kind: TimelineKind,
+}
+
+impl Timeline {
+    /// Timelines that consist of a sequence of tokens, as opposed to
+    /// timelines that track the level of a resource.
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self.kind, TimelineKind::Symbolic)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub enum TimelineKind {
+    #[default]
+    Symbolic,
+    /// A consumable resource whose level is changed by the `resource_effects`
+    /// of tokens on other timelines. Reservoir timelines have no token types.
+    Reservoir(Reservoir),
+    /// A numeric quantity that changes linearly with the `numeric_rates` of tokens
+    /// on other timelines while they are active, and instantly with their
+    /// `resource_effects`. Numeric timelines have no token types.
+    Numeric(NumericVariable),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Reservoir {
+    pub initial: i32,
+    pub min: i32,
+    pub max: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct NumericVariable {
+    pub initial: f32,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct TokenType {
     pub value: String,
     pub duration_limits: (usize, Option<usize>),
     pub conditions: Vec<Vec<Condition>>,
     pub capacity: u32,
+    #[serde(default)]
+    pub cost: Cost,
+    #[serde(default)]
+    pub resource_effects: Vec<ResourceEffect>,
+    #[serde(default)]
+    pub numeric_rates: Vec<NumericRate>,
+    #[serde(default)]
+    pub numeric_conditions: Vec<NumericCondition>,
+    /// The duration of a contingent token is chosen by the environment within the
+    /// duration limits, and is only known when the token ends.
+    #[serde(default)]
+    pub contingent: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Cost of using a token, counted once for each token in the plan.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Cost {
+    #[serde(default)]
+    pub fixed: f32,
+    #[serde(default)]
+    pub per_time_unit: f32,
+}
+
+impl Cost {
+    pub fn is_zero(&self) -> bool {
+        self.fixed == 0.0 && self.per_time_unit == 0.0
+    }
+
+    pub fn of_duration(&self, duration: f32) -> f32 {
+        self.fixed + self.per_time_unit * duration
+    }
+}
+
+/// A change in the level of a reservoir timeline, happening at the start
+/// or at the end of a token. Positive amounts produce, negative amounts consume.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResourceEffect {
+    pub timeline_ref: String,
+    pub time: TokenPoint,
+    pub amount: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPoint {
+    Start,
+    End,
+}
+
+/// Change per time unit of a numeric timeline while the token is active.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NumericRate {
+    pub timeline_ref: String,
+    pub rate: f32,
+}
+
+/// Threshold on the value of a numeric or reservoir timeline at the start or end of
+/// the token. The value is taken just before any instantaneous effects at that time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NumericCondition {
+    pub timeline_ref: String,
+    pub time: TokenPoint,
+    pub relation: NumericRelation,
+    pub value: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericRelation {
+    GreaterOrEqual,
+    LessOrEqual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Condition {
     pub timeline_ref: String,
     pub temporal_relationship: TemporalRelationship,
@@ -50,16 +292,24 @@ impl Condition {
                 TemporalRelationship::MetByTransitionFrom
             ))
             && self.timeline_ref == timeline )
-        .then(|| self.value.as_str())
+        .then_some(self.value.as_str())
     }
     pub fn is_timeline_transition_to(&self, timeline: &str) -> Option<&str> {
         (matches!(self.temporal_relationship, TemporalRelationship::Meets)
             && self.timeline_ref == timeline)
-        .then(|| self.value.as_str())
+        .then_some(self.value.as_str())
+    }
+
+    /// Negative conditions forbid a temporal relation instead of requiring one.
+    pub fn is_negative(&self) -> bool {
+        matches!(
+            self.temporal_relationship,
+            TemporalRelationship::NotOverlapping | TemporalRelationship::NotDuring
+        )
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum TemporalRelationship {
     MetBy,
     MetByTransitionFrom,
@@ -70,6 +320,10 @@ pub enum TemporalRelationship {
     Cover,
     Equal,
     StartsAfter,
+    /// The token must not overlap any token with the given value.
+    NotOverlapping,
+    /// The token must not lie within any token with the given value.
+    NotDuring,
 }
 
 //
@@ -80,6 +334,8 @@ pub enum TemporalRelationship {
 pub struct Solution {
     pub timelines: Vec<SolutionTimeline>,
     pub end_of_time :f32,
+    #[serde(default)]
+    pub total_cost :f32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -94,11 +350,21 @@ pub struct SolutionToken {
     pub start_time: f32,
     pub end_time: f32,
     pub conditions :Vec<SolutionCondition>,
+    #[serde(default)]
+    pub cost :f32,
+    /// The token comes from a static fact rather than from a token type.
+    #[serde(default)]
+    pub fact :bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SolutionCondition {
     pub timeline :String,
+    /// The position of the target token in the `tokens` of its timeline.
     pub token_idx :usize,
+    #[serde(default)]
+    pub temporal_relationship :Option<TemporalRelationship>,
+    #[serde(default)]
+    pub amount :u32,
 }
 
@@ -0,0 +1,432 @@
+//! A local planning service.
+//!
+//! The server takes HTTP requests on a TCP port or a Unix socket, with JSON bodies
+//! in the format of `jsonapi`:
+//!
+//! - `POST /solve` solves a `SolveRequest` and returns the `SolveResponse`, as
+//!   `solve_json` does.
+//! - `POST /validate` checks a problem and returns `{"valid": ..., "errors": [...]}`.
+//! - `POST /jobs` starts solving a `SolveRequest` in the background and returns the
+//!   `JobStatus` with the id of the job.
+//! - `GET /jobs` returns the status of all jobs, and `GET /jobs/<id>` the status of
+//!   one job, with the response when it has finished.
+//! - `POST /jobs/<id>/cancel` stops a job, which then fails with `Cancelled` or
+//!   returns the best plan found so far. `DELETE /jobs/<id>` also removes it.
+//!
+//! Each job runs in its own thread, with the timeout from its settings or else the
+//! default timeout of the server. Only the latest finished jobs are kept. Errors
+//! are returned as `jsonapi::ErrorInfo`. Requests whose headers are longer than
+//! `MAX_HEADER_SIZE`, or whose bodies are larger than `MAX_BODY_SIZE`, are refused.
+
+use crate::jsonapi::{solve_problem, ErrorInfo, SolveRequest, SolveResponse};
+use crate::problem::Problem;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The largest request body that the server reads, in bytes.
+pub const MAX_BODY_SIZE: usize = 64 << 20;
+
+/// The largest request line and headers that the server reads, in bytes.
+pub const MAX_HEADER_SIZE: usize = 64 << 10;
+
+#[derive(Serialize, Debug)]
+pub struct JobStatus<'a> {
+    pub id: u64,
+    /// `Running`, or the status of the response.
+    pub status: &'static str,
+    /// Seconds since the job was started, or the time it took.
+    pub elapsed: f64,
+    pub cancelled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<&'a SolveResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ValidateResponse {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
+struct Job {
+    cancel: Arc<AtomicBool>,
+    started: Instant,
+    finished: Option<(f64, SolveResponse)>,
+    /// Jobs started by `POST /jobs`. The others are removed by their request.
+    background: bool,
+}
+
+struct State {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    next_id: Mutex<u64>,
+    default_timeout: Option<f64>,
+    max_jobs: usize,
+    keep_finished: usize,
+}
+
+/// The jobs of the server. Clones share the same jobs.
+#[derive(Clone)]
+pub struct Server {
+    state: Arc<State>,
+}
+
+impl Server {
+    /// A server that runs at most `max_jobs` jobs at the same time, and keeps the
+    /// status of the last `keep_finished` jobs started by `POST /jobs` after they
+    /// have finished. Requests without a timeout in their settings get
+    /// `default_timeout`.
+    pub fn new(default_timeout: Option<f64>, max_jobs: usize, keep_finished: usize) -> Self {
+        Server {
+            state: Arc::new(State {
+                jobs: Mutex::new(BTreeMap::new()),
+                next_id: Mutex::new(1),
+                default_timeout,
+                max_jobs: max_jobs.max(1),
+                keep_finished: keep_finished.max(1),
+            }),
+        }
+    }
+
+    /// Serve the connections on a TCP listener, each in its own thread.
+    pub fn serve_tcp(&self, listener: TcpListener) -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            std::thread::spawn(move || server.handle_connection(&stream, &stream));
+        }
+        Ok(())
+    }
+
+    /// Serve the connections on a Unix socket, each in its own thread.
+    #[cfg(unix)]
+    pub fn serve_unix(&self, listener: std::os::unix::net::UnixListener) -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            std::thread::spawn(move || server.handle_connection(&stream, &stream));
+        }
+        Ok(())
+    }
+
+    /// Read one HTTP request, and write the response.
+    pub fn handle_connection(&self, reader: impl Read, mut writer: impl Write) {
+        let (code, body) = match read_request(reader) {
+            Ok((method, path, body)) => {
+                let (code, body) = self.handle(&method, &path, &body);
+                log::info!("{} {} {}", method, path, code);
+                (code, body)
+            }
+            Err((code, body)) => {
+                log::warn!("Bad request: {}", body);
+                (code, body)
+            }
+        };
+        let reason = match code {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "",
+        };
+        let _ = write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            code,
+            reason,
+            body.len(),
+            body
+        );
+        let _ = writer.flush();
+    }
+
+    /// Handle a request given by its method, path and body, and return the status
+    /// code and the JSON body of the response.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> (u16, String) {
+        let parts = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>();
+        match (method, parts.as_slice()) {
+            ("POST", ["solve"]) => {
+                let request = match parse_request(body) {
+                    Ok(r) => r,
+                    Err(e) => return e,
+                };
+                match self.start_job(request, false) {
+                    Ok(id) => match self.take_response(id) {
+                        Some(response) => (200, response),
+                        None => error(
+                            404,
+                            "UnknownJob",
+                            format!("job {} was deleted before it finished", id),
+                        ),
+                    },
+                    Err(e) => e,
+                }
+            }
+            ("POST", ["validate"]) => match serde_json::from_str::<Problem>(body) {
+                Ok(problem) => {
+                    let errors = problem.validate().err().unwrap_or_default();
+                    let response = ValidateResponse {
+                        valid: errors.is_empty(),
+                        errors,
+                    };
+                    (200, serde_json::to_string(&response).unwrap())
+                }
+                Err(e) => error(400, "InvalidJson", e.to_string()),
+            },
+            ("POST", ["jobs"]) => {
+                let request = match parse_request(body) {
+                    Ok(r) => r,
+                    Err(e) => return e,
+                };
+                match self.start_job(request, true) {
+                    Ok(id) => (202, self.status_json(id).unwrap()),
+                    Err(e) => e,
+                }
+            }
+            ("GET", ["jobs"]) => {
+                let jobs = self.state.jobs.lock().unwrap();
+                let statuses = jobs
+                    .iter()
+                    .map(|(id, job)| job.status(*id, false))
+                    .collect::<Vec<_>>();
+                (200, serde_json::to_string(&statuses).unwrap())
+            }
+            ("GET", ["jobs", id]) => self.with_job(id, |id| self.status_json(id)),
+            ("POST", ["jobs", id, "cancel"]) => self.with_job(id, |id| {
+                self.cancel(id);
+                self.status_json(id)
+            }),
+            ("DELETE", ["jobs", id]) => self.with_job(id, |id| {
+                self.cancel(id);
+                let status = self.status_json(id);
+                self.state.jobs.lock().unwrap().remove(&id);
+                status
+            }),
+            _ => error(
+                404,
+                "NotFound",
+                format!("no endpoint for {} {}", method, path),
+            ),
+        }
+    }
+
+    /// Start a job, in a new thread if `background` is set, and otherwise in the
+    /// current thread until it has finished.
+    fn start_job(&self, mut request: SolveRequest, background: bool) -> Result<u64, (u16, String)> {
+        if request.settings.timeout.is_none() {
+            request.settings.timeout = self.state.default_timeout;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let id = {
+            let mut jobs = self.state.jobs.lock().unwrap();
+            let running = jobs.values().filter(|j| j.finished.is_none()).count();
+            if running >= self.state.max_jobs {
+                return Err(error(
+                    503,
+                    "TooManyJobs",
+                    format!("{} jobs are already running", running),
+                ));
+            }
+            let mut next_id = self.state.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            jobs.insert(
+                id,
+                Job {
+                    cancel: cancel.clone(),
+                    started: Instant::now(),
+                    finished: None,
+                    background,
+                },
+            );
+            id
+        };
+
+        let server = self.clone();
+        let run = move || {
+            let response = solve_problem(&request.problem, &request.settings, Some(&cancel));
+            server.finish(id, response);
+        };
+        if background {
+            std::thread::spawn(run);
+        } else {
+            run();
+        }
+        Ok(id)
+    }
+
+    /// Store the response of a job, and remove the earliest finished background
+    /// jobs beyond `keep_finished`.
+    fn finish(&self, id: u64, response: SolveResponse) {
+        let mut jobs = self.state.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&id) {
+            job.finished = Some((job.started.elapsed().as_secs_f64(), response));
+        }
+        let finished = jobs
+            .iter()
+            .filter(|(_, job)| job.background && job.finished.is_some())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let excess = finished.len().saturating_sub(self.state.keep_finished);
+        for id in &finished[..excess] {
+            jobs.remove(id);
+        }
+    }
+
+    fn cancel(&self, id: u64) {
+        if let Some(job) = self.state.jobs.lock().unwrap().get(&id) {
+            job.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The response of a finished job, which is removed from the jobs, or `None` if
+    /// the job has already been deleted.
+    fn take_response(&self, id: u64) -> Option<String> {
+        let job = self.state.jobs.lock().unwrap().remove(&id);
+        let (_, response) = job?.finished?;
+        Some(serde_json::to_string(&response).unwrap())
+    }
+
+    fn status_json(&self, id: u64) -> Option<String> {
+        let jobs = self.state.jobs.lock().unwrap();
+        let status = jobs.get(&id)?.status(id, true);
+        Some(serde_json::to_string(&status).unwrap())
+    }
+
+    fn with_job(&self, id: &str, f: impl FnOnce(u64) -> Option<String>) -> (u16, String) {
+        match id.parse::<u64>().ok().and_then(f) {
+            Some(body) => (200, body),
+            None => error(404, "UnknownJob", format!("no job with id {}", id)),
+        }
+    }
+}
+
+impl Job {
+    fn status(&self, id: u64, with_response: bool) -> JobStatus<'_> {
+        let (status, elapsed, response) = match self.finished.as_ref() {
+            None => ("Running", self.started.elapsed().as_secs_f64(), None),
+            Some((elapsed, response)) => {
+                let status = match response {
+                    SolveResponse::Solved { .. } => "Solved",
+                    SolveResponse::Failed { .. } => "Failed",
+                };
+                (status, *elapsed, Some(response).filter(|_| with_response))
+            }
+        };
+        JobStatus {
+            id,
+            status,
+            elapsed,
+            cancelled: self.cancel.load(Ordering::Relaxed),
+            response,
+        }
+    }
+}
+
+fn parse_request(body: &str) -> Result<SolveRequest, (u16, String)> {
+    serde_json::from_str::<SolveRequest>(body).map_err(|e| error(400, "InvalidJson", e.to_string()))
+}
+
+fn error(code: u16, kind: &str, message: String) -> (u16, String) {
+    let error = ErrorInfo {
+        kind: kind.to_string(),
+        message,
+        details: Vec::new(),
+    };
+    (code, serde_json::to_string(&error).unwrap())
+}
+
+/// The method, path and body of an HTTP request, or the status code and the body
+/// of an error response.
+fn read_request(reader: impl Read) -> Result<(String, String, String), (u16, String)> {
+    let bad_request = |message: String| error(400, "BadRequest", message);
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    let mut header_left = MAX_HEADER_SIZE;
+    read_header_line(&mut reader, &mut line, &mut header_left)?;
+    let mut request_line = line.split_whitespace();
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Some(m), Some(p)) => (m.to_string(), p.to_string()),
+        _ => {
+            return Err(bad_request(format!(
+                "invalid request line {:?}",
+                line.trim_end()
+            )))
+        }
+    };
+
+    let mut content_length = 0;
+    loop {
+        if read_header_line(&mut reader, &mut line, &mut header_left)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().map_err(|_| {
+                    bad_request(format!("invalid Content-Length {:?}", value.trim()))
+                })?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err(error(
+            413,
+            "PayloadTooLarge",
+            format!(
+                "the body has {} bytes, and the limit is {}",
+                content_length, MAX_BODY_SIZE
+            ),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| bad_request(e.to_string()))?;
+    let body = String::from_utf8(body).map_err(|e| bad_request(e.to_string()))?;
+    Ok((method, path, body))
+}
+
+/// Read a line of the request line or headers into `line`, with at most `left`
+/// bytes left for them, and return its length.
+fn read_header_line(
+    reader: &mut impl BufRead,
+    line: &mut String,
+    left: &mut usize,
+) -> Result<usize, (u16, String)> {
+    let too_large = || {
+        error(
+            431,
+            "HeaderTooLarge",
+            format!("the headers are longer than {} bytes", MAX_HEADER_SIZE),
+        )
+    };
+    if *left == 0 {
+        return Err(too_large());
+    }
+    line.clear();
+    let length = reader
+        .take(*left as u64)
+        .read_line(line)
+        .map_err(|e| error(400, "BadRequest", e.to_string()))?;
+    *left -= length;
+    if *left == 0 && !line.ends_with('\n') {
+        return Err(too_large());
+    }
+    Ok(length)
+}
@@ -0,0 +1,550 @@
+//! A backend that runs an SMT solver as a separate process and talks to it in
+//! SMT-LIB2 over its standard input and output.
+//!
+//! Any solver that supports the incremental `check-sat-assuming` and
+//! `get-unsat-assumptions` commands can be used, for example `z3 -in` or
+//! `cvc5 --incremental`. The terms are kept as SMT-LIB strings, and the declarations
+//! and assertions are sent to the solver as soon as they are made.
+
+use crate::backend::{Backend, SatResult};
+use log::trace;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// An SMT-LIB term.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Term(Rc<str>);
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Term {
+    fn new(s: String) -> Self {
+        Term(s.into())
+    }
+
+    fn is_symbol(&self) -> bool {
+        !self.0.starts_with('(')
+    }
+}
+
+pub struct SmtLibBackend {
+    child: Mutex<Child>,
+    stdin: RefCell<BufWriter<ChildStdin>>,
+    stdout: RefCell<BufReader<ChildStdout>>,
+    n_names: Cell<usize>,
+    /// The declarations and assertions sent to the solver.
+    script: RefCell<String>,
+    /// Assumptions that are not symbols are replaced by a named proxy, so that
+    /// they can be recognized in the unsat cores.
+    proxies: RefCell<HashMap<Term, Term>>,
+    proxied: RefCell<HashMap<Term, Term>>,
+    /// The first error from the solver process. After an error, nothing more is
+    /// sent to the solver.
+    error: RefCell<Option<String>>,
+}
+
+impl fmt::Debug for SmtLibBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtLibBackend")
+            .field("child", &self.child)
+            .field("n_names", &self.n_names)
+            .finish()
+    }
+}
+
+impl SmtLibBackend {
+    /// Start the solver from a command line, such as `z3 -in`, split on whitespace.
+    pub fn spawn(command: &str, random_seed: u32) -> std::io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty solver command")
+        })?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let stdin = BufWriter::new(child.stdin.take().unwrap());
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let backend = SmtLibBackend {
+            child: Mutex::new(child),
+            stdin: RefCell::new(stdin),
+            stdout: RefCell::new(stdout),
+            n_names: Cell::new(0),
+            script: RefCell::new(String::new()),
+            proxies: RefCell::new(HashMap::new()),
+            proxied: RefCell::new(HashMap::new()),
+            error: RefCell::new(None),
+        };
+
+        backend.send("(set-option :print-success false)");
+        backend.send("(set-option :produce-models true)");
+        backend.send("(set-option :produce-unsat-assumptions true)");
+        backend.send(&format!("(set-option :random-seed {})", random_seed));
+        backend.send("(set-logic QF_LRA)");
+        if let Some(e) = backend.error.borrow_mut().take() {
+            return Err(std::io::Error::other(e));
+        }
+        Ok(backend)
+    }
+
+    /// Record an error, unless there already is one.
+    fn fail(&self, message: String) {
+        let mut error = self.error.borrow_mut();
+        if error.is_none() {
+            *error = Some(message);
+        }
+    }
+
+    fn send(&self, command: &str) {
+        trace!("smt> {}", command);
+        if self.error.borrow().is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.stdin.borrow_mut(), "{}", command) {
+            self.fail(e.to_string());
+        }
+    }
+
+    /// Send a command and read one s-expression of response, or `None` after an error.
+    fn query(&self, command: &str) -> Option<Sexp> {
+        self.send(command);
+        if self.error.borrow().is_some() {
+            return None;
+        }
+        match self.read_response() {
+            Ok(response) => Some(response),
+            Err(e) => {
+                self.fail(e);
+                None
+            }
+        }
+    }
+
+    fn read_response(&self) -> Result<Sexp, String> {
+        self.stdin.borrow_mut().flush().map_err(|e| e.to_string())?;
+        let mut stdout = self.stdout.borrow_mut();
+        let mut response = read_sexp(&mut *stdout).map_err(|e| e.to_string())?;
+        // Responses to options that the solver does not support can come before
+        // the response to the command.
+        while response == Sexp::Atom("unsupported".to_string()) {
+            response = read_sexp(&mut *stdout).map_err(|e| e.to_string())?;
+        }
+        trace!("smt< {:?}", response);
+        if let Sexp::List(xs) = &response {
+            if xs.first() == Some(&Sexp::Atom("error".to_string())) {
+                return Err(sexp_to_string(&response));
+            }
+        }
+        Ok(response)
+    }
+
+    fn fresh_name(&self, prefix: &str, sort: &str) -> Term {
+        let n = self.n_names.get();
+        self.n_names.set(n + 1);
+        let name = symbol(&format!("{}!{}", prefix, n));
+        let decl = format!("(declare-fun {} () {})", name, sort);
+        self.send(&decl);
+        self.script.borrow_mut().push_str(&decl);
+        self.script.borrow_mut().push('\n');
+        Term::new(name)
+    }
+
+    fn get_value(&self, a: &Term) -> Option<Sexp> {
+        match self.query(&format!("(get-value ({}))", a))? {
+            Sexp::List(mut pairs) if pairs.len() == 1 => match pairs.pop().unwrap() {
+                Sexp::List(mut pair) if pair.len() == 2 => pair.pop(),
+                x => {
+                    self.fail(format!("unexpected value: {}", sexp_to_string(&x)));
+                    None
+                }
+            },
+            x => {
+                self.fail(format!("unexpected value: {}", sexp_to_string(&x)));
+                None
+            }
+        }
+    }
+}
+
+impl Drop for SmtLibBackend {
+    fn drop(&mut self) {
+        self.send("(exit)");
+        let _ = self.stdin.borrow_mut().flush();
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// A symbol, quoted if it contains characters that are not allowed in simple symbols.
+fn symbol(name: &str) -> String {
+    let simple = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c));
+    if simple {
+        name.to_string()
+    } else {
+        format!("|{}|", name.replace(['|', '\\'], "_"))
+    }
+}
+
+fn real_literal(value: i64) -> String {
+    if value < 0 {
+        format!("(- {}.0)", -value)
+    } else {
+        format!("{}.0", value)
+    }
+}
+
+/// Apply an associative operator, avoiding the unary and nullary forms.
+fn nary<T: fmt::Display>(op: &str, xs: &[&T], empty: &str) -> Term {
+    match xs {
+        [] => Term::new(empty.to_string()),
+        [x] => Term::new(x.to_string()),
+        xs => {
+            let args = xs.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            Term::new(format!("({} {})", op, args.join(" ")))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+/// Read lines until they make up a complete s-expression.
+fn read_sexp(reader: &mut impl BufRead) -> std::io::Result<Sexp> {
+    let mut text = String::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    loop {
+        let start = text.len();
+        if reader.read_line(&mut text)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the SMT solver closed its output",
+            ));
+        }
+        for c in text[start..].chars() {
+            match c {
+                '"' => in_string = !in_string,
+                '(' if !in_string => depth += 1,
+                ')' if !in_string => depth -= 1,
+                _ => {}
+            }
+        }
+        if depth <= 0 && !text.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut tokens = tokenize(&text).into_iter().peekable();
+    Ok(parse_sexp(&mut tokens))
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' => tokens.push(c.to_string()),
+            '"' | '|' => {
+                let mut token = c.to_string();
+                for d in chars.by_ref() {
+                    token.push(d);
+                    if d == c {
+                        break;
+                    }
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = c.to_string();
+                while let Some(&d) = chars.peek() {
+                    if d.is_whitespace() || d == '(' || d == ')' {
+                        break;
+                    }
+                    token.push(d);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_sexp(tokens: &mut std::iter::Peekable<impl Iterator<Item = String>>) -> Sexp {
+    match tokens.next() {
+        Some(t) if t == "(" => {
+            let mut xs = Vec::new();
+            while let Some(t) = tokens.peek() {
+                if t == ")" {
+                    tokens.next();
+                    break;
+                }
+                xs.push(parse_sexp(tokens));
+            }
+            Sexp::List(xs)
+        }
+        Some(t) => Sexp::Atom(t),
+        None => Sexp::List(vec![]),
+    }
+}
+
+fn sexp_to_string(x: &Sexp) -> String {
+    match x {
+        Sexp::Atom(a) => a.clone(),
+        Sexp::List(xs) => format!(
+            "({})",
+            xs.iter().map(sexp_to_string).collect::<Vec<_>>().join(" ")
+        ),
+    }
+}
+
+/// Evaluate a real-valued constant such as `2.5`, `(- 1.0)` or `(/ 1.0 3.0)`.
+fn eval_real(x: &Sexp) -> Option<f64> {
+    match x {
+        Sexp::Atom(a) => a.parse().ok(),
+        Sexp::List(xs) => match xs.as_slice() {
+            [Sexp::Atom(op), a] if op == "-" => Some(-eval_real(a)?),
+            [Sexp::Atom(op), a, b] if op == "/" => Some(eval_real(a)? / eval_real(b)?),
+            [Sexp::Atom(op), a, b] if op == "-" => Some(eval_real(a)? - eval_real(b)?),
+            _ => None,
+        },
+    }
+}
+
+impl Backend for SmtLibBackend {
+    type Bool = Term;
+    type Real = Term;
+
+    fn fresh_bool(&self, prefix: &str) -> Term {
+        self.fresh_name(prefix, "Bool")
+    }
+
+    fn fresh_real(&self, prefix: &str) -> Term {
+        self.fresh_name(prefix, "Real")
+    }
+
+    fn bool_const(&self, value: bool) -> Term {
+        Term::new(value.to_string())
+    }
+
+    fn real_const(&self, num: i32, den: i32) -> Term {
+        if den == 1 {
+            Term::new(real_literal(num as i64))
+        } else {
+            Term::new(format!(
+                "(/ {} {})",
+                real_literal(num as i64),
+                real_literal(den as i64)
+            ))
+        }
+    }
+
+    fn real_from_f32(&self, value: f32) -> Term {
+        // The Display impl for floats never uses exponent notation.
+        let mut string = format!("{}", value.abs());
+        if !string.contains('.') {
+            string.push_str(".0");
+        }
+        if value < 0.0 {
+            Term::new(format!("(- {})", string))
+        } else {
+            Term::new(string)
+        }
+    }
+
+    fn not(&self, a: &Term) -> Term {
+        Term::new(format!("(not {})", a))
+    }
+
+    fn and(&self, xs: &[&Term]) -> Term {
+        nary("and", xs, "true")
+    }
+
+    fn or(&self, xs: &[&Term]) -> Term {
+        nary("or", xs, "false")
+    }
+
+    fn implies(&self, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(=> {} {})", a, b))
+    }
+
+    fn pb_le(&self, xs: &[(&Term, i32)], k: i32) -> Term {
+        // Pseudo-boolean constraints are not part of the standard, so they are
+        // encoded as a sum of reals.
+        let terms = xs
+            .iter()
+            .map(|(x, c)| Term::new(format!("(ite {} {} 0.0)", x, real_literal(*c as i64))))
+            .collect::<Vec<_>>();
+        let sum = nary("+", &terms.iter().collect::<Vec<_>>(), "0.0");
+        Term::new(format!("(<= {} {})", sum, real_literal(k as i64)))
+    }
+
+    fn add(&self, xs: &[&Term]) -> Term {
+        nary("+", xs, "0.0")
+    }
+
+    fn sub(&self, xs: &[&Term]) -> Term {
+        nary("-", xs, "0.0")
+    }
+
+    fn mul(&self, xs: &[&Term]) -> Term {
+        nary("*", xs, "1.0")
+    }
+
+    fn ite(&self, cond: &Term, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(ite {} {} {})", cond, a, b))
+    }
+
+    fn le(&self, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(<= {} {})", a, b))
+    }
+
+    fn lt(&self, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(< {} {})", a, b))
+    }
+
+    fn ge(&self, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(>= {} {})", a, b))
+    }
+
+    fn eq(&self, a: &Term, b: &Term) -> Term {
+        Term::new(format!("(= {} {})", a, b))
+    }
+
+    fn assert(&self, a: &Term) {
+        let assertion = format!("(assert {})", a);
+        self.send(&assertion);
+        self.script.borrow_mut().push_str(&assertion);
+        self.script.borrow_mut().push('\n');
+    }
+
+    fn check(&self, assumptions: &[Term], cancel: Option<&AtomicBool>) -> SatResult {
+        let names = assumptions
+            .iter()
+            .map(|a| {
+                if a.is_symbol() {
+                    return a.clone();
+                }
+                if let Some(name) = self.proxies.borrow().get(a) {
+                    return name.clone();
+                }
+                let name = self.fresh_bool("assumption");
+                self.assert(&self.eq(&name, a));
+                self.proxies.borrow_mut().insert(a.clone(), name.clone());
+                self.proxied.borrow_mut().insert(name.clone(), a.clone());
+                name
+            })
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        let command = format!("(check-sat-assuming ({}))", names.join(" "));
+
+        let response = match cancel {
+            None => self.query(&command),
+            Some(cancel) => {
+                // Stop the solver process from another thread when the cancel flag is set.
+                let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+                let child = &self.child;
+                std::thread::scope(|s| {
+                    s.spawn(move || {
+                        while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                            done_rx.recv_timeout(std::time::Duration::from_millis(10))
+                        {
+                            if cancel.load(Ordering::Relaxed) {
+                                let _ = child.lock().unwrap().kill();
+                                break;
+                            }
+                        }
+                    });
+                    let response = self.query(&command);
+                    drop(done_tx);
+                    response
+                })
+            }
+        };
+
+        match response {
+            Some(Sexp::Atom(a)) if a == "sat" => SatResult::Sat,
+            Some(Sexp::Atom(a)) if a == "unsat" => SatResult::Unsat,
+            Some(Sexp::Atom(a)) if a == "unknown" => SatResult::Unknown,
+            Some(x) => {
+                self.fail(format!("unexpected response: {}", sexp_to_string(&x)));
+                SatResult::Unknown
+            }
+            None => SatResult::Unknown,
+        }
+    }
+
+    fn unsat_core(&self) -> Vec<Term> {
+        let xs = match self.query("(get-unsat-assumptions)") {
+            Some(Sexp::List(xs)) => xs,
+            Some(x) => {
+                self.fail(format!("unexpected unsat core: {}", sexp_to_string(&x)));
+                return Vec::new();
+            }
+            None => return Vec::new(),
+        };
+        let proxied = self.proxied.borrow();
+        xs.iter()
+            .map(|x| {
+                let name = Term::new(sexp_to_string(x));
+                proxied.get(&name).cloned().unwrap_or(name)
+            })
+            .collect()
+    }
+
+    fn bool_value(&self, a: &Term) -> bool {
+        match self.get_value(a) {
+            Some(Sexp::Atom(v)) if v == "true" => true,
+            Some(Sexp::Atom(v)) if v == "false" => false,
+            Some(x) => {
+                self.fail(format!("unexpected boolean value: {}", sexp_to_string(&x)));
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn real_value(&self, a: &Term) -> f32 {
+        match self.get_value(a) {
+            Some(x) => eval_real(&x).unwrap_or_else(|| {
+                self.fail(format!("unexpected real value: {}", sexp_to_string(&x)));
+                0.0
+            }) as f32,
+            None => 0.0,
+        }
+    }
+
+    fn to_smtlib(&self) -> String {
+        self.script.borrow().clone()
+    }
+
+    fn term_to_smtlib(&self, a: &Term) -> String {
+        a.to_string()
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.borrow().clone()
+    }
+}
@@ -1,24 +1,153 @@
+// Lints that the original solver code does not follow.
+#![allow(
+    clippy::filter_map_bool_then,
+    clippy::len_zero,
+    clippy::manual_try_fold,
+    clippy::ptr_arg,
+    clippy::unnecessary_get_then_check,
+    clippy::unnecessary_lazy_evaluations
+)]
+
+#[cfg(feature = "z3")]
+use crate::z3backend::Z3Backend;
 use crate::{
+    // transitionrelation::{transitionrelation, TransitionRelation},
+    backend::{Backend, SatResult},
     problem::{
-        self, Problem, Solution, SolutionCondition, SolutionTimeline, SolutionToken,
-        TemporalRelationship, TokenTime, TokenType,
+        self, NumericRelation, Problem, Solution, SolutionCondition, SolutionTimeline,
+        SolutionToken, TemporalRelationship, TimelineKind, TokenPoint, TokenTime, TokenType,
     },
-    // transitionrelation::{transitionrelation, TransitionRelation},
-    z3real_value,
+    smtlibbackend::SmtLibBackend,
     SolverError,
 };
 use log::{debug, info, trace, warn};
-use std::collections::{HashMap, HashSet};
-use z3::ast::{Ast, Bool, Real};
-
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SolverSettings {
     pub minimize_cores: bool,
+    /// After the first plan has been found, keep searching for plans that
+    /// improve on this objective.
+    pub optimize: Option<Objective>,
+    /// How much each new plan must improve on the objective value of the previous one.
+    pub min_improvement: f32,
+    /// Since the problem is expanded lazily, it is usually not possible to prove that
+    /// the best plan is optimal. Give up after this many refinements without finding
+    /// a better plan.
+    pub optimization_patience: usize,
+    /// Only accept plans that are strongly controllable, i.e. where the start times of
+    /// all controllable tokens can be fixed in advance, whatever the actual durations
+    /// of the contingent tokens turn out to be.
+    pub strong_controllability: bool,
+    /// Seed for the random choices of the SMT solver. The solver is otherwise
+    /// deterministic, so the same problem and seed give the same plan.
+    pub random_seed: u32,
+    /// Write the solver state and the assumptions of every refinement iteration to
+    /// numbered `.smt2` files in this directory, so that the calls to the SMT solver
+    /// can be reproduced with other tools.
+    pub smt2_dump_dir: Option<PathBuf>,
+    /// Run this SMT-LIB2 solver command, such as `z3 -in` or `cvc5 --incremental`,
+    /// as a separate process instead of using the built-in Z3.
+    pub smt_solver_command: Option<String>,
+    /// Stop after this many seconds. When optimizing, the best plan found so far is
+    /// returned, otherwise `SolverError::Timeout`.
+    pub timeout: Option<f64>,
+    pub resource_encoding: ResourceEncoding,
+    /// Goal states and state sequences are expanded when the unsatisfiable core has
+    /// at most this many literals, or when there is nothing else to expand. Larger
+    /// cores only add causal links, which keeps the problem small for longer.
+    pub expansion_core_size: usize,
 }
 
 impl Default for SolverSettings {
     fn default() -> Self {
         SolverSettings {
             minimize_cores: false,
+            optimize: None,
+            min_improvement: 1.0,
+            optimization_patience: 25,
+            strong_controllability: false,
+            random_seed: 0,
+            smt2_dump_dir: None,
+            smt_solver_command: None,
+            timeout: None,
+            resource_encoding: ResourceEncoding::Pairwise,
+            expansion_core_size: 5,
+        }
+    }
+}
+
+/// How the capacity of resources is encoded for the SMT solver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceEncoding {
+    /// Pairwise exclusion between the users of resources with capacity 1, which is
+    /// usually faster, and pseudo-boolean constraints for larger capacities.
+    Pairwise,
+    /// Pseudo-boolean constraints for all resources.
+    PseudoBoolean,
+}
+
+impl std::str::FromStr for ResourceEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pairwise" => Ok(ResourceEncoding::Pairwise),
+            "pseudoboolean" | "pseudo-boolean" | "pb" => Ok(ResourceEncoding::PseudoBoolean),
+            _ => Err(format!("unknown resource encoding \"{}\"", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Objective {
+    /// The sum of the costs of all tokens in the plan.
+    Cost,
+    /// The end of time, i.e. the time when all timelines have reached their goals.
+    Makespan,
+    /// A weighted sum of the total cost and the makespan.
+    Weighted { cost: f32, makespan: f32 },
+}
+
+impl std::str::FromStr for Objective {
+    type Err = String;
+
+    /// Parses `cost`, `makespan` or `weighted:<cost>,<makespan>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        match lower.as_str() {
+            "cost" => Ok(Objective::Cost),
+            "makespan" => Ok(Objective::Makespan),
+            _ => {
+                let weights = lower
+                    .strip_prefix("weighted:")
+                    .and_then(|w| w.split_once(','))
+                    .and_then(|(c, m)| Some((c.trim().parse().ok()?, m.trim().parse().ok()?)));
+                match weights {
+                    Some((cost, makespan)) => Ok(Objective::Weighted { cost, makespan }),
+                    None => Err(format!(
+                        "unknown objective \"{}\", expected cost, makespan or weighted:<cost>,<makespan>",
+                        s
+                    )),
+                }
+            }
+        }
+    }
+}
+
+impl Objective {
+    pub fn value(&self, solution: &Solution) -> f32 {
+        match self {
+            Objective::Cost => solution.total_cost,
+            Objective::Makespan => solution.end_of_time,
+            Objective::Weighted { cost, makespan } => {
+                cost * solution.total_cost + makespan * solution.end_of_time
+            }
         }
     }
 }
@@ -26,57 +155,312 @@ impl Default for SolverSettings {
 // A state is a choice between several possible tokens
 // in the sequence of values that make up a timeline.
 #[derive(Debug)]
-struct State<'z> {
-    start_time: Real<'z>,
-    end_time: Real<'z>,
+struct State<B: Backend> {
+    start_time: B::Real,
+    end_time: B::Real,
+    // The latest times that the state can start and end, depending on the durations
+    // of contingent tokens. These are the same as `start_time` and `end_time` unless
+    // the timeline has contingent token types.
+    start_late: B::Real,
+    end_late: B::Real,
     timeline: usize,
     tokens: Vec<usize>,
     state_seq: usize,
-    active: Bool<'z>,
-    activate_next: Bool<'z>,
+    active: B::Bool,
+    activate_next: B::Bool,
     can_expand: bool,
 }
 
+impl<B: Backend> State<B> {
+    fn start(&self) -> TimePoint<'_, B> {
+        TimePoint {
+            early: &self.start_time,
+            late: &self.start_late,
+        }
+    }
+
+    fn end(&self) -> TimePoint<'_, B> {
+        TimePoint {
+            early: &self.end_time,
+            late: &self.end_late,
+        }
+    }
+}
+
+/// The earliest and latest time of an event.
+struct TimePoint<'a, B: Backend> {
+    early: &'a B::Real,
+    late: &'a B::Real,
+}
+
+impl<'a, B: Backend> Clone for TimePoint<'a, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, B: Backend> Copy for TimePoint<'a, B> {}
+
+impl<'a, B: Backend> TimePoint<'a, B> {
+    fn is_fixed(&self) -> bool {
+        self.early == self.late
+    }
+}
+
+/// `a + delay <= b` for any durations of the contingent tokens.
+fn time_le<B: Backend>(
+    backend: &B,
+    a: TimePoint<'_, B>,
+    delay: i32,
+    b: TimePoint<'_, B>,
+) -> B::Bool {
+    if delay == 0 {
+        backend.le(a.late, b.early)
+    } else {
+        backend.le(
+            &backend.add(&[a.late, &backend.real_const(delay, 1)]),
+            b.early,
+        )
+    }
+}
+
+/// `a < b` for any durations of the contingent tokens.
+fn time_lt<B: Backend>(backend: &B, a: TimePoint<'_, B>, b: TimePoint<'_, B>) -> B::Bool {
+    backend.lt(a.late, b.early)
+}
+
+/// `a < b` for some durations of the contingent tokens.
+fn time_may_be_lt<B: Backend>(backend: &B, a: TimePoint<'_, B>, b: TimePoint<'_, B>) -> B::Bool {
+    backend.lt(a.early, b.late)
+}
+
+/// `a = b` for any durations of the contingent tokens, which requires both to be fixed.
+fn time_eq<B: Backend>(backend: &B, a: TimePoint<'_, B>, b: TimePoint<'_, B>) -> B::Bool {
+    if a.is_fixed() && b.is_fixed() {
+        backend.eq(a.early, b.early)
+    } else {
+        backend.and(&[&time_le(backend, a, 0, b), &time_le(backend, b, 0, a)])
+    }
+}
+
 #[derive(Debug)]
-struct Token<'a, 'z> {
-    active: Option<Bool<'z>>,
+struct Token<'a, B: Backend> {
+    active: Option<B::Bool>,
     state: usize,
     value: &'a str,
     fact: bool,
     conditions: Vec<usize>,
+    cost: problem::Cost,
 }
 
 #[derive(Debug)]
-struct Condition<'a, 'z3> {
-    active: Option<Bool<'z3>>,
+struct Condition<'a, B: Backend> {
+    active: Option<B::Bool>,
     token_idx: usize,
     cond_spec: &'a problem::Condition,
     token_queue: usize,
-    alternatives_extension: Option<Bool<'z3>>,
-    target_chosen: Vec<(usize, Option<Bool<'z3>>)>,
+    alternatives_extension: Option<B::Bool>,
+    target_chosen: Vec<(usize, Option<B::Bool>)>,
 }
 
-struct Timeline<'z> {
+struct Timeline<B: Backend> {
     states: Vec<usize>,
-    goal_state_extension: Option<Bool<'z>>,
+    goal_state_extension: Option<B::Bool>,
     facts_only: bool,
 }
 
+/// Statistics about a solver run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SolveStats {
+    /// Number of calls to the SMT solver.
+    pub smt_calls: usize,
+    /// Number of plans found, which is more than one when optimizing.
+    pub plans_found: usize,
+    /// Size of the formulation at the last call to the SMT solver.
+    pub states: usize,
+    pub tokens: usize,
+    pub conditions: usize,
+    /// Wall-clock time in seconds.
+    pub solve_time: f64,
+}
+
 pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, SolverError> {
+    solve_impl(problem, settings, None, &mut SolveStats::default())
+}
+
+/// Solve the problem and also return statistics about the solver run. The solver
+/// can optionally be stopped with a `cancel` flag, as in `solve_with_cancel`.
+pub fn solve_with_stats(
+    problem: &Problem,
+    settings: &SolverSettings,
+    cancel: Option<&AtomicBool>,
+) -> (Result<Solution, SolverError>, SolveStats) {
+    let start = std::time::Instant::now();
+    let mut stats = SolveStats::default();
+    let result = solve_impl(problem, settings, cancel, &mut stats);
+    stats.solve_time = start.elapsed().as_secs_f64();
+    (result, stats)
+}
+
+/// Solve the problem until a plan is found or the `cancel` flag is set, in which case
+/// the best plan found so far when optimizing, or `SolverError::Cancelled`, is returned.
+pub fn solve_with_cancel(
+    problem: &Problem,
+    settings: &SolverSettings,
+    cancel: &AtomicBool,
+) -> Result<Solution, SolverError> {
+    solve_impl(problem, settings, Some(cancel), &mut SolveStats::default())
+}
+
+/// The solver command used when the crate is built without the `z3` feature.
+pub const DEFAULT_SMT_SOLVER_COMMAND: &str = "z3 -in";
+
+fn solve_impl(
+    problem: &Problem,
+    settings: &SolverSettings,
+    cancel: Option<&AtomicBool>,
+    stats: &mut SolveStats,
+) -> Result<Solution, SolverError> {
+    #[cfg(feature = "z3")]
+    if settings.smt_solver_command.is_none() {
+        let z3_config = z3::Config::new();
+        let ctx = z3::Context::new(&z3_config);
+        let mut backend = Z3Backend::new(&ctx, settings.random_seed);
+        if settings.smt2_dump_dir.is_some() {
+            backend = backend.record_smtlib();
+        }
+        return solve_backend_impl(problem, settings, &backend, cancel, stats);
+    }
+
+    let command = settings
+        .smt_solver_command
+        .as_deref()
+        .unwrap_or(DEFAULT_SMT_SOLVER_COMMAND);
+    let backend = SmtLibBackend::spawn(command, settings.random_seed)
+        .map_err(|e| SolverError::SolverProcess(format!("{}: {}", command, e)))?;
+    solve_backend_impl(problem, settings, &backend, cancel, stats)
+}
+
+/// Solve the problem using the given backend for the SMT formulation. The `random_seed`
+/// setting is not used here, since it is a parameter of the backend.
+pub fn solve_with_backend<B: Backend>(
+    problem: &Problem,
+    settings: &SolverSettings,
+    backend: &B,
+    cancel: Option<&AtomicBool>,
+) -> Result<Solution, SolverError> {
+    solve_backend_impl(
+        problem,
+        settings,
+        backend,
+        cancel,
+        &mut SolveStats::default(),
+    )
+}
+
+fn solve_backend_impl<B: Backend>(
+    problem: &Problem,
+    settings: &SolverSettings,
+    backend: &B,
+    cancel: Option<&AtomicBool>,
+    stats: &mut SolveStats,
+) -> Result<Solution, SolverError> {
+    let timeout = match settings.timeout {
+        Some(timeout) => std::time::Duration::from_secs_f64(timeout.max(0.0)),
+        None => return refinement_loop(problem, settings, backend, cancel, stats),
+    };
+
+    // Stop the solver when the timeout has passed or the caller's `cancel` flag is set.
+    let stop = AtomicBool::new(false);
+    let timed_out = AtomicBool::new(false);
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let (stop_ref, timed_out_ref) = (&stop, &timed_out);
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            let deadline = std::time::Instant::now() + timeout;
+            loop {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    timed_out_ref.store(true, Ordering::Relaxed);
+                    stop_ref.store(true, Ordering::Relaxed);
+                    break;
+                }
+                let wait = (deadline - now).min(std::time::Duration::from_millis(10));
+                match done_rx.recv_timeout(wait) {
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        if cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false) {
+                            stop_ref.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        });
+        let result = refinement_loop(problem, settings, backend, Some(&stop), stats);
+        drop(done_tx);
+        match result {
+            Err(SolverError::Cancelled) if timed_out.load(Ordering::Relaxed) => {
+                Err(SolverError::Timeout)
+            }
+            result => result,
+        }
+    })
+}
+
+fn refinement_loop<B: Backend>(
+    problem: &Problem,
+    settings: &SolverSettings,
+    backend: &B,
+    cancel: Option<&AtomicBool>,
+    stats: &mut SolveStats,
+) -> Result<Solution, SolverError> {
     let _p = hprof::enter("solve");
     let p1 = hprof::enter("prepare");
     debug!("Starting transition-and-pocl solver.");
-    let z3_config = z3::Config::new();
-    let ctx = z3::Context::new(&z3_config);
-    let solver = z3::Solver::new(&ctx);
+    if settings.strong_controllability {
+        if let Some(tt) = problem
+            .timelines
+            .iter()
+            .flat_map(|tl| tl.token_types.iter())
+            .find(|tt| tt.contingent && tt.duration_limits.1.is_none())
+        {
+            return Err(SolverError::UnboundedContingentDuration(tt.value.clone()));
+        }
+    }
 
-    let mut params = z3::Params::new(&ctx);
-    params.set_bool("auto_config", false);
-    params.set_u32("smt.arith.solver", 6);
-    debug!("Z3 params:\n{}", params.to_string());
-    solver.set_params(&params);
+    // Resource effects, numeric rates and numeric conditions are collected by timeline
+    // name while expanding tokens, so the targets must exist and track a level.
+    for tl in problem.timelines.iter() {
+        for tt in tl.token_types.iter() {
+            let level_refs = tt
+                .resource_effects
+                .iter()
+                .map(|e| &e.timeline_ref)
+                .chain(tt.numeric_rates.iter().map(|r| &r.timeline_ref))
+                .chain(tt.numeric_conditions.iter().map(|c| &c.timeline_ref));
+            for timeline_ref in level_refs {
+                let target = problem.timelines.iter().find(|t| &t.name == timeline_ref);
+                match target {
+                    None => {
+                        return Err(SolverError::InvalidProblem(format!(
+                            "token type \"{}\" on timeline \"{}\" refers to unknown timeline \"{}\"",
+                            tt.value, tl.name, timeline_ref
+                        )))
+                    }
+                    Some(target) if target.is_symbolic() => {
+                        return Err(SolverError::InvalidProblem(format!(
+                            "token type \"{}\" on timeline \"{}\" changes the level of symbolic timeline \"{}\"",
+                            tt.value, tl.name, timeline_ref
+                        )))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
 
-    let end_of_time = Real::fresh_const(&ctx, "endoftime");
+    let end_of_time = backend.fresh_real("endoftime");
 
     let mut timelines = problem
         .timelines
@@ -98,17 +482,20 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
     let mut states_queue = 0;
     let mut tokens = Vec::new();
     let mut tokens_queue = 0;
-    let mut conds: Vec<Condition> = Vec::new();
+    let mut conds: Vec<Condition<B>> = Vec::new();
     let mut conds_queue = 0;
+    let mut negative_conds: Vec<usize> = Vec::new();
 
-    let mut goal_lits: HashMap<(&str, isize), Bool> = HashMap::new();
+    let mut goal_lits: HashMap<(&str, isize), B::Bool> = HashMap::new();
 
     let mut expand_links_queue: Vec<(bool, usize)> = Vec::new();
 
-    let mut expand_links_lits: HashMap<Bool, usize> = HashMap::new();
-    let mut expand_goal_state_lits: HashMap<Bool, (usize, &str)> = HashMap::new();
+    let mut expand_links_lits: HashMap<B::Bool, usize> = HashMap::new();
+    let mut expand_goal_state_lits: HashMap<B::Bool, (usize, &str)> = HashMap::new();
 
-    let mut resource_constraints: HashMap<usize, ResourceConstraint> = Default::default(); // token to resourceconstraint
+    let mut resource_constraints: BTreeMap<usize, ResourceConstraint<B>> = Default::default(); // token to resourceconstraint
+    let mut level_constraints: BTreeMap<usize, LevelConstraint<B>> = Default::default(); // timeline to levelconstraint
+    let mut level_lits: HashMap<B::Bool, usize> = HashMap::new();
 
     let timelines_by_name = problem
         .timelines
@@ -144,23 +531,24 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                     state: state_idx,
                     fact: true,
                     conditions: Vec::new(),
+                    cost: Default::default(),
                 });
+                let start_time = start_time
+                    .map(|t| backend.real_const(t as i32, 1))
+                    .unwrap_or_else(|| backend.fresh_real(&format!("t_{}_s_", tl_spec.name)));
+                let end_time = end_time
+                    .map(|t| backend.real_const(t as i32, 1))
+                    .unwrap_or_else(|| backend.fresh_real(&format!("t_{}_e_", tl_spec.name)));
                 states.push(State {
                     state_seq,
                     tokens: vec![token_idx],
-                    start_time: start_time
-                        .map(|t| Real::from_real(&ctx, t as i32, 1))
-                        .unwrap_or_else(|| {
-                            Real::fresh_const(&ctx, &format!("t_{}_s_", tl_spec.name))
-                        }),
-                    end_time: end_time
-                        .map(|t| Real::from_real(&ctx, t as i32, 1))
-                        .unwrap_or_else(|| {
-                            Real::fresh_const(&ctx, &format!("t_{}_e_", tl_spec.name))
-                        }),
+                    start_late: start_time.clone(),
+                    end_late: end_time.clone(),
+                    start_time,
+                    end_time,
                     timeline: tl_idx,
-                    active: Bool::from_bool(&ctx, true),
-                    activate_next: Bool::fresh_const(&ctx, "nxstate"),
+                    active: backend.bool_const(true),
+                    activate_next: backend.fresh_bool("nxstate"),
                     can_expand: false,
                 });
                 tl.states.push(state_idx);
@@ -173,7 +561,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 for alternatives in static_token.conditions.iter() {
                     let mut conditions_clause = Vec::new();
                     if let Some(active) = tokens[token_idx].active.as_ref() {
-                        conditions_clause.push(Bool::not(&active));
+                        conditions_clause.push(backend.not(active));
                     }
 
                     assert!(alternatives.len() > 0);
@@ -181,7 +569,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                         let active = if alternatives.len() == 1 {
                             tokens[token_idx].active.clone()
                         } else {
-                            let active = Bool::fresh_const(&ctx, "condactive");
+                            let active = backend.fresh_bool("condactive");
                             conditions_clause.push(active.clone());
                             Some(active)
                         };
@@ -199,36 +587,32 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                     if conditions_clause.len() >= 2 {
                         let clause_refs = conditions_clause.iter().collect::<Vec<_>>();
-                        solver.assert(&Bool::or(&ctx, &clause_refs));
+                        backend.assert(&backend.or(&clause_refs));
                     }
                 }
 
                 // Minimum duration of state.
-                let prec = &Real::le(
-                    &Real::add(
-                        &ctx,
-                        &[
-                            &states[tokens[token_idx].state].start_time,
-                            &Real::from_real(&ctx, 1_i32, 1), // TODO configurable epsilon
-                        ],
-                    ),
+                let prec = &backend.le(
+                    &backend.add(&[
+                        &states[tokens[token_idx].state].start_time,
+                        &backend.real_const(1_i32, 1), // TODO configurable epsilon
+                    ]),
                     &states[tokens[token_idx].state].end_time,
                 );
-                solver.assert(prec);
+                backend.assert(prec);
             }
         }
     }
 
     // All empty timelines must now start in one of their initial states.
     for timeline in 0..timelines.len() {
-        if timelines[timeline].states.is_empty() {
+        if timelines[timeline].states.is_empty() && problem.timelines[timeline].is_symbolic() {
             assert!(timeline < problem.timelines.len());
 
             trace!("EXPANDING");
             let expanded = expand_until(
                 problem,
-                &ctx,
-                &solver,
+                backend,
                 timeline,
                 &mut timelines,
                 &mut states,
@@ -259,17 +643,25 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
     //     }
     // }
 
-    #[allow(unused)]
     let mut n_smt_calls = 0;
 
     let mut n_exclusions = 0;
     let mut n_pbs = 0;
+
+    // When optimizing, the best plan found so far and the bound that the next plan must satisfy.
+    let mut best_solution: Option<Solution> = None;
+    let mut objective_bound: Option<f32> = None;
+    let mut n_refinements_without_improvement = 0;
     trace!("TL names {:?}", timelines_by_name);
 
     drop(p1);
 
     // REFINEMENT LOOP
     '_refinement: loop {
+        if cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false) {
+            return best_solution.ok_or(SolverError::Cancelled);
+        }
+
         // EXPAND PROBLEM FORMULATION
 
         while states_queue < states.len()
@@ -295,12 +687,12 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 let state = &states[state_idx];
                 if !timelines[state.timeline].facts_only {
                     // If this is the last state, it has to last until the end of time.
-                    solver.assert(&Bool::implies(
-                        &state.activate_next.not(),
-                        &Real::ge(&state.end_time, &end_of_time),
+                    backend.assert(&backend.implies(
+                        &backend.not(&state.activate_next),
+                        &backend.ge(&state.end_time, &end_of_time),
                     ));
                 }
-                solver.assert(&Real::le(&state.end_time, &end_of_time));
+                backend.assert(&backend.le(&state.end_late, &end_of_time));
 
                 // There are no goals for facts only timelines.
                 if !facts_only {
@@ -335,9 +727,9 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                             states[state_idx].can_expand = can_expand;
 
-                            let goal_lit = Bool::fresh_const(&ctx, "goal");
+                            let goal_lit = backend.fresh_bool("goal");
                             if let Some(active) = tokens[token_idx].active.as_ref() {
-                                solver.assert(&Bool::implies(&goal_lit, active));
+                                backend.assert(&backend.implies(&goal_lit, active));
                             }
                             assert!(goal_lits
                                 .insert(
@@ -354,12 +746,12 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                                 .as_ref()
                             {
                                 assert!(expand_goal_state_lits.remove(prev_extension).is_some());
-                                clause.push(Bool::not(prev_extension));
+                                clause.push(backend.not(prev_extension));
                             }
                             clause.push(goal_lit);
 
                             if can_expand {
-                                let extension = Bool::fresh_const(&ctx, "addgoal");
+                                let extension = backend.fresh_bool("addgoal");
                                 clause.push(extension.clone());
                                 expand_goal_state_lits.insert(
                                     extension.clone(),
@@ -370,7 +762,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                             }
 
                             let clause_refs = clause.iter().collect::<Vec<_>>();
-                            solver.assert(&Bool::or(&ctx, &clause_refs));
+                            backend.assert(&backend.or(&clause_refs));
                         }
                     }
 
@@ -379,17 +771,19 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                         goal_lits.get(&(timeline_name, states[state_idx].state_seq as isize - 1))
                     {
                         // Disable each possible token, if the previous state was a goal state.
-                        solver.assert(&Bool::implies(
-                            goal_in_prev_state,
-                            &Bool::not(&states[state_idx].active),
-                        ));
+                        backend.assert(
+                            &backend.implies(
+                                goal_in_prev_state,
+                                &backend.not(&states[state_idx].active),
+                            ),
+                        );
                     }
 
                     // Did we imply that the next state has to be active (from the previous one)
                     if states[state_idx].state_seq > 0 {
                         let prev_state_idx = timelines[states[state_idx].timeline].states
                             [states[state_idx].state_seq - 1];
-                        solver.assert(&Bool::implies(
+                        backend.assert(&backend.implies(
                             &states[prev_state_idx].activate_next,
                             &states[state_idx].active,
                         ));
@@ -413,7 +807,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                                     let mut clause = Vec::new();
                                     if let Some(active) = conds[cond_idx].active.as_ref() {
-                                        clause.push(Bool::not(active));
+                                        clause.push(backend.not(active));
                                     }
 
                                     // ... then the current state must have the given value.
@@ -437,7 +831,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                                         clause.push(active.clone());
 
                                         let clause_refs = clause.iter().collect::<Vec<_>>();
-                                        solver.assert(&Bool::or(&ctx, &clause_refs));
+                                        backend.assert(&backend.or(&clause_refs));
                                     }
                                 }
                             }
@@ -471,6 +865,52 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 resource_constraints.entry(token_idx).or_default().capacity =
                     Some(token_type.capacity);
 
+                // Effects, rates and conditions on reservoir and numeric timelines.
+                let state = &states[tokens[token_idx].state];
+                let active = &tokens[token_idx].active;
+                let time_point = |point: TokenPoint| {
+                    let time = match point {
+                        TokenPoint::Start => state.start(),
+                        TokenPoint::End => state.end(),
+                    };
+                    if settings.strong_controllability {
+                        (time.early.clone(), time.late.clone())
+                    } else {
+                        (time.early.clone(), time.early.clone())
+                    }
+                };
+                for effect in token_type.resource_effects.iter() {
+                    level_constraints
+                        .entry(timelines_by_name[effect.timeline_ref.as_str()])
+                        .or_default()
+                        .effects
+                        .push((active.clone(), time_point(effect.time), effect.amount));
+                }
+                for rate in token_type.numeric_rates.iter() {
+                    level_constraints
+                        .entry(timelines_by_name[rate.timeline_ref.as_str()])
+                        .or_default()
+                        .rates
+                        .push((
+                            active.clone(),
+                            time_point(TokenPoint::Start),
+                            time_point(TokenPoint::End),
+                            rate.rate,
+                        ));
+                }
+                for cond in token_type.numeric_conditions.iter() {
+                    level_constraints
+                        .entry(timelines_by_name[cond.timeline_ref.as_str()])
+                        .or_default()
+                        .conditions
+                        .push((
+                            active.clone(),
+                            time_point(cond.time),
+                            cond.relation,
+                            cond.value,
+                        ));
+                }
+
                 // If there are old links pointing to this value, we need to update them.
                 for (cond_idx, cond) in conds.iter().enumerate() {
                     if cond.cond_spec.value == tokens[token_idx].value {
@@ -479,46 +919,82 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 }
 
                 // Minimum duration of state.
-                let prec = &Real::le(
-                    &Real::add(
-                        &ctx,
-                        &[
-                            &states[tokens[token_idx].state].start_time,
-                            &Real::from_real(&ctx, token_type.duration_limits.0 as i32, 1),
-                        ],
-                    ),
+                let prec = &backend.le(
+                    &backend.add(&[
+                        &states[tokens[token_idx].state].start_time,
+                        &backend.real_const(token_type.duration_limits.0 as i32, 1),
+                    ]),
                     &states[tokens[token_idx].state].end_time,
                 );
                 if let Some(cond) = tokens[token_idx].active.as_ref() {
-                    solver.assert(&Bool::implies(cond, prec))
+                    backend.assert(&backend.implies(cond, prec))
                 } else {
-                    solver.assert(prec);
+                    backend.assert(prec);
                 }
 
                 // Maximum duration
                 if let Some(max_dur) = token_type.duration_limits.1 {
-                    let prec = &Real::ge(
-                        &Real::add(
-                            &ctx,
-                            &[
-                                &states[tokens[token_idx].state].start_time,
-                                &Real::from_real(&ctx, max_dur as i32, 1),
-                            ],
-                        ),
+                    let prec = &backend.ge(
+                        &backend.add(&[
+                            &states[tokens[token_idx].state].start_time,
+                            &backend.real_const(max_dur as i32, 1),
+                        ]),
                         &states[tokens[token_idx].state].end_time,
                     );
 
                     if let Some(cond) = tokens[token_idx].active.as_ref() {
-                        solver.assert(&Bool::implies(cond, prec))
+                        backend.assert(&backend.implies(cond, prec))
+                    } else {
+                        backend.assert(prec);
+                    }
+                }
+
+                // Latest end time of the state, for timelines with contingent token types.
+                let state = &states[tokens[token_idx].state];
+                if !state.end().is_fixed() {
+                    let min_dur = backend.real_const(token_type.duration_limits.0 as i32, 1);
+                    let late_end = if settings.strong_controllability && token_type.contingent {
+                        // The token ends at some time between the earliest start plus the
+                        // minimum duration and the latest start plus the maximum duration.
+                        let max_dur =
+                            backend.real_const(token_type.duration_limits.1.unwrap() as i32, 1);
+                        vec![
+                            backend.eq(
+                                &state.end_time,
+                                &backend.add(&[&state.start_time, &min_dur]),
+                            ),
+                            backend.eq(
+                                &state.end_late,
+                                &backend.add(&[&state.start_late, &max_dur]),
+                            ),
+                        ]
+                    } else if settings.strong_controllability {
+                        // The end of a controllable token is fixed, and it must satisfy
+                        // the duration limits for any start time.
+                        vec![
+                            backend.eq(&state.end_late, &state.end_time),
+                            backend.le(
+                                &backend.add(&[&state.start_late, &min_dur]),
+                                &state.end_time,
+                            ),
+                        ]
                     } else {
-                        solver.assert(prec);
+                        vec![backend.eq(&state.end_late, &state.end_time)]
+                    };
+
+                    for c in late_end {
+                        if let Some(cond) = tokens[token_idx].active.as_ref() {
+                            backend.assert(&backend.implies(cond, &c))
+                        } else {
+                            backend.assert(&c);
+                        }
                     }
                 }
 
                 for alternatives in token_type.conditions.iter() {
                     let mut conditions_clause = Vec::new();
                     if let Some(active) = tokens[token_idx].active.as_ref() {
-                        conditions_clause.push(Bool::not(&active));
+                        conditions_clause.push(backend.not(active));
                     }
 
                     assert!(alternatives.len() > 0);
@@ -526,7 +1002,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                         let active = if alternatives.len() == 1 {
                             tokens[token_idx].active.clone()
                         } else {
-                            let active = Bool::fresh_const(&ctx, "condactive");
+                            let active = backend.fresh_bool("condactive");
                             conditions_clause.push(active.clone());
                             Some(active)
                         };
@@ -544,7 +1020,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                     if conditions_clause.len() >= 2 {
                         let clause_refs = conditions_clause.iter().collect::<Vec<_>>();
-                        solver.assert(&Bool::or(&ctx, &clause_refs));
+                        backend.assert(&backend.or(&clause_refs));
                     }
                 }
             }
@@ -582,7 +1058,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                         let mut clause = vec![];
                         if let Some(l) = conds[cond_idx].active.as_ref() {
-                            clause.push(Bool::not(l));
+                            clause.push(backend.not(l));
                         }
 
                         let mut any_const = false;
@@ -600,7 +1076,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                         if !any_const {
                             let clause_refs = clause.iter().collect::<Vec<_>>();
-                            solver.assert(&Bool::or(&ctx, &clause_refs));
+                            backend.assert(&backend.or(&clause_refs));
                         }
                     } else {
                         debug!(
@@ -633,6 +1109,15 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 //     }
                 // };
 
+                if cond_spec.is_negative() {
+                    // Negative conditions don't have causal links. They are refined
+                    // with the new target tokens after the expansion below.
+                    if need_new_token {
+                        negative_conds.push(cond_idx);
+                    }
+                    continue;
+                }
+
                 let target_tl = cond_spec.timeline_ref.as_str();
                 let target_timeline_idx = timelines_by_name[target_tl];
 
@@ -666,8 +1151,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                     let prev_tokens_len = tokens.len();
                     if expand_until(
                         problem,
-                        &ctx,
-                        &solver,
+                        backend,
                         target_timeline_idx,
                         &mut timelines,
                         &mut states,
@@ -714,7 +1198,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                         );
 
                         if let Some(active) = conds[cond_idx].active.as_ref() {
-                            solver.assert(&active.not());
+                            backend.assert(&backend.not(active));
                         } else {
                             warn!("Unsatisfiable condition {:?}!", cond_spec);
                             return Err(SolverError::NoSolution);
@@ -723,7 +1207,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 } else {
                     let mut alternatives = Vec::new();
 
-                    let old_expansion_lit: Option<Bool> =
+                    let old_expansion_lit: Option<B::Bool> =
                         conds[cond_idx].alternatives_extension.take();
 
                     if let Some(b) = old_expansion_lit.as_ref() {
@@ -753,7 +1237,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                     );
 
                     if can_expand {
-                        let expand_lit = Bool::fresh_const(&ctx, "exp");
+                        let expand_lit = backend.fresh_bool("exp");
                         expand_links_lits.insert(expand_lit.clone(), cond_idx);
                         conds[cond_idx].alternatives_extension = Some(expand_lit.clone());
                         trace!("added expand lit");
@@ -766,70 +1250,59 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                     if let Some(cond) = need_alternatives {
                         trace!("added need alternatives {:?}", old_expansion_lit);
-                        alternatives.push(Bool::not(&cond));
+                        alternatives.push(backend.not(&cond));
                     }
 
                     let const_link = alternatives.len() + new_target_tokens.len() == 1;
                     for token_idx in new_target_tokens.iter().copied() {
                         // Represents the usage of the causal link.
-                        let choose_link = (!const_link).then(|| Bool::fresh_const(&ctx, "cl"));
+                        let choose_link = (!const_link).then(|| backend.fresh_bool("cl"));
 
                         let this_state = &states[tokens[conds[cond_idx].token_idx].state];
                         let target_state = &states[tokens[token_idx].state];
 
+                        let (this, target) = (this_state, target_state);
                         let temporal_rel = match conds[cond_idx].cond_spec.temporal_relationship {
                             TemporalRelationship::MetByTransitionFrom => {
                                 // // The target token should have a next value to transition to.
                                 vec![
                                     target_state.activate_next.clone(),
-                                    Real::_eq(&target_state.end_time, &this_state.start_time),
+                                    time_eq(backend, target.end(), this.start()),
                                 ]
                             }
                             TemporalRelationship::MetBy => {
-                                vec![Real::_eq(&target_state.end_time, &this_state.start_time)]
+                                vec![time_eq(backend, target.end(), this.start())]
                             }
                             TemporalRelationship::Starts => {
-                                vec![Real::_eq(&target_state.start_time, &this_state.start_time)]
+                                vec![time_eq(backend, target.start(), this.start())]
                             }
                             TemporalRelationship::StartsAfter => {
-                                vec![Real::le(&target_state.start_time, &this_state.start_time)]
+                                vec![time_le(backend, target.start(), 0, this.start())]
                             }
                             TemporalRelationship::Cover => vec![
-                                Real::le(&target_state.start_time, &this_state.start_time),
-                                Real::le(&this_state.end_time, &target_state.end_time),
+                                time_le(backend, target.start(), 0, this.start()),
+                                time_le(backend, this.end(), 0, target.end()),
                             ],
                             TemporalRelationship::StartPrecond => vec![
-                                Real::le(
-                                    &Real::add(
-                                        &ctx,
-                                        &[
-                                            &target_state.start_time,
-                                            &Real::from_real(&ctx, 1_i32, 1), // TODO configurable epsilon
-                                        ],
-                                    ),
-                                    &this_state.start_time,
-                                ),
-                                Real::le(&this_state.start_time, &target_state.end_time),
+                                // TODO configurable epsilon
+                                time_le(backend, target.start(), 1, this.start()),
+                                time_le(backend, this.start(), 0, target.end()),
                             ],
                             TemporalRelationship::StartEffect => vec![
-                                Real::le(&target_state.start_time, &this_state.start_time),
-                                Real::le(
-                                    &Real::add(
-                                        &ctx,
-                                        &[
-                                            &this_state.start_time,
-                                            &Real::from_real(&ctx, 1_i32, 1), // TODO configurable epsilon
-                                        ],
-                                    ),
-                                    &target_state.end_time,
-                                ),
+                                time_le(backend, target.start(), 0, this.start()),
+                                // TODO configurable epsilon
+                                time_le(backend, this.start(), 1, target.end()),
                             ],
                             TemporalRelationship::Equal => vec![
-                                Real::_eq(&this_state.start_time, &target_state.start_time),
-                                Real::_eq(&this_state.end_time, &target_state.end_time),
+                                time_eq(backend, this.start(), target.start()),
+                                time_eq(backend, this.end(), target.end()),
                             ],
                             TemporalRelationship::Meets => {
-                                vec![Real::_eq(&target_state.start_time, &this_state.end_time)]
+                                vec![time_eq(backend, target.start(), this.end())]
+                            }
+                            TemporalRelationship::NotOverlapping
+                            | TemporalRelationship::NotDuring => {
+                                unreachable!("negative conditions have no causal links")
                             }
                         };
 
@@ -859,10 +1332,10 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                         for cond in clause {
                             if let Some(choose_link) = choose_link.as_ref() {
-                                solver.assert(&Bool::implies(choose_link, &cond));
+                                backend.assert(&backend.implies(choose_link, &cond));
                                 // alternatives.push(choose_link.clone());
                             } else {
-                                solver.assert(&cond);
+                                backend.assert(&cond);
                             }
                         }
 
@@ -886,7 +1359,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                     if !alternatives.is_empty() {
                         let alternatives_refs = alternatives.iter().collect::<Vec<_>>();
-                        solver.assert(&Bool::or(&ctx, &alternatives_refs));
+                        backend.assert(&backend.or(&alternatives_refs));
                     }
                 }
                 conds[cond_idx].token_queue = tokens.len();
@@ -905,8 +1378,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                         if !has_goal {
                             let expanded = expand_until(
                                 problem,
-                                &ctx,
-                                &solver,
+                                backend,
                                 tl_idx,
                                 &mut timelines,
                                 &mut states,
@@ -927,6 +1399,51 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
             }
         }
 
+        let p = hprof::enter("expand_negative_conds");
+        for cond_idx in negative_conds.iter().copied() {
+            let cond = &conds[cond_idx];
+            let target_timeline_idx = timelines_by_name[cond.cond_spec.timeline_ref.as_str()];
+            let this_state = &states[tokens[cond.token_idx].state];
+
+            // Separate the token from every potential target token that has been
+            // added since the last refinement.
+            for (target_idx, target) in tokens.iter().enumerate().skip(cond.token_queue) {
+                if target_idx == cond.token_idx
+                    || states[target.state].timeline != target_timeline_idx
+                    || target.value != cond.cond_spec.value
+                {
+                    continue;
+                }
+
+                let target_state = &states[target.state];
+                let separation = match cond.cond_spec.temporal_relationship {
+                    TemporalRelationship::NotOverlapping => backend.or(&[
+                        &time_le(backend, this_state.end(), 0, target_state.start()),
+                        &time_le(backend, target_state.end(), 0, this_state.start()),
+                    ]),
+                    TemporalRelationship::NotDuring => backend.or(&[
+                        &time_lt(backend, this_state.start(), target_state.start()),
+                        &time_lt(backend, target_state.end(), this_state.end()),
+                    ]),
+                    _ => unreachable!(),
+                };
+
+                let active = cond
+                    .active
+                    .iter()
+                    .chain(target.active.iter())
+                    .collect::<Vec<_>>();
+                if active.is_empty() {
+                    backend.assert(&separation);
+                } else {
+                    backend.assert(&backend.implies(&backend.and(&active), &separation));
+                }
+            }
+
+            conds[cond_idx].token_queue = tokens.len();
+        }
+        drop(p);
+
         let p = hprof::enter("expand_resources");
         for (_token_idx, rc) in resource_constraints.iter_mut() {
             if rc.users.len() > rc.integrated {
@@ -962,9 +1479,9 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 //     }
                 // }
 
-                const USE_PAIRWISE_RESOURCE_CONSTRAINT: bool = true;
-
-                if USE_PAIRWISE_RESOURCE_CONSTRAINT && rc.capacity.unwrap() == 1 {
+                if settings.resource_encoding == ResourceEncoding::Pairwise
+                    && rc.capacity.unwrap() == 1
+                {
                     // Special-case parwise exclusion, which is probably faster than
                     // the long pseudo-boolean constraint needed for capacity >=2
 
@@ -977,26 +1494,24 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                             assert!(*amount1 == 1);
                             assert!(*amount2 == 1);
 
+                            let (state1, state2) = (
+                                &states[tokens[*token1].state],
+                                &states[tokens[*token2].state],
+                            );
                             let mut alts = vec![
-                                Real::le(
-                                    &states[tokens[*token1].state].end_time,
-                                    &states[tokens[*token2].state].start_time,
-                                ),
-                                Real::le(
-                                    &states[tokens[*token2].state].end_time,
-                                    &states[tokens[*token1].state].start_time,
-                                ),
+                                time_le(backend, state1.end(), 0, state2.start()),
+                                time_le(backend, state2.end(), 0, state1.start()),
                             ];
 
                             if let Some(link1) = link1 {
-                                alts.push(link1.not());
+                                alts.push(backend.not(link1));
                             }
                             if let Some(link2) = link2 {
-                                alts.push(link2.not());
+                                alts.push(backend.not(link2));
                             }
 
                             let alts_refs = alts.iter().collect::<Vec<_>>();
-                            solver.assert(&Bool::or(&ctx, &alts_refs));
+                            backend.assert(&backend.or(&alts_refs));
                             n_exclusions += 1;
                         }
                     }
@@ -1006,23 +1521,20 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                             .users
                             .iter()
                             .map(|(link2, token2, amount2)| {
-                                let overlap = Bool::and(
-                                    &ctx,
-                                    &[
-                                        // &link1.clone().unwrap_or_else(|| Bool::from_bool(&ctx, true)),
-                                        &link2
-                                            .clone()
-                                            .unwrap_or_else(|| Bool::from_bool(&ctx, true)),
-                                        &Real::lt(
-                                            &states[tokens[*token1].state].start_time,
-                                            &states[tokens[*token2].state].end_time,
-                                        ),
-                                        &Real::lt(
-                                            &states[tokens[*token2].state].start_time,
-                                            &states[tokens[*token1].state].end_time,
-                                        ),
-                                    ],
-                                );
+                                let overlap = backend.and(&[
+                                    // &link1.clone().unwrap_or_else(|| backend.bool_const(true)),
+                                    &link2.clone().unwrap_or_else(|| backend.bool_const(true)),
+                                    &time_may_be_lt(
+                                        backend,
+                                        states[tokens[*token1].state].start(),
+                                        states[tokens[*token2].state].end(),
+                                    ),
+                                    &time_may_be_lt(
+                                        backend,
+                                        states[tokens[*token2].state].start(),
+                                        states[tokens[*token1].state].end(),
+                                    ),
+                                ]);
 
                                 (overlap, *amount2)
                             })
@@ -1041,11 +1553,11 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                             rc.capacity.unwrap()
                         );
 
-                        let pb = Bool::pb_le(&ctx, &overlaps_refs, rc.capacity.unwrap() as i32);
+                        let pb = backend.pb_le(&overlaps_refs, rc.capacity.unwrap() as i32);
                         if let Some(link1) = link1 {
-                            solver.assert(&Bool::implies(link1, &pb));
+                            backend.assert(&backend.implies(link1, &pb));
                         } else {
-                            solver.assert(&pb);
+                            backend.assert(&pb);
                         }
                         n_pbs += 1;
                     }
@@ -1055,6 +1567,51 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
             }
         }
 
+        for (tl_idx, lc) in level_constraints.iter_mut() {
+            if lc.has_new_users() {
+                let (initial, min, max) = match &problem.timelines[*tl_idx].kind {
+                    TimelineKind::Reservoir(r) => (
+                        backend.real_const(r.initial, 1),
+                        Some(backend.real_const(r.min, 1)),
+                        Some(backend.real_const(r.max, 1)),
+                    ),
+                    TimelineKind::Numeric(n) => (
+                        backend.real_from_f32(n.initial),
+                        n.min.map(|x| backend.real_from_f32(x)),
+                        n.max.map(|x| backend.real_from_f32(x)),
+                    ),
+                    TimelineKind::Symbolic => {
+                        unreachable!(
+                            "level users on symbolic timelines are rejected before solving"
+                        )
+                    }
+                };
+
+                debug!(
+                    "Adding level constraint for {} with {} effects {} rates {} conditions",
+                    timeline_names[*tl_idx],
+                    lc.effects.len(),
+                    lc.rates.len(),
+                    lc.conditions.len(),
+                );
+
+                // The levels are extended with the new users, and the statement that there
+                // are no more users is moved to a new guard literal, so the old guard literal
+                // is no longer assumed.
+                if let Some(old_guard) = lc.guard.take() {
+                    assert!(level_lits.remove(&old_guard).is_some());
+                }
+                let guard = backend.fresh_bool("level");
+
+                for constraint in lc.encode(backend, &initial, min.as_ref(), max.as_ref()) {
+                    backend.assert(&backend.implies(&guard, &constraint));
+                }
+
+                level_lits.insert(guard.clone(), *tl_idx);
+                lc.guard = Some(guard);
+            }
+        }
+
         // Now we have refined the problem enough for a potential solution to come from solving the SMT.
         // Will call the SMT solver with a list of assumptions that negate all the extension literals.
         // Extensions are:
@@ -1065,9 +1622,9 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
         drop(p);
         let p = hprof::enter("solve_smt");
 
-        let expand_state_seq_lits: HashMap<Bool, usize> = timelines
+        let expand_state_seq_lits: HashMap<B::Bool, usize> = timelines
             .iter()
-            .map(|tl| *tl.states.last().unwrap())
+            .filter_map(|tl| tl.states.last().copied())
             .filter(|s_idx| states[*s_idx].can_expand)
             .map(|s_idx| (states[s_idx].activate_next.clone(), s_idx))
             .collect();
@@ -1076,7 +1633,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
             .keys()
             .chain(expand_goal_state_lits.keys())
             .chain(expand_state_seq_lits.keys())
-            .map(|l| (Bool::not(l), l.clone()))
+            .map(|l| (backend.not(l), l.clone()))
             .collect::<HashMap<_, _>>();
 
         for (i, timeline) in timelines.iter().enumerate() {
@@ -1099,35 +1656,131 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
             n_pbs,
         );
 
-        trace!("{}", solver.to_string());
+        // The assumptions are ordered by the conditions, timelines and states they
+        // belong to, so that the solver sees the same problem on every run.
+        let mut link_lits = expand_links_lits.iter().collect::<Vec<_>>();
+        link_lits.sort_by_key(|(_, cond_idx)| **cond_idx);
+        let mut goal_state_lits = expand_goal_state_lits.iter().collect::<Vec<_>>();
+        goal_state_lits.sort_by_key(|(_, (tl_idx, _))| *tl_idx);
+        let mut state_seq_lits = expand_state_seq_lits.iter().collect::<Vec<_>>();
+        state_seq_lits.sort_by_key(|(_, state_idx)| **state_idx);
+        let mut level_guards = level_lits.iter().collect::<Vec<_>>();
+        level_guards.sort_by_key(|(_, tl_idx)| **tl_idx);
+
+        let mut assumptions = link_lits
+            .into_iter()
+            .map(|(l, _)| l)
+            .chain(goal_state_lits.into_iter().map(|(l, _)| l))
+            .chain(state_seq_lits.into_iter().map(|(l, _)| l))
+            .map(|l| backend.not(l))
+            .chain(level_guards.into_iter().map(|(l, _)| l.clone()))
+            .collect::<Vec<_>>();
+
+        // The objective bound is re-created for every call because the set of tokens
+        // that contribute to the objective grows as the problem is expanded.
+        let bound_lit = objective_bound.map(|bound| {
+            let lit = backend.fresh_bool("objbound");
+            let objective = objective_expr(
+                backend,
+                settings.optimize.as_ref().unwrap(),
+                &tokens,
+                &states,
+                &end_of_time,
+            );
+            backend.assert(
+                &backend.implies(&lit, &backend.le(&objective, &backend.real_from_f32(bound))),
+            );
+            assumptions.push(lit.clone());
+            lit
+        });
+
+        trace!("{}", backend.to_smtlib());
         // panic!();
 
         n_smt_calls += 1;
-        debug!(
-            "ASSUMPTIONS {:?}",
-            neg_expansions.keys().cloned().collect::<Vec<_>>()
-        );
-        let result = solver.check_assumptions(&neg_expansions.keys().cloned().collect::<Vec<_>>());
+        stats.smt_calls = n_smt_calls;
+        stats.states = states.len();
+        stats.tokens = tokens.len();
+        stats.conditions = conds.len();
+        debug!("ASSUMPTIONS {:?}", assumptions);
+        if let Some(dir) = settings.smt2_dump_dir.as_ref() {
+            if let Err(e) = write_smt2(dir, n_smt_calls, backend, &assumptions) {
+                warn!("Could not write SMT-LIB file to {}: {}", dir.display(), e);
+            }
+        }
+        let result = backend.check(&assumptions, cancel);
         drop(p);
 
         match result {
-            z3::SatResult::Unsat => {
+            SatResult::Unsat => {
                 let _p = hprof::enter("unsat_core");
-                let mut core = solver.get_unsat_core();
-                if core.is_empty() {
-                    return Err(SolverError::NoSolution);
-                }
-
+                let mut core = backend.unsat_core();
                 if settings.minimize_cores {
                     let use_trim_core = true;
                     let use_minimize_core = true;
                     debug!("Minmizing core...");
+                    let mut minimized = Ok(());
                     if use_trim_core {
-                        crate::cores::trim_core(&mut core, &solver, |_| {});
+                        minimized = crate::cores::trim_core(&mut core, backend, cancel, |_| {});
+                    }
+
+                    if use_minimize_core && minimized.is_ok() {
+                        minimized = crate::cores::minimize_core(&mut core, backend, cancel, |_| {});
+                    }
+
+                    if let Err(e) = minimized {
+                        return best_solution.ok_or(e);
+                    }
+                }
+                if let Some(e) = backend.error() {
+                    return best_solution.ok_or(SolverError::SolverProcess(e));
+                }
+
+                if let Some(bound_lit) = bound_lit.as_ref() {
+                    core.retain(|c| c != bound_lit);
+                }
+
+                let levels_in_core = core
+                    .iter()
+                    .filter_map(|c| level_lits.get(c).copied())
+                    .collect::<Vec<_>>();
+                core.retain(|c| !level_lits.contains_key(c));
+
+                if !levels_in_core.is_empty() {
+                    // The reservoir or numeric levels are in conflict, so make room for
+                    // more tokens that change the levels. The rest of the core may only
+                    // be asking for goal expansions that cannot fix the levels.
+                    debug!("Expand users of levels {:?}", levels_in_core);
+                    let expanded = expand_level_users(
+                        problem,
+                        backend,
+                        &levels_in_core,
+                        &mut timelines,
+                        &mut states,
+                        &mut tokens,
+                    );
+                    if expanded && core.is_empty() {
+                        continue;
+                    }
+                }
+
+                if core.is_empty() {
+                    if let Some(solution) = best_solution {
+                        // No plan can improve on the objective bound, so the best plan is optimal.
+                        info!("Optimal plan found after {} solver calls", n_smt_calls);
+                        return Ok(solution);
                     }
+                    return Err(SolverError::NoSolution);
+                }
 
-                    if use_minimize_core {
-                        crate::cores::minimize_core(&mut core, &solver, |_| {});
+                if best_solution.is_some() {
+                    n_refinements_without_improvement += 1;
+                    if n_refinements_without_improvement > settings.optimization_patience {
+                        info!(
+                            "No improved plan found in {} refinements, returning the best plan",
+                            settings.optimization_patience
+                        );
+                        return Ok(best_solution.take().unwrap());
                     }
                 }
 
@@ -1156,7 +1809,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 for c in core {
                     if let Some(nc) = neg_expansions.get(&c) {
                         if let Some((timeline, goalvalue)) = expand_goal_state_lits.get(nc) {
-                            if coresize <= 5 || expandstate_only {
+                            if coresize <= settings.expansion_core_size || expandstate_only {
                                 debug!("Expand goals in timleine {}", timeline_names[*timeline]);
                                 debug!(
                                     "  -expand goal value {} for {}",
@@ -1165,8 +1818,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                                 let expanded = expand_until(
                                     problem,
-                                    &ctx,
-                                    &solver,
+                                    backend,
                                     *timeline,
                                     &mut timelines,
                                     &mut states,
@@ -1203,7 +1855,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                                 .map(|t| tokens[*t].value)
                                 .collect::<Vec<_>>();
 
-                            if coresize <= 5 || expandstateseq_only {
+                            if coresize <= settings.expansion_core_size || expandstateseq_only {
                                 if timelines[states[state_idx].timeline].facts_only {
                                     debug!(
                                         "Cannot expand facts-only timleine  {} state{} values{:?}",
@@ -1217,8 +1869,7 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
 
                                     expand_n(
                                         problem,
-                                        &ctx,
-                                        &solver,
+                                        backend,
                                         states[state_idx].timeline,
                                         &mut timelines,
                                         &mut states,
@@ -1236,112 +1887,251 @@ pub fn solve(problem: &Problem, settings: &SolverSettings) -> Result<Solution, S
                 }
             }
 
-            z3::SatResult::Sat => {
+            SatResult::Sat => {
                 let _p = hprof::enter("extract_solution");
                 info!("SAT after {} solver calls", n_smt_calls);
-                let model = solver.get_model().unwrap();
-                trace!("{}", model.to_string());
-
-                let mut timelines: Vec<SolutionTimeline> = problem
-                    .timelines
-                    .iter()
-                    .map(|t| SolutionTimeline {
-                        name: t.name.clone(),
-                        tokens: Vec::new(),
-                    })
-                    .collect::<Vec<_>>();
+                let solution = extract_solution(
+                    problem,
+                    backend,
+                    &timeline_names,
+                    &states,
+                    &tokens,
+                    &conds,
+                    &end_of_time,
+                );
+                if let Some(e) = backend.error() {
+                    return best_solution.ok_or(SolverError::SolverProcess(e));
+                }
+                stats.plans_found += 1;
 
-                for v in tokens.iter() {
-                    let state = &states[v.state];
-                    let tl_idx = state.timeline;
+                if let Some(objective) = settings.optimize.as_ref() {
+                    let value = objective.value(&solution);
+                    info!(
+                        "Found plan with objective value {} ({:?}), searching for a better plan",
+                        value, objective
+                    );
+                    objective_bound = Some(value - settings.min_improvement);
+                    best_solution = Some(solution);
+                    n_refinements_without_improvement = 0;
+                } else {
+                    return Ok(solution);
+                }
+            }
 
-                    let active = v
-                        .active
-                        .as_ref()
-                        .map(|a| model.eval(a, true).unwrap().as_bool().unwrap())
-                        .unwrap_or(true);
+            SatResult::Unknown => {
+                return best_solution.ok_or_else(|| crate::cores::undecided(backend, cancel));
+            }
+        }
+    }
+}
 
-                    if !active {
-                        trace!("token {} ({:?}) not active", v.value, v.active);
-                        continue;
-                    }
+fn extract_solution<B: Backend>(
+    problem: &Problem,
+    backend: &B,
+    timeline_names: &[&str],
+    states: &[State<B>],
+    tokens: &[Token<B>],
+    conds: &[Condition<B>],
+    end_of_time: &B::Real,
+) -> Solution {
+    let mut timelines: Vec<SolutionTimeline> = problem
+        .timelines
+        .iter()
+        .map(|t| SolutionTimeline {
+            name: t.name.clone(),
+            tokens: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut token_seqs = vec![Vec::new(); timelines.len()];
 
-                    let start_time =
-                        z3real_value(&model.eval(&states[v.state].start_time, true).unwrap());
-                    let end_time =
-                        z3real_value(&model.eval(&states[v.state].end_time, true).unwrap());
+    for v in tokens.iter() {
+        let state = &states[v.state];
+        let tl_idx = state.timeline;
 
-                    trace!("value {:?}", v.value);
+        let active = v
+            .active
+            .as_ref()
+            .map(|a| backend.bool_value(a))
+            .unwrap_or(true);
 
-                    let mut solution_conditions = Vec::new();
-                    for cond in v.conditions.iter() {
-                        let cond = &conds[*cond];
-                        let cond_active = cond
-                            .active
-                            .as_ref()
-                            .map(|a| model.eval(a, true).unwrap().as_bool().unwrap())
-                            .unwrap_or(true);
+        if !active {
+            trace!("token {} ({:?}) not active", v.value, v.active);
+            continue;
+        }
 
-                        if !cond_active {
-                            continue;
-                        }
+        let start_time = backend.real_value(&states[v.state].start_time);
+        let end_time = backend.real_value(&states[v.state].end_time);
 
-                        for (target_token_idx, value) in cond.target_chosen.iter() {
-                            let active = value
-                                .as_ref()
-                                .map(|a| model.eval(a, true).unwrap().as_bool().unwrap())
-                                .unwrap_or(true);
+        trace!("value {:?}", v.value);
 
-                            if !active {
-                                continue;
-                            }
+        let mut solution_conditions = Vec::new();
+        for cond in v.conditions.iter() {
+            let cond = &conds[*cond];
+            let cond_active = cond
+                .active
+                .as_ref()
+                .map(|a| backend.bool_value(a))
+                .unwrap_or(true);
 
-                            let state = &states[tokens[*target_token_idx].state];
-                            let other_timeline = &timeline_names[state.timeline];
-                            let other_token_seq = state.state_seq;
+            if !cond_active {
+                continue;
+            }
 
-                            solution_conditions.push(SolutionCondition {
-                                timeline: other_timeline.to_string(),
-                                token_idx: other_token_seq,
-                            });
-                        }
-                    }
+            for (target_token_idx, value) in cond.target_chosen.iter() {
+                let active = value
+                    .as_ref()
+                    .map(|a| backend.bool_value(a))
+                    .unwrap_or(true);
 
-                    timelines[tl_idx].tokens.push(SolutionToken {
-                        value: v.value.to_string(),
-                        start_time,
-                        end_time,
-                        conditions: solution_conditions,
-                    })
+                if !active {
+                    continue;
                 }
 
-                for tl in timelines.iter_mut() {
-                    tl.tokens
-                        .sort_by_key(|t| ordered_float::OrderedFloat(t.start_time));
-                }
+                let state = &states[tokens[*target_token_idx].state];
+                let other_timeline = &timeline_names[state.timeline];
+                let other_token_seq = state.state_seq;
 
-                return Ok(Solution {
-                    timelines,
-                    end_of_time: z3real_value(&model.eval(&end_of_time, true).unwrap()),
+                solution_conditions.push(SolutionCondition {
+                    timeline: other_timeline.to_string(),
+                    token_idx: other_token_seq,
+                    temporal_relationship: Some(cond.cond_spec.temporal_relationship),
+                    amount: cond.cond_spec.amount,
                 });
             }
+        }
 
-            z3::SatResult::Unknown => {
-                panic!("Z3 is undecided.")
-            }
+        timelines[tl_idx].tokens.push(SolutionToken {
+            value: v.value.to_string(),
+            start_time,
+            end_time,
+            conditions: solution_conditions,
+            cost: v.cost.of_duration(end_time - start_time),
+            fact: v.fact,
+        });
+        token_seqs[tl_idx].push(state.state_seq);
+    }
+
+    // The conditions refer to their targets by the sequence number of the target's
+    // state, which is not the target's position once the tokens are sorted by time,
+    // for example for facts on a timeline without token types.
+    let mut positions = Vec::new();
+    for (tl, seqs) in timelines.iter_mut().zip(token_seqs) {
+        let mut tl_tokens = std::mem::take(&mut tl.tokens)
+            .into_iter()
+            .zip(seqs)
+            .collect::<Vec<_>>();
+        tl_tokens.sort_by_key(|(t, _)| ordered_float::OrderedFloat(t.start_time));
+        positions.push(
+            tl_tokens
+                .iter()
+                .enumerate()
+                .map(|(position, (_, seq))| (*seq, position))
+                .collect::<HashMap<_, _>>(),
+        );
+        tl.tokens = tl_tokens.into_iter().map(|(t, _)| t).collect();
+    }
+    for tl in timelines.iter_mut() {
+        for cond in tl.tokens.iter_mut().flat_map(|t| t.conditions.iter_mut()) {
+            let target_tl = timeline_names
+                .iter()
+                .position(|n| *n == cond.timeline)
+                .unwrap();
+            cond.token_idx = positions[target_tl][&cond.token_idx];
+        }
+    }
+
+    let total_cost = timelines
+        .iter()
+        .flat_map(|tl| tl.tokens.iter())
+        .map(|t| t.cost)
+        .sum();
+
+    Solution {
+        timelines,
+        end_of_time: backend.real_value(end_of_time),
+        total_cost,
+    }
+}
+
+fn objective_expr<B: Backend>(
+    backend: &B,
+    objective: &Objective,
+    tokens: &[Token<'_, B>],
+    states: &[State<B>],
+    end_of_time: &B::Real,
+) -> B::Real {
+    let cost = || {
+        let zero = backend.real_const(0, 1);
+        let terms = tokens
+            .iter()
+            .filter(|t| !t.cost.is_zero())
+            .map(|t| {
+                let state = &states[t.state];
+                let duration = backend.sub(&[&state.end_time, &state.start_time]);
+                let cost = backend.add(&[
+                    &backend.real_from_f32(t.cost.fixed),
+                    &backend.mul(&[&backend.real_from_f32(t.cost.per_time_unit), &duration]),
+                ]);
+                match t.active.as_ref() {
+                    Some(active) => backend.ite(active, &cost, &zero),
+                    None => cost,
+                }
+            })
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return zero;
         }
+        let term_refs = terms.iter().collect::<Vec<_>>();
+        backend.add(&term_refs)
+    };
+
+    match objective {
+        Objective::Cost => cost(),
+        Objective::Makespan => end_of_time.clone(),
+        Objective::Weighted {
+            cost: cost_weight,
+            makespan: makespan_weight,
+        } => backend.add(&[
+            &backend.mul(&[&backend.real_from_f32(*cost_weight), &cost()]),
+            &backend.mul(&[&backend.real_from_f32(*makespan_weight), end_of_time]),
+        ]),
     }
 }
 
+/// Write the assertions and a `check-sat-assuming` command for the assumptions
+/// to `<dir>/iteration_<n>.smt2`.
+fn write_smt2<B: Backend>(
+    dir: &Path,
+    iteration: usize,
+    backend: &B,
+    assumptions: &[B::Bool],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("iteration_{:04}.smt2", iteration));
+    let mut f = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    writeln!(f, "; paraspace refinement iteration {}", iteration)?;
+    writeln!(f, "(set-option :produce-unsat-cores true)")?;
+    writeln!(f, "(set-option :produce-unsat-assumptions true)")?;
+    writeln!(f, "(set-logic QF_LRA)")?;
+    writeln!(f, "{}", backend.to_smtlib())?;
+    let assumptions = assumptions
+        .iter()
+        .map(|a| backend.term_to_smtlib(a))
+        .collect::<Vec<_>>();
+    writeln!(f, "(check-sat-assuming ({}))", assumptions.join(" "))?;
+    f.flush()?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
-fn expand_until<'a, 'z>(
+fn expand_until<'a, B: Backend>(
     problem: &'a Problem,
-    ctx: &'z z3::Context,
-    solver: &z3::Solver,
+    backend: &B,
     timeline_idx: usize,
-    timelines: &mut Vec<Timeline<'z>>,
-    states: &mut Vec<State<'z>>,
-    tokens: &mut Vec<Token<'a, 'z>>,
+    timelines: &mut Vec<Timeline<B>>,
+    states: &mut Vec<State<B>>,
+    tokens: &mut Vec<Token<'a, B>>,
     value: Option<&str>,
 ) -> bool {
     let n = if let Some(value) = value {
@@ -1363,32 +2153,22 @@ fn expand_until<'a, 'z>(
     };
 
     assert!(n > 0);
-    expand_n(
-        problem,
-        ctx,
-        solver,
-        timeline_idx,
-        timelines,
-        states,
-        tokens,
-        n,
-    );
+    expand_n(problem, backend, timeline_idx, timelines, states, tokens, n);
     true
 }
 
 #[allow(clippy::too_many_arguments)]
-fn expand_n<'a, 'z>(
+fn expand_n<'a, B: Backend>(
     problem: &'a Problem,
-    ctx: &'z z3::Context,
-    solver: &z3::Solver,
+    backend: &B,
     timeline_idx: usize,
-    timelines: &mut Vec<Timeline<'z>>,
-    states: &mut Vec<State<'z>>,
-    tokens: &mut Vec<Token<'a, 'z>>,
+    timelines: &mut Vec<Timeline<B>>,
+    states: &mut Vec<State<B>>,
+    tokens: &mut Vec<Token<'a, B>>,
     n: usize,
 ) {
     for _ in 0..n {
-        let (state_seq, start_time, prev_values) =
+        let (state_seq, start_time, start_late, prev_values) =
             if let Some(prev_state_idx) = timelines[timeline_idx].states.last().copied() {
                 let prev_state = &states[prev_state_idx];
                 let prev_values = prev_state
@@ -1398,20 +2178,28 @@ fn expand_n<'a, 'z>(
                     .collect::<Vec<_>>();
                 let seq = prev_state.state_seq + 1;
 
-                (seq, prev_state.end_time.clone(), Some(prev_values))
-            } else {
                 (
-                    0,
-                    Real::fresh_const(
-                        ctx,
-                        &format!("t_{}_init_", problem.timelines[timeline_idx].name),
-                    ),
-                    None,
+                    seq,
+                    prev_state.end_time.clone(),
+                    prev_state.end_late.clone(),
+                    Some(prev_values),
                 )
+            } else {
+                let start_time = backend
+                    .fresh_real(&format!("t_{}_init_", problem.timelines[timeline_idx].name));
+                (0, start_time.clone(), start_time, None)
             };
 
-        let end_time =
-            Real::fresh_const(ctx, &format!("t_{}_", problem.timelines[timeline_idx].name));
+        let end_time = backend.fresh_real(&format!("t_{}_", problem.timelines[timeline_idx].name));
+        let end_late = if problem.timelines[timeline_idx]
+            .token_types
+            .iter()
+            .any(|tt| tt.contingent)
+        {
+            backend.fresh_real(&format!("t_{}_late_", problem.timelines[timeline_idx].name))
+        } else {
+            end_time.clone()
+        };
 
         let state_idx = states.len();
         let token_start_idx = tokens.len();
@@ -1419,7 +2207,9 @@ fn expand_n<'a, 'z>(
 
         trace!(
             "adding tl:{} state:{} values{:?}",
-            problem.timelines[timeline_idx].name, state_seq, values
+            problem.timelines[timeline_idx].name,
+            state_seq,
+            values
         );
 
         let state_tokens = values
@@ -1430,18 +2220,22 @@ fn expand_n<'a, 'z>(
                 // let active = if prev_unique && values.len() == 1 {
                 //     None // only one chocie heree
                 // } else {
-                //     Some(Bool::fresh_const(ctx, "x"))
+                //     Some(backend.fresh_bool("x"))
                 // };
 
-                let active = Some(Bool::fresh_const(
-                    ctx,
-                    &format!(
-                        "state_{}_{}_{}_",
-                        problem.timelines[timeline_idx].name,
-                        timelines[timeline_idx].states.len(),
-                        idx
-                    ),
-                ));
+                let active = Some(backend.fresh_bool(&format!(
+                    "state_{}_{}_{}_",
+                    problem.timelines[timeline_idx].name,
+                    timelines[timeline_idx].states.len(),
+                    idx
+                )));
+
+                let cost = problem.timelines[timeline_idx]
+                    .token_types
+                    .iter()
+                    .find(|tt| tt.value == *value)
+                    .map(|tt| tt.cost)
+                    .unwrap_or_default();
 
                 Token {
                     active,
@@ -1449,6 +2243,7 @@ fn expand_n<'a, 'z>(
                     value,
                     fact: false,
                     conditions: Vec::new(),
+                    cost,
                 }
             })
             .collect::<Vec<_>>();
@@ -1467,21 +2262,21 @@ fn expand_n<'a, 'z>(
             .filter_map(|t| t.active.as_ref().map(|b| (b, 1)))
             .collect::<Vec<_>>();
         if am1.len() > 1 {
-            solver.assert(&Bool::pb_le(ctx, &am1, 1));
+            backend.assert(&backend.pb_le(&am1, 1));
         }
 
         let tokens_active = state_tokens
             .iter()
             .map(|t| t.active.as_ref().unwrap())
             .collect::<Vec<_>>();
-        let state_active = Bool::or(ctx, &tokens_active);
+        let state_active = backend.or(&tokens_active);
 
         if state_seq > 0 {
             // for state_token in state_tokens.iter() {
             //     // If a token is active, the previous state must also be active.
             //     let mut clause = Vec::new();
             //     if let Some(active) = state_token.active.as_ref() {
-            //         clause.push(Bool::not(active));
+            //         clause.push(backend.not(active));
             //     }
 
             //     // any in the previous state
@@ -1497,15 +2292,12 @@ fn expand_n<'a, 'z>(
 
             //     if !any_const {
             //         let clause_refs = clause.iter().collect::<Vec<_>>();
-            //         solver.assert(&Bool::or(ctx, &clause_refs));
+            //         backend.assert(&backend.or(&clause_refs));
             //     }
             // }
 
             let prev_state_idx = timelines[timeline_idx].states[state_seq - 1];
-            solver.assert(&Bool::implies(
-                &state_active,
-                &states[prev_state_idx].active,
-            ))
+            backend.assert(&backend.implies(&state_active, &states[prev_state_idx].active))
         }
 
         let token_idxs = state_tokens
@@ -1520,19 +2312,64 @@ fn expand_n<'a, 'z>(
             tokens: token_idxs,
             start_time,
             end_time,
+            start_late,
+            end_late,
             timeline: timeline_idx,
             active: state_active,
-            activate_next: Bool::fresh_const(ctx, "nxstate"),
+            activate_next: backend.fresh_bool("nxstate"),
             can_expand: true,
         });
         timelines[timeline_idx].states.push(state_idx);
     }
 }
 
+/// Add states to the timelines that have token types with effects or rates on the
+/// given reservoir or numeric timelines, so that the levels can be changed by new tokens.
+fn expand_level_users<'a, B: Backend>(
+    problem: &'a Problem,
+    backend: &B,
+    levels: &[usize],
+    timelines: &mut Vec<Timeline<B>>,
+    states: &mut Vec<State<B>>,
+    tokens: &mut Vec<Token<'a, B>>,
+) -> bool {
+    let mut expanded = false;
+    for timeline_idx in 0..timelines.len() {
+        if timelines[timeline_idx].facts_only {
+            continue;
+        }
+
+        let tl_spec = &problem.timelines[timeline_idx];
+        let last_values = states[*timelines[timeline_idx].states.last().unwrap()]
+            .tokens
+            .iter()
+            .map(|t| tokens[*t].value)
+            .collect::<Vec<_>>();
+
+        let distance = tl_spec
+            .token_types
+            .iter()
+            .filter(|tt| {
+                let affects =
+                    |name: &str| levels.iter().any(|l| problem.timelines[*l].name == name);
+                tt.resource_effects.iter().any(|e| affects(&e.timeline_ref))
+                    || tt.numeric_rates.iter().any(|r| affects(&r.timeline_ref))
+            })
+            .filter_map(|tt| distance_to(tl_spec, &last_values, &tt.value))
+            .min();
+
+        if let Some(n) = distance {
+            expand_n(problem, backend, timeline_idx, timelines, states, tokens, n);
+            expanded = true;
+        }
+    }
+    expanded
+}
+
 fn next_values_from<'a>(
     timeline: &'a problem::Timeline,
     prev_values: Option<&[&'a str]>,
-) -> HashSet<&'a str> {
+) -> BTreeSet<&'a str> {
     // Want to prune the set of possible token types for the next,
     // based on the possible token types in the previous state and the
     // transitions conditions, i.e. the conditions on the immediately previous
@@ -1541,7 +2378,7 @@ fn next_values_from<'a>(
     fn set_from_ok<'a>(
         timeline: &'a problem::Timeline,
         prev_values: Option<&[&str]>,
-    ) -> HashSet<&'a str> {
+    ) -> BTreeSet<&'a str> {
         let has_required_previous_values = |tt: &TokenType| -> bool {
             !tt.conditions.iter().any(|cs| {
                 cs.iter().all(|c| {
@@ -1563,13 +2400,13 @@ fn next_values_from<'a>(
             .token_types
             .iter()
             .filter_map(|tt| has_required_previous_values(tt).then(|| tt.value.as_str()))
-            .collect::<HashSet<_>>()
+            .collect::<BTreeSet<_>>()
     }
 
     fn set_to_ok_value<'a>(
         timeline: &'a problem::Timeline,
         prev_value: &str,
-    ) -> Option<HashSet<&'a str>> {
+    ) -> Option<BTreeSet<&'a str>> {
         timeline
             .token_types
             .iter()
@@ -1578,7 +2415,7 @@ fn next_values_from<'a>(
                 tt.conditions
                     .iter()
                     .map(|cs| {
-                        let mut set = HashSet::new();
+                        let mut set = BTreeSet::new();
                         for c in cs.iter() {
                             if let Some(v) = c.is_timeline_transition_to(&timeline.name) {
                                 set.insert(v);
@@ -1599,12 +2436,12 @@ fn next_values_from<'a>(
     fn set_to_ok<'a>(
         timeline: &'a problem::Timeline,
         prev_values: Option<&[&str]>,
-    ) -> Option<HashSet<&'a str>> {
+    ) -> Option<BTreeSet<&'a str>> {
         if let Some(prev_values) = prev_values {
             prev_values
                 .iter()
                 .map(|pv| set_to_ok_value(timeline, pv))
-                .fold(Some(HashSet::new()), |a, b| match (a, b) {
+                .fold(Some(BTreeSet::new()), |a, b| match (a, b) {
                     (Some(a), Some(b)) => Some(a.union(&b).copied().collect()),
                     _ => None,
                 })
@@ -1634,6 +2471,41 @@ fn can_expand(timeline: &problem::Timeline, start_values: &[&str], goal_value: &
     distance_to(timeline, start_values, goal_value).is_some()
 }
 
+/// The values that a timeline can reach through its transition conditions, with the
+/// smallest number of tokens needed to get there. The timeline starts from the
+/// values of its facts, or from any value that has no required previous value if
+/// it has no facts.
+pub fn reachable_values(timeline: &problem::Timeline) -> BTreeMap<&str, usize> {
+    let mut reachable = BTreeMap::new();
+    let mut current_values = timeline
+        .static_tokens
+        .iter()
+        .filter(|t| matches!(t.const_time, TokenTime::Fact(_, _)))
+        .map(|t| t.value.as_str())
+        .collect::<BTreeSet<_>>();
+    let mut steps = 0;
+    if current_values.is_empty() {
+        current_values = next_values_from(timeline, None);
+        steps = 1;
+    }
+
+    while !current_values.is_empty() {
+        for value in current_values.iter() {
+            reachable.insert(*value, steps);
+        }
+        let next_values = next_values_from(
+            timeline,
+            Some(&current_values.iter().copied().collect::<Vec<_>>()),
+        );
+        current_values = next_values
+            .into_iter()
+            .filter(|v| !reachable.contains_key(v))
+            .collect();
+        steps += 1;
+    }
+    reachable
+}
+
 fn distance_to(
     timeline: &problem::Timeline,
     start_values: &[&str],
@@ -1644,12 +2516,12 @@ fn distance_to(
         start_values,
         goal_value
     );
-    let mut visited_values = HashSet::new();
-    let mut current_values = start_values.iter().copied().collect::<HashSet<_>>();
+    let mut visited_values = BTreeSet::new();
+    let mut current_values = start_values.iter().copied().collect::<BTreeSet<_>>();
 
     let mut steps = 1;
     loop {
-        let mut next_values = HashSet::new();
+        let mut next_values = BTreeSet::new();
         let reachable = next_values_from(
             timeline,
             Some(&current_values.iter().copied().collect::<Vec<_>>()),
@@ -1674,10 +2546,340 @@ fn distance_to(
     }
 }
 
-#[derive(Default)]
-struct ResourceConstraint<'z3> {
+/// Users of a reservoir or numeric timeline. The time of each event is given as its
+/// earliest and latest time, which are different only for events that depend on the
+/// durations of contingent tokens when solving for strong controllability.
+#[allow(clippy::type_complexity)]
+struct LevelConstraint<B: Backend> {
+    effects: Vec<(Option<B::Bool>, (B::Real, B::Real), i32)>,
+    rates: Vec<(Option<B::Bool>, (B::Real, B::Real), (B::Real, B::Real), f32)>,
+    conditions: Vec<(Option<B::Bool>, (B::Real, B::Real), NumericRelation, f32)>,
+    /// The number of effects, rates and conditions that have been encoded.
+    integrated: (usize, usize, usize),
+    /// The times where the level is checked, with the lowest and highest level
+    /// at that time once they have been needed.
+    checkpoints: Vec<Checkpoint<B>>,
+    /// The levels that have been encoded, each as a sum that ends with a remainder.
+    /// Later users are added to the remainder, and the remainder is zero when
+    /// there are no more users.
+    levels: Vec<LevelSum<B>>,
+    guard: Option<B::Bool>,
+}
+
+struct Checkpoint<B: Backend> {
+    active: Option<B::Bool>,
+    time: B::Real,
+    inclusive: bool,
+    lowest: Option<B::Real>,
+    highest: Option<B::Real>,
+}
+
+struct LevelSum<B: Backend> {
+    time: B::Real,
+    inclusive: bool,
+    lowest: bool,
+    remainder: B::Real,
+}
+
+impl<B: Backend> Default for LevelConstraint<B> {
+    fn default() -> Self {
+        LevelConstraint {
+            effects: Vec::new(),
+            rates: Vec::new(),
+            conditions: Vec::new(),
+            integrated: (0, 0, 0),
+            checkpoints: Vec::new(),
+            levels: Vec::new(),
+            guard: None,
+        }
+    }
+}
+
+impl<B: Backend> LevelConstraint<B> {
+    fn has_new_users(&self) -> bool {
+        self.integrated != (self.effects.len(), self.rates.len(), self.conditions.len())
+    }
+
+    /// The changes of the level before the given time made by the given effects and
+    /// rates, so that their sum is the lowest or highest level for any durations of
+    /// the contingent tokens. The lowest level counts consumption from the earliest
+    /// time it can happen and production from the latest time, and the other way
+    /// around for the highest level. If `inclusive`, the instantaneous effects
+    /// happening exactly at that time are included.
+    #[allow(clippy::type_complexity)]
+    fn changes(
+        backend: &B,
+        effects: &[(Option<B::Bool>, (B::Real, B::Real), i32)],
+        rates: &[(Option<B::Bool>, (B::Real, B::Real), (B::Real, B::Real), f32)],
+        time: &B::Real,
+        inclusive: bool,
+        lowest: bool,
+    ) -> Vec<B::Real> {
+        let zero = backend.real_const(0, 1);
+        let with_active = |active: &Option<B::Bool>, cond: B::Bool| match active {
+            Some(active) => backend.and(&[active, &cond]),
+            None => cond,
+        };
+
+        let mut terms = Vec::new();
+        for (active, (early, late), amount) in effects.iter() {
+            let effect_time = if (*amount < 0) == lowest { early } else { late };
+            let before = if inclusive {
+                backend.le(effect_time, time)
+            } else {
+                backend.lt(effect_time, time)
+            };
+            terms.push(backend.ite(
+                &with_active(active, before),
+                &backend.real_const(*amount, 1),
+                &zero,
+            ));
+        }
+
+        for (active, (start_early, start_late), (end_early, end_late), rate) in rates.iter() {
+            // The part of the token's duration that lies before the given time, where
+            // the token lasts as long or as short as possible.
+            let (start, end) = if (*rate < 0.0) == lowest {
+                (start_early, end_late)
+            } else {
+                (start_late, end_early)
+            };
+            let not_started = if start_late == start_early && end_late == end_early {
+                backend.le(time, start)
+            } else {
+                backend.or(&[&backend.le(time, start), &backend.le(end, start)])
+            };
+            let elapsed = backend.ite(
+                &not_started,
+                &zero,
+                &backend.ite(
+                    &backend.ge(time, end),
+                    &backend.sub(&[end, start]),
+                    &backend.sub(&[time, start]),
+                ),
+            );
+            let change = backend.mul(&[&backend.real_from_f32(*rate), &elapsed]);
+            terms.push(match active {
+                Some(active) => backend.ite(active, &change, &zero),
+                None => change,
+            });
+        }
+
+        terms
+    }
+
+    /// A new variable for the lowest or highest level at the given time, including
+    /// all the current effects and rates.
+    fn level_at(
+        &mut self,
+        backend: &B,
+        initial: &B::Real,
+        time: &B::Real,
+        inclusive: bool,
+        lowest: bool,
+    ) -> B::Real {
+        let level = backend.fresh_real("level");
+        let remainder = backend.fresh_real("levelrest");
+        let mut terms = vec![initial.clone()];
+        terms.extend(Self::changes(
+            backend,
+            &self.effects,
+            &self.rates,
+            time,
+            inclusive,
+            lowest,
+        ));
+        terms.push(remainder.clone());
+        let term_refs = terms.iter().collect::<Vec<_>>();
+        backend.assert(&backend.eq(&level, &backend.add(&term_refs)));
+
+        self.levels.push(LevelSum {
+            time: time.clone(),
+            inclusive,
+            lowest,
+            remainder,
+        });
+        level
+    }
+
+    fn checkpoint_level(
+        &mut self,
+        backend: &B,
+        initial: &B::Real,
+        idx: usize,
+        lowest: bool,
+    ) -> B::Real {
+        let cached = if lowest {
+            &self.checkpoints[idx].lowest
+        } else {
+            &self.checkpoints[idx].highest
+        };
+        if let Some(level) = cached {
+            return level.clone();
+        }
+
+        let (time, inclusive) = (
+            self.checkpoints[idx].time.clone(),
+            self.checkpoints[idx].inclusive,
+        );
+        let level = self.level_at(backend, initial, &time, inclusive, lowest);
+        if lowest {
+            self.checkpoints[idx].lowest = Some(level.clone());
+        } else {
+            self.checkpoints[idx].highest = Some(level.clone());
+        }
+        level
+    }
+
+    /// Asserts the constraints for the users that were added since the last call.
+    /// The levels encoded earlier are extended with the new users instead of being
+    /// encoded again. Returns the constraints that hold only while there are no more
+    /// users, which must be assumed through a guard literal.
+    fn encode(
+        &mut self,
+        backend: &B,
+        initial: &B::Real,
+        min: Option<&B::Real>,
+        max: Option<&B::Real>,
+    ) -> Vec<B::Bool> {
+        let implied_by = |active: &Option<B::Bool>, cond: B::Bool| match active {
+            Some(active) => backend.implies(active, &cond),
+            None => cond,
+        };
+        let times = |(early, late): &(B::Real, B::Real)| {
+            if early == late {
+                vec![early.clone()]
+            } else {
+                vec![early.clone(), late.clone()]
+            }
+        };
+        let (n_effects, n_rates, n_conditions) = self.integrated;
+
+        // Add the new users to the levels that were encoded before.
+        for level in self.levels.iter_mut() {
+            let mut terms = Self::changes(
+                backend,
+                &self.effects[n_effects..],
+                &self.rates[n_rates..],
+                &level.time,
+                level.inclusive,
+                level.lowest,
+            );
+            let remainder = backend.fresh_real("levelrest");
+            terms.push(remainder.clone());
+            let term_refs = terms.iter().collect::<Vec<_>>();
+            backend.assert(&backend.eq(&level.remainder, &backend.add(&term_refs)));
+            level.remainder = remainder;
+        }
+
+        // The level is piecewise linear, so it is enough to check the bounds at the
+        // times where effects happen and where rates start and stop. At an effect,
+        // the level just before it is checked too, since a rate can cross a bound
+        // right before an effect brings the level back.
+        let first_new_checkpoint = self.checkpoints.len();
+        let mut new_checkpoints = Vec::new();
+        for (active, time, _) in self.effects[n_effects..].iter() {
+            for time in times(time) {
+                new_checkpoints.push((active.clone(), time.clone(), true));
+                new_checkpoints.push((active.clone(), time, false));
+            }
+        }
+        for (active, start, end, _) in self.rates[n_rates..].iter() {
+            for time in times(start).into_iter().chain(times(end)) {
+                new_checkpoints.push((active.clone(), time, true));
+            }
+        }
+        for (active, time, inclusive) in new_checkpoints {
+            self.checkpoints.push(Checkpoint {
+                active,
+                time,
+                inclusive,
+                lowest: None,
+                highest: None,
+            });
+        }
+
+        for idx in first_new_checkpoint..self.checkpoints.len() {
+            let active = self.checkpoints[idx].active.clone();
+            if let Some(min) = min {
+                let lowest = self.checkpoint_level(backend, initial, idx, true);
+                backend.assert(&implied_by(&active, backend.ge(&lowest, min)));
+            }
+            if let Some(max) = max {
+                let highest = self.checkpoint_level(backend, initial, idx, false);
+                backend.assert(&implied_by(&active, backend.le(&highest, max)));
+            }
+        }
+
+        for cond_idx in 0..self.conditions.len() {
+            let (active, (early, late), relation, value) = self.conditions[cond_idx].clone();
+            let is_new = cond_idx >= n_conditions;
+            if !is_new && early == late {
+                continue;
+            }
+
+            let lowest = relation == NumericRelation::GreaterOrEqual;
+            let value = backend.real_from_f32(value);
+            let holds = |level: &B::Real| {
+                if lowest {
+                    backend.ge(level, &value)
+                } else {
+                    backend.le(level, &value)
+                }
+            };
+
+            if is_new {
+                let level = self.level_at(backend, initial, &early, false, lowest);
+                backend.assert(&implied_by(&active, holds(&level)));
+            }
+
+            if early != late {
+                // The condition must hold at any time the token can start or end.
+                if is_new {
+                    let level = self.level_at(backend, initial, &late, false, lowest);
+                    backend.assert(&implied_by(&active, holds(&level)));
+                }
+                let first_checkpoint = if is_new { 0 } else { first_new_checkpoint };
+                for idx in first_checkpoint..self.checkpoints.len() {
+                    if !self.checkpoints[idx].inclusive {
+                        continue;
+                    }
+                    let time = self.checkpoints[idx].time.clone();
+                    let level = self.checkpoint_level(backend, initial, idx, lowest);
+                    let within =
+                        backend.and(&[&backend.le(&early, &time), &backend.lt(&time, &late)]);
+                    backend.assert(&implied_by(
+                        &active,
+                        backend.implies(&within, &holds(&level)),
+                    ));
+                }
+            }
+        }
+
+        self.integrated = (self.effects.len(), self.rates.len(), self.conditions.len());
+
+        let zero = backend.real_const(0, 1);
+        self.levels
+            .iter()
+            .map(|level| backend.eq(&level.remainder, &zero))
+            .collect()
+    }
+}
+
+struct ResourceConstraint<B: Backend> {
     capacity: Option<u32>,
-    users: Vec<(Option<Bool<'z3>>, usize, u32)>,
+    users: Vec<(Option<B::Bool>, usize, u32)>,
     integrated: usize,
     closed: bool,
 }
+
+impl<B: Backend> Default for ResourceConstraint<B> {
+    fn default() -> Self {
+        ResourceConstraint {
+            capacity: None,
+            users: Vec::new(),
+            integrated: 0,
+            closed: false,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! The Z3 backend, which calls Z3 through its C API.
+
+use crate::backend::{Backend, SatResult};
+use crate::{z3real_from_f32, z3real_value};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use z3::ast::{Ast, Bool, Real};
+
+#[derive(Debug)]
+pub struct Z3Backend<'ctx> {
+    ctx: &'ctx z3::Context,
+    solver: z3::Solver<'ctx>,
+    model: RefCell<Option<z3::Model<'ctx>>>,
+    // The assertions, and each pseudo-boolean constraint with an equivalent sum of
+    // `ite` terms, for writing standard SMT-LIB that other solvers accept. They are
+    // only recorded when `record_smtlib` has been called.
+    record: bool,
+    assertions: RefCell<Vec<Bool<'ctx>>>,
+    pb_constraints: RefCell<Vec<(Bool<'ctx>, Bool<'ctx>)>>,
+}
+
+impl<'ctx> Z3Backend<'ctx> {
+    pub fn new(ctx: &'ctx z3::Context, random_seed: u32) -> Self {
+        let solver = z3::Solver::new(ctx);
+        let mut params = z3::Params::new(ctx);
+        params.set_bool("auto_config", false);
+        params.set_u32("smt.arith.solver", 6);
+        params.set_u32("random_seed", random_seed);
+        log::debug!("Z3 params:\n{}", params);
+        solver.set_params(&params);
+        Z3Backend {
+            ctx,
+            solver,
+            model: RefCell::new(None),
+            record: false,
+            assertions: RefCell::new(Vec::new()),
+            pb_constraints: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Keep the assertions, so that `to_smtlib` gives standard SMT-LIB without Z3's
+    /// pseudo-boolean constraints. Used for the `smt2_dump_dir` setting.
+    pub fn record_smtlib(mut self) -> Self {
+        self.record = true;
+        self
+    }
+
+    fn check_assumptions(&self, assumptions: &[Bool<'ctx>]) -> SatResult {
+        match self.solver.check_assumptions(assumptions) {
+            z3::SatResult::Sat => SatResult::Sat,
+            z3::SatResult::Unsat => SatResult::Unsat,
+            z3::SatResult::Unknown => SatResult::Unknown,
+        }
+    }
+
+    /// Replace Z3's pseudo-boolean constraints in a term with sums of `ite` terms.
+    fn without_pb(&self, a: &Bool<'ctx>) -> Bool<'ctx> {
+        let pb_constraints = self.pb_constraints.borrow();
+        let substitutions = pb_constraints
+            .iter()
+            .map(|(pb, sum)| (pb, sum))
+            .collect::<Vec<_>>();
+        a.substitute(&substitutions)
+    }
+}
+
+impl<'ctx> Backend for Z3Backend<'ctx> {
+    type Bool = Bool<'ctx>;
+    type Real = Real<'ctx>;
+
+    fn fresh_bool(&self, prefix: &str) -> Bool<'ctx> {
+        Bool::fresh_const(self.ctx, prefix)
+    }
+
+    fn fresh_real(&self, prefix: &str) -> Real<'ctx> {
+        Real::fresh_const(self.ctx, prefix)
+    }
+
+    fn bool_const(&self, value: bool) -> Bool<'ctx> {
+        Bool::from_bool(self.ctx, value)
+    }
+
+    fn real_const(&self, num: i32, den: i32) -> Real<'ctx> {
+        Real::from_real(self.ctx, num, den)
+    }
+
+    fn real_from_f32(&self, value: f32) -> Real<'ctx> {
+        z3real_from_f32(self.ctx, value)
+    }
+
+    fn not(&self, a: &Bool<'ctx>) -> Bool<'ctx> {
+        a.not()
+    }
+
+    fn and(&self, xs: &[&Bool<'ctx>]) -> Bool<'ctx> {
+        Bool::and(self.ctx, xs)
+    }
+
+    fn or(&self, xs: &[&Bool<'ctx>]) -> Bool<'ctx> {
+        Bool::or(self.ctx, xs)
+    }
+
+    fn implies(&self, a: &Bool<'ctx>, b: &Bool<'ctx>) -> Bool<'ctx> {
+        a.implies(b)
+    }
+
+    fn pb_le(&self, xs: &[(&Bool<'ctx>, i32)], k: i32) -> Bool<'ctx> {
+        let pb = Bool::pb_le(self.ctx, xs, k);
+        if !self.record {
+            return pb;
+        }
+
+        let zero = Real::from_real(self.ctx, 0, 1);
+        let terms = xs
+            .iter()
+            .map(|(x, c)| x.ite(&Real::from_real(self.ctx, *c, 1), &zero))
+            .collect::<Vec<_>>();
+        let sum = if terms.is_empty() {
+            zero.clone()
+        } else {
+            Real::add(self.ctx, &terms.iter().collect::<Vec<_>>())
+        };
+        let sum = sum.le(&Real::from_real(self.ctx, k, 1));
+        self.pb_constraints.borrow_mut().push((pb.clone(), sum));
+        pb
+    }
+
+    fn add(&self, xs: &[&Real<'ctx>]) -> Real<'ctx> {
+        Real::add(self.ctx, xs)
+    }
+
+    fn sub(&self, xs: &[&Real<'ctx>]) -> Real<'ctx> {
+        Real::sub(self.ctx, xs)
+    }
+
+    fn mul(&self, xs: &[&Real<'ctx>]) -> Real<'ctx> {
+        Real::mul(self.ctx, xs)
+    }
+
+    fn ite(&self, cond: &Bool<'ctx>, a: &Real<'ctx>, b: &Real<'ctx>) -> Real<'ctx> {
+        cond.ite(a, b)
+    }
+
+    fn le(&self, a: &Real<'ctx>, b: &Real<'ctx>) -> Bool<'ctx> {
+        a.le(b)
+    }
+
+    fn lt(&self, a: &Real<'ctx>, b: &Real<'ctx>) -> Bool<'ctx> {
+        a.lt(b)
+    }
+
+    fn ge(&self, a: &Real<'ctx>, b: &Real<'ctx>) -> Bool<'ctx> {
+        a.ge(b)
+    }
+
+    fn eq(&self, a: &Real<'ctx>, b: &Real<'ctx>) -> Bool<'ctx> {
+        a._eq(b)
+    }
+
+    fn assert(&self, a: &Bool<'ctx>) {
+        self.solver.assert(a);
+        if self.record {
+            self.assertions.borrow_mut().push(a.clone());
+        }
+    }
+
+    fn check(&self, assumptions: &[Bool<'ctx>], cancel: Option<&AtomicBool>) -> SatResult {
+        let result = match cancel {
+            None => self.check_assumptions(assumptions),
+            Some(cancel) => {
+                // Interrupt the solver from another thread when the cancel flag is set.
+                let handle = self.ctx.handle();
+                let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+                std::thread::scope(|s| {
+                    s.spawn(move || {
+                        while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                            done_rx.recv_timeout(std::time::Duration::from_millis(10))
+                        {
+                            if cancel.load(Ordering::Relaxed) {
+                                handle.interrupt();
+                                break;
+                            }
+                        }
+                    });
+                    let result = self.check_assumptions(assumptions);
+                    drop(done_tx);
+                    result
+                })
+            }
+        };
+
+        *self.model.borrow_mut() = if result == SatResult::Sat {
+            self.solver.get_model()
+        } else {
+            None
+        };
+        result
+    }
+
+    fn unsat_core(&self) -> Vec<Bool<'ctx>> {
+        self.solver.get_unsat_core()
+    }
+
+    fn bool_value(&self, a: &Bool<'ctx>) -> bool {
+        let model = self.model.borrow();
+        let model = model.as_ref().expect("no model");
+        model.eval(a, true).unwrap().as_bool().unwrap()
+    }
+
+    fn real_value(&self, a: &Real<'ctx>) -> f32 {
+        let model = self.model.borrow();
+        let model = model.as_ref().expect("no model");
+        z3real_value(&model.eval(a, true).unwrap())
+    }
+
+    fn to_smtlib(&self) -> String {
+        if !self.record {
+            return self.solver.to_string();
+        }
+
+        // Z3 prints pseudo-boolean constraints with its own syntax, so print a copy of
+        // the assertions without them.
+        let solver = z3::Solver::new(self.ctx);
+        for a in self.assertions.borrow().iter() {
+            solver.assert(&self.without_pb(a));
+        }
+        solver.to_string()
+    }
+
+    fn term_to_smtlib(&self, a: &Bool<'ctx>) -> String {
+        self.without_pb(a).to_string()
+    }
+}
@@ -0,0 +1,264 @@
+#![cfg(feature = "z3")]
+
+mod common;
+
+use common::two_routes_problem;
+use paraspace::{
+    backend::{Backend, SatResult},
+    plancheck::check_plan,
+    problem::*,
+    transitionsolver::{solve_with_backend, Objective, SolverSettings},
+    z3backend::Z3Backend,
+    SolverError,
+};
+use std::cell::Cell;
+use std::sync::atomic::AtomicBool;
+
+// A backend that passes everything on to another backend and counts the calls to
+// the solver. After `decided` checks, the solver gives up.
+#[derive(Debug)]
+struct CountingBackend<B> {
+    inner: B,
+    checks: Cell<usize>,
+    assertions: Cell<usize>,
+    decided: usize,
+}
+
+impl<B: Backend> Backend for CountingBackend<B> {
+    type Bool = B::Bool;
+    type Real = B::Real;
+
+    fn fresh_bool(&self, prefix: &str) -> B::Bool {
+        self.inner.fresh_bool(prefix)
+    }
+    fn fresh_real(&self, prefix: &str) -> B::Real {
+        self.inner.fresh_real(prefix)
+    }
+    fn bool_const(&self, value: bool) -> B::Bool {
+        self.inner.bool_const(value)
+    }
+    fn real_const(&self, num: i32, den: i32) -> B::Real {
+        self.inner.real_const(num, den)
+    }
+    fn real_from_f32(&self, value: f32) -> B::Real {
+        self.inner.real_from_f32(value)
+    }
+    fn not(&self, a: &B::Bool) -> B::Bool {
+        self.inner.not(a)
+    }
+    fn and(&self, xs: &[&B::Bool]) -> B::Bool {
+        self.inner.and(xs)
+    }
+    fn or(&self, xs: &[&B::Bool]) -> B::Bool {
+        self.inner.or(xs)
+    }
+    fn implies(&self, a: &B::Bool, b: &B::Bool) -> B::Bool {
+        self.inner.implies(a, b)
+    }
+    fn pb_le(&self, xs: &[(&B::Bool, i32)], k: i32) -> B::Bool {
+        self.inner.pb_le(xs, k)
+    }
+    fn add(&self, xs: &[&B::Real]) -> B::Real {
+        self.inner.add(xs)
+    }
+    fn sub(&self, xs: &[&B::Real]) -> B::Real {
+        self.inner.sub(xs)
+    }
+    fn mul(&self, xs: &[&B::Real]) -> B::Real {
+        self.inner.mul(xs)
+    }
+    fn ite(&self, cond: &B::Bool, a: &B::Real, b: &B::Real) -> B::Real {
+        self.inner.ite(cond, a, b)
+    }
+    fn le(&self, a: &B::Real, b: &B::Real) -> B::Bool {
+        self.inner.le(a, b)
+    }
+    fn lt(&self, a: &B::Real, b: &B::Real) -> B::Bool {
+        self.inner.lt(a, b)
+    }
+    fn ge(&self, a: &B::Real, b: &B::Real) -> B::Bool {
+        self.inner.ge(a, b)
+    }
+    fn eq(&self, a: &B::Real, b: &B::Real) -> B::Bool {
+        self.inner.eq(a, b)
+    }
+    fn assert(&self, a: &B::Bool) {
+        self.assertions.set(self.assertions.get() + 1);
+        self.inner.assert(a)
+    }
+    fn check(&self, assumptions: &[B::Bool], cancel: Option<&AtomicBool>) -> SatResult {
+        self.checks.set(self.checks.get() + 1);
+        if self.checks.get() > self.decided {
+            return SatResult::Unknown;
+        }
+        self.inner.check(assumptions, cancel)
+    }
+    fn unsat_core(&self) -> Vec<B::Bool> {
+        self.inner.unsat_core()
+    }
+    fn bool_value(&self, a: &B::Bool) -> bool {
+        self.inner.bool_value(a)
+    }
+    fn real_value(&self, a: &B::Real) -> f32 {
+        self.inner.real_value(a)
+    }
+    fn to_smtlib(&self) -> String {
+        self.inner.to_smtlib()
+    }
+    fn term_to_smtlib(&self, a: &B::Bool) -> String {
+        self.inner.term_to_smtlib(a)
+    }
+}
+
+#[test]
+pub fn wrapped_backend() {
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        ..Default::default()
+    };
+    let ctx = z3::Context::new(&z3::Config::new());
+    let backend = CountingBackend {
+        inner: Z3Backend::new(&ctx, 0),
+        checks: Cell::new(0),
+        assertions: Cell::new(0),
+        decided: usize::MAX,
+    };
+
+    let problem = two_routes_problem();
+    let solution = solve_with_backend(&problem, &settings, &backend, None).unwrap();
+    assert_eq!(check_plan(&problem, &solution), Ok(()));
+    let values = solution.timelines[0]
+        .tokens
+        .iter()
+        .map(|t| t.value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec!["A", "moveAC", "C", "moveCB", "B"]);
+
+    // Optimizing needs at least one check to find a plan and one to prove it optimal.
+    assert!(backend.checks.get() >= 2);
+    assert!(backend.assertions.get() > 0);
+    assert!(!backend.to_smtlib().is_empty());
+}
+
+#[test]
+pub fn smt2_dump() {
+    let dir = std::env::temp_dir().join(format!("paraspace_smt2_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        smt2_dump_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+    let ctx = z3::Context::new(&z3::Config::new());
+    let backend = CountingBackend {
+        inner: Z3Backend::new(&ctx, 0).record_smtlib(),
+        checks: Cell::new(0),
+        assertions: Cell::new(0),
+        decided: usize::MAX,
+    };
+    solve_with_backend(&two_routes_problem(), &settings, &backend, None).unwrap();
+
+    // One file for each call to the solver.
+    let mut files = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files.len(), backend.checks.get());
+    assert_eq!(files[0], "iteration_0001.smt2");
+
+    let contents = std::fs::read_to_string(dir.join(&files[0])).unwrap();
+    assert!(contents.contains("(set-option :produce-unsat-assumptions true)"));
+    assert!(contents.contains("(set-logic QF_LRA)"));
+    assert!(contents.contains("(declare-fun"));
+    assert!(contents.contains("(assert"));
+    assert!(contents.trim_end().ends_with("))"));
+    assert!(contents.contains("(check-sat-assuming ("));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn undecided_solver() {
+    // The robot can't reach B while the window is open, so the first check is
+    // unsatisfiable, and the solver gives up on the next one, which either refines
+    // the problem or minimizes the core.
+    let mut problem = two_routes_problem();
+    problem.timelines[0].token_types[5]
+        .conditions
+        .push(vec![Condition {
+            temporal_relationship: TemporalRelationship::Cover,
+            amount: 0,
+            timeline_ref: "window".to_string(),
+            value: "open".to_string(),
+        }]);
+    problem.timelines.push(Timeline {
+        name: "window".to_string(),
+        static_tokens: vec![Token {
+            value: "open".to_string(),
+            const_time: TokenTime::Fact(Some(0), Some(5)),
+            capacity: 0,
+            conditions: vec![],
+        }],
+        ..Default::default()
+    });
+    for minimize_cores in [false, true] {
+        let settings = SolverSettings {
+            minimize_cores,
+            ..Default::default()
+        };
+        let ctx = z3::Context::new(&z3::Config::new());
+        let backend = CountingBackend {
+            inner: Z3Backend::new(&ctx, 0),
+            checks: Cell::new(0),
+            assertions: Cell::new(0),
+            decided: 1,
+        };
+        assert!(matches!(
+            solve_with_backend(&problem, &settings, &backend, None),
+            Err(SolverError::Unknown)
+        ));
+        assert_eq!(backend.checks.get(), 2);
+    }
+}
+
+#[test]
+pub fn standard_pseudo_boolean_constraints() {
+    let ctx = z3::Context::new(&z3::Config::new());
+    let backend = Z3Backend::new(&ctx, 0).record_smtlib();
+    let (a, b, c) = (
+        backend.fresh_bool("a"),
+        backend.fresh_bool("b"),
+        backend.fresh_bool("c"),
+    );
+    let pb = backend.pb_le(&[(&a, 1), (&b, 2)], 2);
+    backend.assert(&backend.or(&[&pb, &a]));
+    backend.assert(&backend.implies(&c, &backend.not(&pb)));
+
+    // Other solvers don't know Z3's syntax for pseudo-boolean constraints.
+    let smtlib = backend.to_smtlib();
+    assert!(!smtlib.contains("pble"));
+    assert!(!smtlib.contains("at-most"));
+    assert!(smtlib.contains("(ite "));
+    assert_eq!(backend.check(&[c], None), SatResult::Sat);
+}
+
+#[test]
+pub fn exact_float_constants() {
+    let ctx = z3::Context::new(&z3::Config::new());
+    let backend = Z3Backend::new(&ctx, 0);
+    for value in [0.0, 5.0, -1.5, 0.1, 1e-3, 3e6, -2.5e-7, 1e30] {
+        let x = backend.fresh_real("x");
+        backend.assert(&backend.eq(&x, &backend.real_from_f32(value)));
+        assert_eq!(backend.check(&[], None), SatResult::Sat);
+        if value.abs() < 1e9 {
+            assert_eq!(backend.real_value(&x), value);
+        }
+    }
+
+    // 0.1 is not exactly representable, so it is not the rational 1/10.
+    let tenth = backend.real_from_f32(0.1);
+    assert_eq!(
+        backend.check(&[backend.eq(&tenth, &backend.real_const(1, 10))], None),
+        SatResult::Unsat
+    );
+}
@@ -0,0 +1,58 @@
+use paraspace::{batch, generators, problem::Solution};
+use std::path::Path;
+
+#[test]
+pub fn solve_directory() {
+    let dir = std::env::temp_dir().join(format!("paraspace_batch_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["carbonara_1p_1c", "goac_1pics_1wind"] {
+        let problem = generators::by_name(name).unwrap();
+        std::fs::write(
+            dir.join(format!("{}.json", name)),
+            serde_json::to_string(&problem).unwrap(),
+        )
+        .unwrap();
+    }
+    std::fs::write(dir.join("bad.json"), "{}").unwrap();
+
+    let files = batch::input_files(&dir).unwrap();
+    assert_eq!(files.len(), 3);
+    let results = batch::solve_files(&files, &Default::default(), 2, |_| {});
+    let statuses = results
+        .iter()
+        .map(|r| r.status.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(statuses, vec!["InvalidJson", "Solved", "Solved"]);
+    assert_eq!(results[2].makespan, Some(4000.0));
+
+    let output = results[2].output.as_ref().unwrap();
+    assert_eq!(output, &dir.join("goac_1pics_1wind.out.json"));
+    let solution =
+        serde_json::from_str::<Solution>(&std::fs::read_to_string(output).unwrap()).unwrap();
+    assert_eq!(solution.end_of_time, 4000.0);
+
+    // The solutions are not solved again.
+    assert_eq!(batch::input_files(&dir).unwrap(), files);
+    assert_eq!(
+        batch::input_files(&dir.join("goac_*")).unwrap(),
+        vec![dir.join("goac_1pics_1wind.json")]
+    );
+
+    let summary = batch::summary(&results);
+    assert_eq!(summary.lines().count(), 5);
+    assert!(summary.ends_with("Solved 2 of 3 files.\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn output_names() {
+    assert_eq!(
+        batch::output_file(Path::new("a/b/problem.json")),
+        Path::new("a/b/problem.out.json")
+    );
+    assert_eq!(
+        batch::output_file(Path::new("problem")),
+        Path::new("problem.out.json")
+    );
+}
@@ -0,0 +1,79 @@
+use paraspace::benchmark::{compare, expand_pattern, to_csv, InstanceSet, Manifest};
+use std::path::Path;
+
+fn manifest() -> Manifest {
+    Manifest {
+        sets: vec![InstanceSet {
+            name: "small".to_string(),
+            instances: vec![
+                "instances/jobs_2j_1m.json".to_string(),
+                "instances/robot_?.json".to_string(),
+            ],
+            generated: vec![],
+            settings: None,
+        }],
+        repetitions: 2,
+        timeout: Some(60.0),
+        settings: Default::default(),
+    }
+}
+
+#[test]
+pub fn run_manifest() {
+    let mut n_progress = 0;
+    let results = manifest()
+        .run(Path::new("benchmarks"), |_| n_progress += 1)
+        .unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(n_progress, 4);
+    assert!(results.iter().all(|r| r.solved() && r.stats.smt_calls > 0));
+    assert_eq!(results[0].instance, "instances/jobs_2j_1m.json");
+    assert_eq!(results[2].instance, "instances/robot_5.json");
+
+    let csv = to_csv(&results);
+    assert_eq!(csv.lines().count(), 5);
+    assert!(csv.starts_with("set,instance,repetition,status,solve_time,"));
+
+    // The JSON results can be read back as a baseline.
+    let json = serde_json::to_string(&results).unwrap();
+    let baseline = serde_json::from_str::<Vec<paraspace::benchmark::RunResult>>(&json).unwrap();
+    assert!(compare(&results, &baseline, 0.2).is_empty());
+}
+
+#[test]
+pub fn regressions() {
+    let results = manifest().run(Path::new("benchmarks"), |_| {}).unwrap();
+
+    let mut baseline = results.clone();
+    for r in baseline.iter_mut() {
+        r.stats.solve_time = 0.0;
+    }
+    let mut current = results;
+    current[0].stats.solve_time = 1.0;
+    current[1].stats.solve_time = 1.0;
+    current[3].status = "Timeout".to_string();
+
+    let regressions = compare(&current, &baseline, 0.2);
+    assert_eq!(regressions.len(), 2);
+    assert_eq!(regressions[0].instance, "instances/jobs_2j_1m.json");
+    assert!(regressions[0].message.starts_with("median solve time"));
+    assert_eq!(regressions[1].instance, "instances/robot_5.json");
+    assert_eq!(regressions[1].message, "was solved, now Timeout");
+}
+
+#[test]
+pub fn patterns() {
+    let files = expand_pattern(Path::new("benchmarks/instances/robot_*.json")).unwrap();
+    assert_eq!(files.len(), 3);
+    assert!(
+        expand_pattern(Path::new("benchmarks/instances/none_*.json"))
+            .unwrap()
+            .is_empty()
+    );
+
+    let mut missing = manifest();
+    missing.sets[0]
+        .instances
+        .push("instances/none_*.json".to_string());
+    assert!(missing.run(Path::new("benchmarks"), |_| {}).is_err());
+}
@@ -0,0 +1,76 @@
+use paraspace::{generators, problem::Solution};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Run the command line tool with the input on stdin, and return the exit status,
+// stdout and stderr.
+fn run(args: &[&str], input: &str) -> (bool, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_paraspace"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+pub fn solve_from_stdin() {
+    let problem = serde_json::to_string(&generators::goac(1, 1)).unwrap();
+    let (success, stdout, stderr) = run(&["solve", "-"], &problem);
+    assert!(success);
+    let solution = serde_json::from_str::<Solution>(&stdout).unwrap();
+    assert_eq!(solution.end_of_time, 4000.0);
+    assert!(stderr.contains("[INFO] Solved."));
+
+    let (success, stdout, stderr) = run(&["solve", "-", "--log-level", "off"], &problem);
+    assert!(success);
+    assert!(serde_json::from_str::<Solution>(&stdout).is_ok());
+    assert!(stderr.is_empty());
+}
+
+#[test]
+pub fn error_json() {
+    // The goal refers to a timeline that does not exist.
+    let problem = r#"{"timelines": [{"name": "a", "token_types": [], "static_tokens": [
+        {"value": "x", "capacity": 0, "const_time": "Goal", "conditions": [[
+            {"timeline_ref": "b", "temporal_relationship": "Cover", "value": "y", "amount": 0}
+        ]]}
+    ]}]}"#;
+    let (success, stdout, stderr) = run(&["solve", "-"], problem);
+    assert!(!success);
+    let error = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+    assert_eq!(error["kind"], "InvalidProblem");
+    assert!(stderr.contains("[ERROR]"));
+
+    // The validation errors go to stderr.
+    let (success, stdout, stderr) = run(&["validate", "-"], problem);
+    assert!(!success);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("[ERROR]") && stderr.contains("\"b\""));
+
+    let (success, stdout, _) = run(&["solve", "-"], "{");
+    assert!(!success);
+    let error = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+    assert_eq!(error["kind"], "InvalidJson");
+
+    // Errors are reported even when the log is off.
+    let (success, _, stderr) = run(&["solve", "-", "--log-level", "off"], "{");
+    assert!(!success);
+    assert!(stderr.contains("[ERROR] InvalidJson"));
+    let (success, _, stderr) = run(&["validate", "-", "--log-level", "off"], "{");
+    assert!(!success);
+    assert!(stderr.contains("[ERROR] Invalid file -"));
+}
@@ -0,0 +1,209 @@
+//! Problem fixtures shared by the integration tests. Each test file uses only some
+//! of them.
+#![allow(dead_code)]
+
+use paraspace::{
+    flexible::{FlexibleSolution, FlexibleToken},
+    problem::*,
+};
+
+pub fn cond(timeline: &str, temporal_relationship: TemporalRelationship, value: &str) -> Condition {
+    cond_with_amount(timeline, temporal_relationship, value, 0)
+}
+
+// A condition that uses `amount` of the capacity of the token it is linked to.
+pub fn cond_with_amount(
+    timeline: &str,
+    temporal_relationship: TemporalRelationship,
+    value: &str,
+    amount: u32,
+) -> Condition {
+    Condition {
+        temporal_relationship,
+        amount,
+        timeline_ref: timeline.to_string(),
+        value: value.to_string(),
+    }
+}
+
+pub fn static_token(value: &str, const_time: TokenTime) -> Token {
+    static_token_with_capacity(value, const_time, 0)
+}
+
+pub fn static_token_with_capacity(value: &str, const_time: TokenTime, capacity: u32) -> Token {
+    Token {
+        value: value.to_string(),
+        const_time,
+        capacity,
+        conditions: vec![],
+    }
+}
+
+pub fn token_type(
+    value: &str,
+    conditions: Vec<Vec<Condition>>,
+    duration: (usize, Option<usize>),
+) -> TokenType {
+    TokenType {
+        value: value.to_string(),
+        conditions,
+        duration_limits: duration,
+        ..Default::default()
+    }
+}
+
+// The robot moves for 5 to 10 time units, and the camera takes a
+// picture while the robot is at the target.
+pub fn picture_problem() -> Problem {
+    Problem {
+        timelines: vec![
+            Timeline {
+                name: "robot".to_string(),
+                token_types: vec![
+                    token_type("idle", vec![], (1, None)),
+                    token_type(
+                        "move",
+                        vec![vec![cond("robot", TemporalRelationship::MetBy, "idle")]],
+                        (5, Some(10)),
+                    ),
+                    token_type(
+                        "arrived",
+                        vec![vec![cond("robot", TemporalRelationship::MetBy, "move")]],
+                        (1, None),
+                    ),
+                ],
+                static_tokens: vec![
+                    static_token("idle", TokenTime::Fact(Some(0), None)),
+                    static_token("arrived", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+            Timeline {
+                name: "camera".to_string(),
+                token_types: vec![
+                    token_type("off", vec![], (1, None)),
+                    token_type(
+                        "picture",
+                        vec![
+                            vec![cond("camera", TemporalRelationship::MetBy, "off")],
+                            vec![cond("robot", TemporalRelationship::Cover, "arrived")],
+                        ],
+                        (2, Some(2)),
+                    ),
+                    token_type(
+                        "done",
+                        vec![vec![cond("camera", TemporalRelationship::MetBy, "picture")]],
+                        (1, None),
+                    ),
+                ],
+                static_tokens: vec![
+                    static_token("off", TokenTime::Fact(Some(0), None)),
+                    static_token("done", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+        ],
+    }
+}
+
+// The picture problem, where the robot cannot move before the gate opens at
+// time 3. The gate's facts are not given in the order they happen.
+pub fn gated_picture_problem() -> Problem {
+    let mut problem = picture_problem();
+    problem.timelines[0].token_types[1]
+        .conditions
+        .push(vec![cond(
+            "gate",
+            TemporalRelationship::StartsAfter,
+            "open",
+        )]);
+    problem.timelines.push(Timeline {
+        name: "gate".to_string(),
+        token_types: vec![],
+        static_tokens: vec![
+            static_token("open", TokenTime::Fact(Some(3), None)),
+            static_token("closed", TokenTime::Fact(Some(0), Some(2))),
+        ],
+        ..Default::default()
+    });
+    problem
+}
+
+pub fn transition_from(values: &[&str]) -> Vec<Condition> {
+    transition_from_on("robot", values)
+}
+
+pub fn transition_from_on(timeline: &str, values: &[&str]) -> Vec<Condition> {
+    values
+        .iter()
+        .map(|v| cond(timeline, TemporalRelationship::MetBy, v))
+        .collect()
+}
+
+// The robot can go from A to B directly or through C.
+pub fn two_routes_problem() -> Problem {
+    Problem {
+        timelines: vec![Timeline {
+            name: "robot".to_string(),
+            token_types: vec![
+                token_type("A", vec![], (1, None)),
+                token_type("moveAB", vec![transition_from(&["A"])], (10, Some(10))),
+                token_type("moveAC", vec![transition_from(&["A"])], (3, Some(3))),
+                token_type("C", vec![transition_from(&["moveAC"])], (1, None)),
+                token_type("moveCB", vec![transition_from(&["C"])], (3, Some(3))),
+                token_type("B", vec![transition_from(&["moveAB", "moveCB"])], (1, None)),
+            ],
+            static_tokens: vec![
+                static_token("A", TokenTime::Fact(Some(0), None)),
+                static_token("B", TokenTime::Goal),
+            ],
+            ..Default::default()
+        }],
+    }
+}
+
+// The first token with the given value on the named timeline of a solution.
+pub fn token<'a>(solution: &'a Solution, timeline: &str, value: &str) -> &'a SolutionToken {
+    solution
+        .timelines
+        .iter()
+        .find(|t| t.name == timeline)
+        .unwrap()
+        .tokens
+        .iter()
+        .find(|t| t.value == value)
+        .unwrap()
+}
+
+pub fn token_mut<'a>(
+    solution: &'a mut Solution,
+    timeline: &str,
+    value: &str,
+) -> &'a mut SolutionToken {
+    solution
+        .timelines
+        .iter_mut()
+        .find(|t| t.name == timeline)
+        .unwrap()
+        .tokens
+        .iter_mut()
+        .find(|t| t.value == value)
+        .unwrap()
+}
+
+// The first token with the given value on the named timeline of a flexible plan.
+pub fn flexible_token<'a>(
+    flexible: &'a FlexibleSolution,
+    timeline: &str,
+    value: &str,
+) -> &'a FlexibleToken {
+    flexible
+        .timelines
+        .iter()
+        .find(|t| t.name == timeline)
+        .unwrap()
+        .tokens
+        .iter()
+        .find(|t| t.value == value)
+        .unwrap()
+}
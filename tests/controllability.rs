@@ -0,0 +1,95 @@
+mod common;
+
+use common::{cond, picture_problem, token};
+use paraspace::{
+    problem::*,
+    transitionsolver::{solve, SolverSettings},
+    SolverError,
+};
+
+// The robot's movement takes between 5 and `max_move_duration` time units
+// depending on the terrain. The camera can take a picture of the target after the
+// robot has arrived.
+fn contingent_picture_problem(max_move_duration: Option<usize>) -> Problem {
+    let mut problem = picture_problem();
+    let movement = &mut problem.timelines[0].token_types[1];
+    movement.duration_limits = (5, max_move_duration);
+    movement.contingent = true;
+    problem.timelines[1].token_types[1].conditions[1] =
+        vec![cond("robot", TemporalRelationship::StartsAfter, "arrived")];
+    problem
+}
+
+#[test]
+pub fn strongly_controllable_picture() {
+    let settings = SolverSettings {
+        strong_controllability: true,
+        ..Default::default()
+    };
+    let solution = solve(&contingent_picture_problem(Some(10)), &settings).unwrap();
+    let movement = token(&solution, "robot", "move");
+    let picture = token(&solution, "camera", "picture");
+
+    // The picture must wait for the longest possible movement.
+    assert!(picture.start_time >= movement.start_time + 10.0 - 1e-5);
+}
+
+#[test]
+pub fn contingent_without_controllability() {
+    let solution = solve(&contingent_picture_problem(Some(10)), &Default::default()).unwrap();
+    let movement = token(&solution, "robot", "move");
+    let arrived = token(&solution, "robot", "arrived");
+    let picture = token(&solution, "camera", "picture");
+    assert!(movement.end_time - movement.start_time <= 10.0 + 1e-5);
+    assert!(picture.start_time >= arrived.start_time - 1e-5);
+}
+
+#[test]
+pub fn unbounded_contingent_duration() {
+    let settings = SolverSettings {
+        strong_controllability: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        solve(&contingent_picture_problem(None), &settings),
+        Err(SolverError::UnboundedContingentDuration(_))
+    ));
+}
+
+#[test]
+pub fn strongly_controllable_reservoir() {
+    // Instead of waiting for the robot to arrive, the picture needs a sample that
+    // the robot delivers when it arrives.
+    let mut problem = contingent_picture_problem(Some(10));
+    let movement = &mut problem.timelines[0].token_types[1];
+    movement.resource_effects.push(ResourceEffect {
+        timeline_ref: "samples".to_string(),
+        time: TokenPoint::End,
+        amount: 1,
+    });
+    let picture = &mut problem.timelines[1].token_types[1];
+    picture.conditions.pop();
+    picture.resource_effects.push(ResourceEffect {
+        timeline_ref: "samples".to_string(),
+        time: TokenPoint::Start,
+        amount: -1,
+    });
+    problem.timelines.push(Timeline {
+        name: "samples".to_string(),
+        kind: TimelineKind::Reservoir(Reservoir {
+            initial: 0,
+            min: 0,
+            max: 1,
+        }),
+        ..Default::default()
+    });
+
+    let settings = SolverSettings {
+        strong_controllability: true,
+        ..Default::default()
+    };
+    let solution = solve(&problem, &settings).unwrap();
+    let movement = token(&solution, "robot", "move");
+    let picture = token(&solution, "camera", "picture");
+    assert!(picture.start_time >= movement.start_time + 10.0 - 1e-5);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::two_routes_problem;
+use paraspace::{
+    problem::*,
+    transitionsolver::{solve, Objective, SolverSettings},
+};
+
+// The robot can go directly from A to B at a low cost, or take a faster
+// and more expensive route through C.
+fn priced_routes_problem() -> Problem {
+    let mut problem = two_routes_problem();
+    for tt in problem.timelines[0].token_types.iter_mut() {
+        let fixed = match tt.value.as_str() {
+            "moveAB" => 1.0,
+            "moveAC" | "moveCB" => 5.0,
+            _ => 0.0,
+        };
+        tt.cost = Cost {
+            fixed,
+            per_time_unit: 0.0,
+        };
+    }
+    problem
+}
+
+fn values(solution: &Solution) -> Vec<&str> {
+    solution.timelines[0]
+        .tokens
+        .iter()
+        .map(|t| t.value.as_str())
+        .collect()
+}
+
+#[test]
+pub fn minimize_cost() {
+    let settings = SolverSettings {
+        optimize: Some(Objective::Cost),
+        ..Default::default()
+    };
+    let solution = solve(&priced_routes_problem(), &settings).unwrap();
+    assert_eq!(values(&solution), vec!["A", "moveAB", "B"]);
+    assert!((solution.total_cost - 1.0).abs() < 1e-5);
+
+    let token_costs = solution.timelines[0]
+        .tokens
+        .iter()
+        .map(|t| t.cost)
+        .sum::<f32>();
+    assert!((token_costs - solution.total_cost).abs() < 1e-5);
+}
+
+#[test]
+pub fn minimize_makespan() {
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        ..Default::default()
+    };
+    let solution = solve(&priced_routes_problem(), &settings).unwrap();
+    assert_eq!(values(&solution), vec!["A", "moveAC", "C", "moveCB", "B"]);
+    assert!((solution.total_cost - 10.0).abs() < 1e-5);
+}
+
+#[test]
+pub fn cost_per_time_unit() {
+    let mut problem = priced_routes_problem();
+    for tt in problem.timelines[0].token_types.iter_mut() {
+        if tt.value == "moveAB" {
+            tt.cost = Cost {
+                fixed: 0.5,
+                per_time_unit: 0.25,
+            };
+        }
+    }
+
+    let settings = SolverSettings {
+        optimize: Some(Objective::Weighted {
+            cost: 1.0,
+            makespan: 0.0,
+        }),
+        ..Default::default()
+    };
+    let solution = solve(&problem, &settings).unwrap();
+    assert_eq!(values(&solution), vec!["A", "moveAB", "B"]);
+    assert!((solution.total_cost - 3.0).abs() < 1e-5);
+}
+
+#[test]
+pub fn no_costs() {
+    let mut problem = priced_routes_problem();
+    for tt in problem.timelines[0].token_types.iter_mut() {
+        tt.cost = Default::default();
+    }
+
+    for objective in [
+        Objective::Cost,
+        Objective::Weighted {
+            cost: 1.0,
+            makespan: 1.0,
+        },
+    ] {
+        let settings = SolverSettings {
+            optimize: Some(objective),
+            ..Default::default()
+        };
+        let solution = solve(&problem, &settings).unwrap();
+        assert_eq!(solution.total_cost, 0.0);
+    }
+}
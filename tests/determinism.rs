@@ -0,0 +1,112 @@
+mod common;
+
+use common::{static_token, token_type};
+use paraspace::{
+    problem::*,
+    transitionsolver::{solve, SolverSettings},
+};
+
+fn conds(
+    timeline: &str,
+    temporal_relationship: TemporalRelationship,
+    values: &[&str],
+) -> Vec<Condition> {
+    values
+        .iter()
+        .map(|v| Condition {
+            temporal_relationship,
+            amount: 0,
+            timeline_ref: timeline.to_string(),
+            value: v.to_string(),
+        })
+        .collect()
+}
+
+// Each robot can drive from a to d through either b or c, and the routes take
+// the same time. The camera takes a picture of whichever robot arrives at d.
+fn robot(name: &str) -> Timeline {
+    use TemporalRelationship::MetBy;
+    Timeline {
+        name: name.to_string(),
+        token_types: vec![
+            token_type("at_a", vec![], (1, None)),
+            token_type(
+                "drive_ab",
+                vec![conds(name, MetBy, &["at_a"])],
+                (5, Some(5)),
+            ),
+            token_type(
+                "drive_ac",
+                vec![conds(name, MetBy, &["at_a"])],
+                (5, Some(5)),
+            ),
+            token_type("at_b", vec![conds(name, MetBy, &["drive_ab"])], (1, None)),
+            token_type("at_c", vec![conds(name, MetBy, &["drive_ac"])], (1, None)),
+            token_type(
+                "drive_bd",
+                vec![conds(name, MetBy, &["at_b"])],
+                (5, Some(5)),
+            ),
+            token_type(
+                "drive_cd",
+                vec![conds(name, MetBy, &["at_c"])],
+                (5, Some(5)),
+            ),
+            token_type(
+                "at_d",
+                vec![conds(name, MetBy, &["drive_bd", "drive_cd"])],
+                (1, None),
+            ),
+        ],
+        static_tokens: vec![static_token("at_a", TokenTime::Fact(Some(0), None))],
+        ..Default::default()
+    }
+}
+
+fn robots_problem() -> Problem {
+    let names = ["robot1", "robot2", "robot3", "robot4"];
+    let picture_conds = names
+        .iter()
+        .flat_map(|name| conds(name, TemporalRelationship::Cover, &["at_d"]))
+        .collect();
+    let mut timelines = names.iter().map(|name| robot(name)).collect::<Vec<_>>();
+    timelines.push(Timeline {
+        name: "camera".to_string(),
+        token_types: vec![
+            token_type("off", vec![], (1, None)),
+            token_type(
+                "picture",
+                vec![
+                    conds("camera", TemporalRelationship::MetBy, &["off"]),
+                    picture_conds,
+                ],
+                (2, Some(2)),
+            ),
+            token_type(
+                "done",
+                vec![conds("camera", TemporalRelationship::MetBy, &["picture"])],
+                (1, None),
+            ),
+        ],
+        static_tokens: vec![
+            static_token("off", TokenTime::Fact(Some(0), None)),
+            static_token("done", TokenTime::Goal),
+        ],
+        ..Default::default()
+    });
+    Problem { timelines }
+}
+
+#[test]
+pub fn identical_solutions() {
+    let problem = robots_problem();
+    let settings = SolverSettings {
+        random_seed: 7,
+        ..Default::default()
+    };
+    let first = serde_json::to_string(&solve(&problem, &settings).unwrap()).unwrap();
+    for _ in 0..5 {
+        let solution = serde_json::to_string(&solve(&problem, &settings).unwrap()).unwrap();
+        assert_eq!(first, solution);
+    }
+}
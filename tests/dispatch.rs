@@ -0,0 +1,196 @@
+mod common;
+
+use common::{gated_picture_problem, picture_problem, token};
+use paraspace::{
+    dispatch::{Dispatcher, EventKind, Feedback, ReplanReason},
+    flexible::FlexibleSolution,
+    problem::*,
+    transitionsolver::solve,
+};
+
+fn token_idx(solution: &Solution, timeline: usize, value: &str) -> usize {
+    solution.timelines[timeline]
+        .tokens
+        .iter()
+        .position(|t| t.value == value)
+        .unwrap()
+}
+
+fn feedback(timeline: &str, token_idx: usize, kind: EventKind, time: f32) -> Feedback {
+    let timeline = timeline.to_string();
+    match kind {
+        EventKind::Start => Feedback::Started {
+            timeline,
+            token_idx,
+            time,
+        },
+        EventKind::End => Feedback::Ended {
+            timeline,
+            token_idx,
+            time,
+        },
+    }
+}
+
+/// Execute the ready events at their earliest times, except the end of the
+/// robot's movement which is delayed until `move_end`.
+fn execute(dispatcher: &mut Dispatcher, move_end: f32) -> Vec<(String, String, EventKind, f32)> {
+    let mut log = Vec::new();
+    let mut now = 0.0;
+    while !dispatcher.is_finished() {
+        let events = dispatcher.ready_events(now);
+        let event = match events.first() {
+            Some(event) => event.clone(),
+            None => {
+                now = dispatcher
+                    .pending_events()
+                    .iter()
+                    .map(|e| e.earliest)
+                    .fold(f32::INFINITY, f32::min)
+                    .max(now + 1.0);
+                continue;
+            }
+        };
+
+        let mut time = event.earliest.max(now);
+        if event.value == "move" && event.kind == EventKind::End {
+            time = time.max(move_end);
+        }
+        // Events at the same time on the timeline, such as the end of a token and
+        // the start of the next one, are executed together.
+        let pending = dispatcher.pending_events();
+        assert!(dispatcher
+            .feedback(feedback(&event.timeline, event.token_idx, event.kind, time))
+            .is_none());
+        let still_pending = dispatcher.pending_events();
+        for e in pending {
+            if !still_pending
+                .iter()
+                .any(|p| (&p.timeline, p.token_idx, p.kind) == (&e.timeline, e.token_idx, e.kind))
+            {
+                log.push((e.timeline, e.value, e.kind, time));
+            }
+        }
+        now = time;
+    }
+    log
+}
+
+fn time_of(log: &[(String, String, EventKind, f32)], value: &str, kind: EventKind) -> f32 {
+    log.iter()
+        .find(|(_, v, k, _)| v == value && *k == kind)
+        .unwrap()
+        .3
+}
+
+#[test]
+pub fn dispatch_flexible_plan_with_delay() {
+    let problem = picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+    let mut dispatcher = Dispatcher::new(flexible);
+
+    let pending = dispatcher.pending_events();
+    assert!(pending.windows(2).all(|w| w[0].earliest <= w[1].earliest));
+
+    // The movement is slower than the fastest 5 time units.
+    let log = execute(&mut dispatcher, 10.0);
+    let move_start = time_of(&log, "move", EventKind::Start);
+    let move_end = time_of(&log, "move", EventKind::End);
+    assert!((move_end - 10.0).abs() < 1e-3);
+    assert!(move_end - move_start > 5.0);
+
+    // The picture waits for the robot to arrive.
+    assert!(time_of(&log, "picture", EventKind::Start) >= move_end - 1e-3);
+    assert!(dispatcher.replan_required().is_none());
+}
+
+#[test]
+pub fn dispatch_movement_too_slow() {
+    let problem = picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let move_idx = token_idx(&solution, 0, "move");
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+    let mut dispatcher = Dispatcher::new(flexible);
+
+    let start = dispatcher
+        .pending_events()
+        .into_iter()
+        .find(|e| e.value == "move" && e.kind == EventKind::Start)
+        .unwrap();
+    let start_time = start.earliest;
+    for event in dispatcher.ready_events(start_time) {
+        dispatcher.feedback(feedback(
+            &event.timeline,
+            event.token_idx,
+            event.kind,
+            start_time,
+        ));
+    }
+    dispatcher.feedback(feedback("robot", move_idx, EventKind::Start, start_time));
+
+    // The movement can take at most 10 time units.
+    assert!(dispatcher.advance(start_time + 5.0).is_none());
+    assert_eq!(
+        dispatcher.advance(start_time + 11.0),
+        Some(&ReplanReason::DeadlineMissed {
+            timeline: "robot".to_string(),
+            token_idx: move_idx
+        })
+    );
+}
+
+#[test]
+pub fn dispatch_fixed_solution() {
+    let problem = picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let picture = &solution.timelines[1].tokens[token_idx(&solution, 1, "picture")];
+    let planned_move_end = solution.timelines[0].tokens[token_idx(&solution, 0, "move")].end_time;
+    let mut dispatcher = Dispatcher::from_solution(&solution).unwrap();
+
+    // Delaying the movement by 3 time units delays the picture by the same amount.
+    let log = execute(&mut dispatcher, planned_move_end + 3.0);
+    let picture_start = time_of(&log, "picture", EventKind::Start);
+    assert!((picture_start - (picture.start_time + 3.0)).abs() < 1e-3);
+}
+
+#[test]
+pub fn dispatch_failure() {
+    let problem = picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let move_idx = token_idx(&solution, 0, "move");
+    let mut dispatcher = Dispatcher::from_solution(&solution).unwrap();
+    let reason = dispatcher.feedback(Feedback::Failed {
+        timeline: "robot".to_string(),
+        token_idx: move_idx,
+        time: 2.0,
+    });
+    assert!(matches!(reason, Some(ReplanReason::Failed { .. })));
+}
+
+#[test]
+pub fn dispatch_with_unsorted_facts() {
+    let problem = gated_picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let planned_open = token(&solution, "gate", "open").start_time;
+    let planned_move = token(&solution, "robot", "move").start_time;
+    let mut dispatcher = Dispatcher::from_solution(&solution).unwrap();
+
+    // The movement is linked to the gate opening, so it waits when the gate opens
+    // 2 time units late.
+    let open_idx = token_idx(&solution, 2, "open");
+    assert!(dispatcher
+        .feedback(feedback(
+            "gate",
+            open_idx,
+            EventKind::Start,
+            planned_open + 2.0
+        ))
+        .is_none());
+    let move_start = dispatcher
+        .pending_events()
+        .into_iter()
+        .find(|e| e.value == "move" && e.kind == EventKind::Start)
+        .unwrap();
+    assert!(move_start.earliest >= planned_move + 2.0 - 1e-3);
+}
@@ -0,0 +1,75 @@
+use paraspace::{
+    export::{parse_epoch, to_csv, to_ical, IcalOptions},
+    problem::*,
+};
+
+fn solution() -> Solution {
+    let token =
+        |value: &str, start: f32, end: f32, conditions: Vec<SolutionCondition>| SolutionToken {
+            value: value.to_string(),
+            start_time: start,
+            end_time: end,
+            conditions,
+            cost: 0.0,
+            fact: false,
+        };
+    Solution {
+        timelines: vec![
+            SolutionTimeline {
+                name: "oven".to_string(),
+                tokens: vec![token("Heat, high", 0.0, 30.0, vec![])],
+            },
+            SolutionTimeline {
+                name: "bread".to_string(),
+                tokens: vec![token(
+                    "Bake",
+                    30.0,
+                    75.5,
+                    vec![SolutionCondition {
+                        timeline: "oven".to_string(),
+                        token_idx: 0,
+                        temporal_relationship: Some(TemporalRelationship::MetBy),
+                        amount: 0,
+                    }],
+                )],
+            },
+        ],
+        end_of_time: 75.5,
+        total_cost: 0.0,
+    }
+}
+
+#[test]
+pub fn csv() {
+    assert_eq!(
+        to_csv(&solution()),
+        "timeline,token,value,start,end,duration,fact,dependencies\n\
+         oven,0,\"Heat, high\",0,30,30,false,\n\
+         bread,0,Bake,30,75.5,45.5,false,\"oven[0] Heat, high\"\n"
+    );
+}
+
+#[test]
+pub fn ical() {
+    let options = IcalOptions {
+        epoch: parse_epoch("2024-02-28T23:30:00Z").unwrap(),
+        time_unit: 60.0,
+        ..Default::default()
+    };
+    let ics = to_ical(&solution(), &options);
+    let lines = ics.split("\r\n").collect::<Vec<_>>();
+    assert_eq!(lines[0], "BEGIN:VCALENDAR");
+    assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 2);
+    assert!(lines.contains(&"DTSTART:20240228T233000Z"));
+    // Across the leap day.
+    assert!(lines.contains(&"DTSTART:20240229T000000Z"));
+    assert!(lines.contains(&"DTEND:20240229T004530Z"));
+    assert!(lines.contains(&"SUMMARY:Heat\\, high (oven)"));
+    assert!(lines.contains(&"DESCRIPTION:Depends on: oven[0] Heat\\, high"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    assert!(lines.iter().all(|l| l.len() <= 75));
+
+    assert_eq!(parse_epoch("1970-01-02"), Ok(86400));
+    assert_eq!(parse_epoch("1969-12-31T23:59:59Z"), Ok(-1));
+    assert!(parse_epoch("2024-13-01").is_err());
+}
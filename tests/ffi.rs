@@ -0,0 +1,97 @@
+use paraspace::{ffi::*, problem::*};
+use serde_json::Value;
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+fn robot_problem() -> Problem {
+    Problem {
+        timelines: vec![Timeline {
+            name: "robot".to_string(),
+            token_types: vec![
+                TokenType {
+                    value: "A".to_string(),
+                    duration_limits: (1, None),
+                    ..Default::default()
+                },
+                TokenType {
+                    value: "B".to_string(),
+                    duration_limits: (2, Some(2)),
+                    conditions: vec![vec![Condition {
+                        temporal_relationship: TemporalRelationship::MetBy,
+                        amount: 0,
+                        timeline_ref: "robot".to_string(),
+                        value: "A".to_string(),
+                    }]],
+                    ..Default::default()
+                },
+            ],
+            static_tokens: vec![
+                Token {
+                    value: "A".to_string(),
+                    const_time: TokenTime::Fact(Some(0), None),
+                    capacity: 0,
+                    conditions: vec![],
+                },
+                Token {
+                    value: "B".to_string(),
+                    const_time: TokenTime::Goal,
+                    capacity: 0,
+                    conditions: vec![],
+                },
+            ],
+            ..Default::default()
+        }],
+    }
+}
+
+unsafe fn take_json(s: *mut std::os::raw::c_char) -> Value {
+    assert!(!s.is_null());
+    let value = serde_json::from_str(CStr::from_ptr(s).to_str().unwrap()).unwrap();
+    paraspace_string_free(s);
+    value
+}
+
+#[test]
+pub fn solve_and_cancel() {
+    unsafe {
+        let problem_json = CString::new(paraspace::to_json(&robot_problem())).unwrap();
+        let settings_json = CString::new(r#"{"optimize": "Makespan"}"#).unwrap();
+        let mut error = ptr::null_mut();
+        let problem = paraspace_problem_from_json(problem_json.as_ptr(), &mut error);
+        let settings = paraspace_settings_from_json(settings_json.as_ptr(), &mut error);
+        assert!(!problem.is_null() && !settings.is_null() && error.is_null());
+        let cancel = paraspace_cancel_token_new();
+
+        let response = take_json(paraspace_solve(problem, settings, cancel));
+        assert_eq!(response["status"], "Solved");
+        let tokens = &response["solution"]["timelines"][0]["tokens"];
+        assert_eq!(tokens[1]["value"], "B");
+        assert_eq!(tokens[1]["end_time"], 3.0);
+
+        paraspace_cancel(cancel);
+        let response = take_json(paraspace_solve(problem, ptr::null(), cancel));
+        assert_eq!(response["error"]["kind"], "Cancelled");
+
+        paraspace_cancel_token_free(cancel);
+        paraspace_settings_free(settings);
+        paraspace_problem_free(problem);
+    }
+}
+
+#[test]
+pub fn parse_errors() {
+    unsafe {
+        let json = CString::new("{\"timelines\": [").unwrap();
+        let mut error = ptr::null_mut();
+        assert!(paraspace_problem_from_json(json.as_ptr(), &mut error).is_null());
+        assert!(!error.is_null());
+        paraspace_string_free(error);
+
+        // The error message is optional.
+        assert!(paraspace_settings_from_json(json.as_ptr(), ptr::null_mut()).is_null());
+
+        let response = take_json(paraspace_solve_json(json.as_ptr()));
+        assert_eq!(response["error"]["kind"], "InvalidJson");
+        assert!(!CStr::from_ptr(paraspace_version()).to_bytes().is_empty());
+    }
+}
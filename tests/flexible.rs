@@ -0,0 +1,116 @@
+mod common;
+
+use common::{flexible_token, gated_picture_problem};
+use paraspace::{
+    flexible::{FlexibleSolution, END_OF_TIME, ORIGIN},
+    generators,
+    transitionsolver::solve,
+};
+
+#[test]
+pub fn flexible_picture_plan() {
+    let problem = gated_picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+
+    // The fixed times of the solution are within the bounds.
+    for (tl, flex_tl) in solution.timelines.iter().zip(flexible.timelines.iter()) {
+        for (token, flex_token) in tl.tokens.iter().zip(flex_tl.tokens.iter()) {
+            assert!(token.start_time >= flex_token.start.lower - 1e-3);
+            assert!(flex_token
+                .start
+                .upper
+                .map(|u| token.start_time <= u + 1e-3)
+                .unwrap_or(true));
+        }
+    }
+
+    let movement = flexible_token(&flexible, "robot", "move");
+    assert!((movement.start.lower - 3.0).abs() < 1e-3);
+    assert_eq!(movement.start.upper, None);
+    assert_eq!(
+        flexible.difference_bounds(movement.start_point, movement.end_point),
+        (Some(5.0), Some(10.0))
+    );
+
+    let picture = flexible_token(&flexible, "camera", "picture");
+    assert!((picture.start.lower - 8.0).abs() < 1e-3);
+
+    // The arrival lasts until the end of time.
+    let arrived = flexible_token(&flexible, "robot", "arrived");
+    assert_eq!(
+        flexible.difference_bounds(arrived.end_point, END_OF_TIME),
+        (Some(0.0), Some(0.0))
+    );
+    assert_eq!(
+        flexible.difference_bounds(ORIGIN, ORIGIN),
+        (Some(0.0), Some(0.0))
+    );
+}
+
+#[test]
+pub fn facts_with_the_same_value() {
+    // The communication windows in GOAC are facts with the same value.
+    let problem = generators::goac(1, 3);
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+    let windows = flexible
+        .timelines
+        .iter()
+        .find(|t| t.name == "Visibility")
+        .unwrap()
+        .tokens
+        .iter()
+        .map(|t| (t.start.lower, t.end.upper))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        windows,
+        vec![
+            (280.0, Some(4000.0)),
+            (8000.0, Some(15000.0)),
+            (20000.0, Some(25000.0))
+        ]
+    );
+}
+
+#[test]
+pub fn unsorted_facts() {
+    let problem = gated_picture_problem();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let gate = solution
+        .timelines
+        .iter()
+        .find(|t| t.name == "gate")
+        .unwrap();
+    let values = gate
+        .tokens
+        .iter()
+        .map(|t| t.value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec!["closed", "open"]);
+
+    // The movement is linked to the gate opening, which is the second token of the
+    // gate timeline but the first of its static tokens.
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+    let movement = flexible_token(&flexible, "robot", "move");
+    assert!((movement.start.lower - 3.0).abs() < 1e-3);
+}
+
+#[test]
+pub fn links_without_relations() {
+    let problem = gated_picture_problem();
+    let mut solution = solve(&problem, &Default::default()).unwrap();
+    for tl in solution.timelines.iter_mut() {
+        for token in tl.tokens.iter_mut() {
+            for cond in token.conditions.iter_mut() {
+                cond.temporal_relationship = None;
+            }
+        }
+    }
+
+    let flexible = FlexibleSolution::from_solution(&problem, &solution).unwrap();
+    let movement = flexible_token(&flexible, "robot", "move");
+    assert!((movement.start.lower - 3.0).abs() < 1e-3);
+    let picture = flexible_token(&flexible, "camera", "picture");
+    assert!((picture.start.lower - 8.0).abs() < 1e-3);
+}
@@ -0,0 +1,69 @@
+use paraspace::{
+    gantt::{to_html, to_svg, GanttOptions},
+    generators,
+    problem::*,
+    transitionsolver,
+};
+
+#[test]
+pub fn gantt_chart() {
+    let solution = transitionsolver::solve(&generators::goac(1, 1), &Default::default()).unwrap();
+    let tokens = solution
+        .timelines
+        .iter()
+        .map(|tl| tl.tokens.len())
+        .sum::<usize>();
+    let links = solution
+        .timelines
+        .iter()
+        .flat_map(|tl| tl.tokens.iter())
+        .map(|t| t.conditions.len())
+        .sum::<usize>();
+    assert!(links > 0);
+
+    let svg = to_svg(&solution, &Default::default());
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches(r#"<rect class="token""#).count(), tokens);
+    assert_eq!(
+        svg.matches(r#"<text class="timeline""#).count(),
+        solution.timelines.len()
+    );
+    for tl in solution.timelines.iter() {
+        assert!(svg.contains(&format!(">{}</text>", tl.name)));
+    }
+    assert!(!svg.contains(r#"class="link""#));
+
+    let options = GanttOptions {
+        links: true,
+        title: Some("goac".to_string()),
+        ..Default::default()
+    };
+    let html = to_html(&solution, &options);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>goac</title>"));
+    assert_eq!(html.matches(r#"<line class="link""#).count(), links);
+}
+
+#[test]
+pub fn escaped_names() {
+    let solution = Solution {
+        timelines: vec![SolutionTimeline {
+            name: "a<b>".to_string(),
+            tokens: vec![SolutionToken {
+                value: "x&y".to_string(),
+                start_time: 0.0,
+                end_time: 10.0,
+                conditions: vec![],
+                cost: 0.0,
+                fact: true,
+            }],
+        }],
+        end_of_time: 10.0,
+        total_cost: 0.0,
+    };
+    let svg = to_svg(&solution, &Default::default());
+    assert!(svg.contains("a&lt;b&gt;"));
+    assert!(svg.contains("x&amp;y [0, 10]"));
+    assert!(!svg.contains("a<b>"));
+}
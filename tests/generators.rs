@@ -0,0 +1,78 @@
+use paraspace::{
+    benchmark::{InstanceSet, Manifest},
+    generators,
+    plancheck::check_plan,
+    transitionsolver,
+};
+
+#[test]
+pub fn names() {
+    let names = generators::benchmark_names();
+    assert_eq!(names.len(), 36 + 5 + 45);
+    assert!(names.iter().all(|n| generators::by_name(n).is_some()));
+    assert!(generators::by_name("carbonara_1p").is_none());
+    assert!(generators::by_name("carbonara_xp_1c").is_none());
+    assert!(generators::by_name("pasta_1p_1c").is_none());
+}
+
+#[test]
+pub fn carbonara_structure() {
+    let problem = generators::carbonara(2, 1);
+    let names = problem
+        .timelines
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            "Plate_0",
+            "Plate_1",
+            "Water_2",
+            "Oil_3",
+            "spaghetti_0",
+            "lardon_0",
+            "eggs_0",
+            "carbonara_0"
+        ]
+    );
+
+    // Cooking needs one of the plates.
+    let cooking = &problem.timelines[7].token_types[0];
+    let plates = &cooking.conditions[3];
+    assert_eq!(plates.len(), 2);
+    assert!(plates
+        .iter()
+        .all(|c| c.amount == 1 && c.value == "Available"));
+}
+
+#[test]
+pub fn solve_generated() {
+    for name in ["carbonara_2p_2c", "ceramic_2m_4j", "goac_2pics_2wind"] {
+        let problem = generators::by_name(name).unwrap();
+        problem.validate().unwrap();
+        let solution = transitionsolver::solve(&problem, &Default::default()).unwrap();
+        assert_eq!(check_plan(&problem, &solution), Ok(()), "{}", name);
+    }
+}
+
+#[test]
+pub fn generated_benchmark() {
+    let manifest = Manifest {
+        sets: vec![InstanceSet {
+            name: "goac".to_string(),
+            instances: vec![],
+            generated: vec!["goac_1pics_1wind".to_string()],
+            settings: None,
+        }],
+        repetitions: 1,
+        timeout: None,
+        settings: Default::default(),
+    };
+    let results = manifest.run(std::path::Path::new("."), |_| {}).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].instance, "goac_1pics_1wind");
+    assert!(results[0].solved());
+    // The download must happen in the first visibility window.
+    assert!(results[0].end_of_time.unwrap() <= 4000.0);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::two_routes_problem;
+use paraspace::{problem::*, solve_json};
+use serde_json::{json, Value};
+
+fn call(request: Value) -> Value {
+    serde_json::from_str(&solve_json(request.to_string())).unwrap()
+}
+
+#[test]
+pub fn solved_with_settings() {
+    let response = call(json!({
+        "problem": two_routes_problem(),
+        "settings": { "optimize": "Makespan" },
+    }));
+    assert_eq!(response["status"], "Solved");
+    let tokens = response["solution"]["timelines"][0]["tokens"]
+        .as_array()
+        .unwrap();
+    assert_eq!(tokens.last().unwrap()["value"], "B");
+    assert!(tokens.last().unwrap()["start_time"].as_f64().unwrap() <= 10.0 + 1e-5);
+    assert!(response["stats"]["smt_calls"].as_u64().unwrap() >= 2);
+    assert!(response["stats"]["plans_found"].as_u64().unwrap() >= 1);
+}
+
+#[test]
+pub fn invalid_json() {
+    let response: Value = serde_json::from_str(&solve_json("{\"problem\": [".to_string())).unwrap();
+    assert_eq!(response["status"], "Failed");
+    assert_eq!(response["error"]["kind"], "InvalidJson");
+    assert!(response.get("stats").is_none());
+}
+
+#[test]
+pub fn invalid_problem() {
+    let mut problem = two_routes_problem();
+    problem.timelines[0].token_types[1].conditions[0][0].timeline_ref = "rover".to_string();
+    problem.timelines[0].token_types[2].duration_limits = (3, Some(2));
+    let response = call(json!({ "problem": problem }));
+    assert_eq!(response["status"], "Failed");
+    assert_eq!(response["error"]["kind"], "InvalidProblem");
+    let details = response["error"]["details"].as_array().unwrap();
+    assert_eq!(details.len(), 2);
+    assert!(details[0]
+        .as_str()
+        .unwrap()
+        .contains("unknown timeline \"rover\""));
+}
+
+#[test]
+pub fn solver_error() {
+    // The fact on timeline a must be covered by the fact on timeline b, but they
+    // happen at different times.
+    let fact = |value: &str, time: (usize, usize), conditions: Vec<Vec<Condition>>| Token {
+        value: value.to_string(),
+        const_time: TokenTime::Fact(Some(time.0), Some(time.1)),
+        capacity: 0,
+        conditions,
+    };
+    let cover_q = Condition {
+        temporal_relationship: TemporalRelationship::Cover,
+        amount: 0,
+        timeline_ref: "b".to_string(),
+        value: "q".to_string(),
+    };
+    let problem = Problem {
+        timelines: vec![
+            Timeline {
+                name: "a".to_string(),
+                static_tokens: vec![fact("p", (0, 1), vec![vec![cover_q]])],
+                ..Default::default()
+            },
+            Timeline {
+                name: "b".to_string(),
+                static_tokens: vec![fact("q", (5, 6), vec![])],
+                ..Default::default()
+            },
+        ],
+    };
+    let response = call(json!({ "problem": problem }));
+    assert_eq!(response["status"], "Failed");
+    assert_eq!(response["error"]["kind"], "NoSolution");
+    assert_eq!(response["error"]["message"], "No solution found");
+    assert!(response["stats"].is_object());
+}
@@ -0,0 +1,220 @@
+mod common;
+use common::static_token;
+use paraspace::{lifted::*, problem::*, transitionsolver::solve};
+
+fn param(name: &str, object_type: &str) -> Parameter {
+    Parameter {
+        name: name.to_string(),
+        object_type: object_type.to_string(),
+    }
+}
+
+fn quantifier(kind: QuantifierKind, variable: &str, object_type: &str) -> Option<Quantifier> {
+    Some(Quantifier {
+        kind,
+        variable: variable.to_string(),
+        object_type: object_type.to_string(),
+    })
+}
+
+// Robots move between locations, and the mission is complete when
+// all robots are at their home location.
+fn robots_problem() -> LiftedProblem {
+    let robot = TimelineClass {
+        name: "Robot".to_string(),
+        parameters: vec![param("home", "Location")],
+        token_types: vec![
+            LiftedTokenType {
+                value: "at_{l}".to_string(),
+                parameters: vec![param("l", "Location")],
+                duration_limits: (1, None),
+                conditions: vec![vec![LiftedCondition {
+                    quantifier: quantifier(QuantifierKind::Any, "x", "Location"),
+                    timeline_ref: "{self}".to_string(),
+                    temporal_relationship: TemporalRelationship::MetBy,
+                    value: "go_{x}_{l}".to_string(),
+                    amount: 0,
+                }]],
+                ..Default::default()
+            },
+            LiftedTokenType {
+                value: "go_{from}_{to}".to_string(),
+                parameters: vec![param("from", "Location"), param("to", "Location")],
+                duration_limits: (5, Some(5)),
+                conditions: vec![vec![LiftedCondition {
+                    quantifier: None,
+                    timeline_ref: "{self}".to_string(),
+                    temporal_relationship: TemporalRelationship::MetBy,
+                    value: "at_{from}".to_string(),
+                    amount: 0,
+                }]],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let mission = TimelineClass {
+        name: "Mission".to_string(),
+        token_types: vec![
+            LiftedTokenType {
+                value: "waiting".to_string(),
+                duration_limits: (1, None),
+                ..Default::default()
+            },
+            LiftedTokenType {
+                value: "complete".to_string(),
+                duration_limits: (1, None),
+                conditions: vec![
+                    vec![LiftedCondition {
+                        quantifier: None,
+                        timeline_ref: "{self}".to_string(),
+                        temporal_relationship: TemporalRelationship::MetBy,
+                        value: "waiting".to_string(),
+                        amount: 0,
+                    }],
+                    vec![LiftedCondition {
+                        quantifier: quantifier(QuantifierKind::All, "r", "Robot"),
+                        timeline_ref: "{r}".to_string(),
+                        temporal_relationship: TemporalRelationship::Cover,
+                        value: "at_home".to_string(),
+                        amount: 0,
+                    }],
+                ],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let robot_instance = |name: &str, start: &str| TimelineInstance {
+        name: name.to_string(),
+        class: "Robot".to_string(),
+        arguments: vec!["home".to_string()],
+        static_tokens: vec![static_token(start, TokenTime::Fact(Some(0), None))],
+    };
+
+    LiftedProblem {
+        objects: ["home", "field", "shed"]
+            .iter()
+            .map(|l| Object {
+                name: l.to_string(),
+                object_type: "Location".to_string(),
+            })
+            .collect(),
+        classes: vec![robot, mission],
+        timelines: vec![
+            robot_instance("robot1", "at_field"),
+            robot_instance("robot2", "at_shed"),
+            TimelineInstance {
+                name: "mission".to_string(),
+                class: "Mission".to_string(),
+                static_tokens: vec![
+                    static_token("waiting", TokenTime::Fact(Some(0), None)),
+                    static_token("complete", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+        ],
+    }
+}
+
+#[test]
+pub fn ground_timeline_classes() {
+    let problem = ground(&robots_problem()).unwrap();
+    let names = problem
+        .timelines
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["robot1", "robot2", "mission"]);
+
+    // 3 locations and 9 movements.
+    let robot1 = &problem.timelines[0];
+    assert_eq!(robot1.token_types.len(), 12);
+    let at_shed = robot1
+        .token_types
+        .iter()
+        .find(|t| t.value == "at_shed")
+        .unwrap();
+    assert_eq!(at_shed.conditions.len(), 1);
+    assert_eq!(at_shed.conditions[0].len(), 3);
+    assert!(at_shed.conditions[0]
+        .iter()
+        .all(|c| c.timeline_ref == "robot1" && c.value.ends_with("_shed")));
+
+    let complete = &problem.timelines[2].token_types[1];
+    let covers = complete
+        .conditions
+        .iter()
+        .skip(1)
+        .map(|c| (c.len(), c[0].timeline_ref.as_str(), c[0].value.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        covers,
+        vec![(1, "robot1", "at_home"), (1, "robot2", "at_home")]
+    );
+}
+
+#[test]
+pub fn solve_grounded_problem() {
+    let problem = ground(&robots_problem()).unwrap();
+    let solution = solve(&problem, &Default::default()).unwrap();
+    for tl in solution.timelines.iter().take(2) {
+        assert!(tl.tokens.iter().any(|t| t.value == "at_home"));
+    }
+    assert!(solution.timelines[2]
+        .tokens
+        .iter()
+        .any(|t| t.value == "complete"));
+}
+
+#[test]
+pub fn ground_type_mismatch() {
+    let mut lifted = robots_problem();
+    lifted.timelines[0].arguments = vec!["robot2".to_string()];
+    let error = ground(&lifted).unwrap_err();
+    assert!(matches!(error, GroundingError::TypeMismatch { .. }));
+    assert_eq!(error.to_string(), "Object robot2 is not of type Location");
+}
+
+#[test]
+pub fn ground_static_token_conditions() {
+    let mut lifted = robots_problem();
+    lifted.timelines[0].static_tokens[0].conditions = vec![vec![Condition {
+        temporal_relationship: TemporalRelationship::StartsAfter,
+        amount: 0,
+        timeline_ref: "{self}".to_string(),
+        value: "at_{home}".to_string(),
+    }]];
+    let problem = ground(&lifted).unwrap();
+    let cond = &problem.timelines[0].static_tokens[0].conditions[0][0];
+    assert_eq!(cond.timeline_ref, "robot1");
+    assert_eq!(cond.value, "at_home");
+}
+
+#[test]
+pub fn ground_empty_quantifier() {
+    // No timelines of the charger class, so a robot can never charge.
+    let mut lifted = robots_problem();
+    lifted.classes.push(TimelineClass {
+        name: "Charger".to_string(),
+        ..Default::default()
+    });
+    lifted.classes[0].token_types.push(LiftedTokenType {
+        value: "charging".to_string(),
+        duration_limits: (1, None),
+        conditions: vec![vec![LiftedCondition {
+            quantifier: quantifier(QuantifierKind::Any, "c", "Charger"),
+            timeline_ref: "{c}".to_string(),
+            temporal_relationship: TemporalRelationship::Cover,
+            value: "available".to_string(),
+            amount: 0,
+        }]],
+        ..Default::default()
+    });
+    assert!(matches!(
+        ground(&lifted),
+        Err(GroundingError::EmptyQuantifier { .. })
+    ));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{cond, static_token, token};
+use paraspace::{problem::*, transitionsolver::solve};
+
+// A timeline that goes from idle through a task to done.
+fn task_timeline(name: &str, task: &str, extra: Vec<Vec<Condition>>) -> Timeline {
+    let mut task_conditions = vec![vec![cond(name, TemporalRelationship::MetBy, "idle")]];
+    task_conditions.extend(extra);
+    Timeline {
+        name: name.to_string(),
+        token_types: vec![
+            TokenType {
+                value: "idle".to_string(),
+                duration_limits: (1, None),
+                ..Default::default()
+            },
+            TokenType {
+                value: task.to_string(),
+                conditions: task_conditions,
+                duration_limits: (5, Some(5)),
+                ..Default::default()
+            },
+            TokenType {
+                value: "done".to_string(),
+                conditions: vec![vec![cond(name, TemporalRelationship::MetBy, task)]],
+                duration_limits: (1, None),
+                ..Default::default()
+            },
+        ],
+        static_tokens: vec![
+            static_token("idle", TokenTime::Fact(Some(0), None)),
+            static_token("done", TokenTime::Goal),
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+pub fn not_overlapping() {
+    let problem = Problem {
+        timelines: vec![
+            task_timeline(
+                "robot",
+                "work",
+                vec![vec![cond(
+                    "camera",
+                    TemporalRelationship::NotOverlapping,
+                    "imaging",
+                )]],
+            ),
+            task_timeline("camera", "imaging", vec![]),
+        ],
+    };
+
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let work = token(&solution, "robot", "work");
+    let imaging = token(&solution, "camera", "imaging");
+    assert!(
+        work.end_time <= imaging.start_time + 1e-5 || imaging.end_time <= work.start_time + 1e-5
+    );
+}
+
+// The environment is busy from time 0 to 10. Work must not lie within the busy period.
+fn busy_environment() -> Timeline {
+    Timeline {
+        name: "environment".to_string(),
+        token_types: vec![],
+        static_tokens: vec![static_token("busy", TokenTime::Fact(Some(0), Some(10)))],
+        ..Default::default()
+    }
+}
+
+#[test]
+pub fn not_during() {
+    let problem = Problem {
+        timelines: vec![
+            task_timeline(
+                "robot",
+                "work",
+                vec![vec![cond(
+                    "environment",
+                    TemporalRelationship::NotDuring,
+                    "busy",
+                )]],
+            ),
+            busy_environment(),
+        ],
+    };
+
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let work = token(&solution, "robot", "work");
+    assert!(work.end_time > 10.0);
+}
+
+#[test]
+pub fn not_overlapping_fact() {
+    let problem = Problem {
+        timelines: vec![
+            task_timeline(
+                "robot",
+                "work",
+                vec![vec![cond(
+                    "environment",
+                    TemporalRelationship::NotOverlapping,
+                    "busy",
+                )]],
+            ),
+            busy_environment(),
+        ],
+    };
+
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let work = token(&solution, "robot", "work");
+    assert!(work.start_time >= 10.0 - 1e-5);
+}
@@ -0,0 +1,227 @@
+mod common;
+use common::{cond, static_token, transition_from_on};
+use paraspace::{
+    problem::*,
+    transitionsolver::{solve, Objective, SolverSettings},
+    SolverError,
+};
+
+// A satellite needs at least 20 units of battery charge to start transmitting,
+// and transmitting drains the battery. The solar panel charges the battery.
+fn satellite_problem(initial_charge: f32) -> Problem {
+    Problem {
+        timelines: vec![
+            Timeline {
+                name: "comms".to_string(),
+                token_types: vec![
+                    TokenType {
+                        value: "idle".to_string(),
+                        duration_limits: (1, None),
+                        ..Default::default()
+                    },
+                    TokenType {
+                        value: "transmitting".to_string(),
+                        conditions: vec![transition_from_on("comms", &["idle"])],
+                        duration_limits: (10, Some(10)),
+                        numeric_rates: vec![NumericRate {
+                            timeline_ref: "battery".to_string(),
+                            rate: -2.5,
+                        }],
+                        numeric_conditions: vec![NumericCondition {
+                            timeline_ref: "battery".to_string(),
+                            time: TokenPoint::Start,
+                            relation: NumericRelation::GreaterOrEqual,
+                            value: 20.0,
+                        }],
+                        ..Default::default()
+                    },
+                    TokenType {
+                        value: "done".to_string(),
+                        conditions: vec![transition_from_on("comms", &["transmitting"])],
+                        duration_limits: (1, None),
+                        ..Default::default()
+                    },
+                ],
+                static_tokens: vec![
+                    static_token("idle", TokenTime::Fact(Some(0), None)),
+                    static_token("done", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+            Timeline {
+                name: "panel".to_string(),
+                token_types: vec![
+                    TokenType {
+                        value: "stowed".to_string(),
+                        duration_limits: (1, None),
+                        ..Default::default()
+                    },
+                    TokenType {
+                        value: "charging".to_string(),
+                        conditions: vec![transition_from_on("panel", &["stowed"])],
+                        duration_limits: (1, None),
+                        numeric_rates: vec![NumericRate {
+                            timeline_ref: "battery".to_string(),
+                            rate: 2.0,
+                        }],
+                        ..Default::default()
+                    },
+                ],
+                static_tokens: vec![static_token("stowed", TokenTime::Fact(Some(0), None))],
+                ..Default::default()
+            },
+            Timeline {
+                name: "battery".to_string(),
+                token_types: vec![],
+                static_tokens: vec![],
+                kind: TimelineKind::Numeric(NumericVariable {
+                    initial: initial_charge,
+                    min: Some(0.0),
+                    max: Some(100.0),
+                }),
+            },
+        ],
+    }
+}
+
+fn battery_level(solution: &Solution, initial: f32, rates: &[(&str, f32)], time: f32) -> f32 {
+    let mut level = initial;
+    for tl in solution.timelines.iter() {
+        for token in tl.tokens.iter() {
+            if let Some((_, rate)) = rates.iter().find(|(v, _)| *v == token.value) {
+                let elapsed = (time.min(token.end_time) - token.start_time).max(0.0);
+                level += rate * elapsed;
+            }
+        }
+    }
+    level
+}
+
+#[test]
+pub fn numeric_condition_without_charging() {
+    let solution = solve(&satellite_problem(30.0), &Default::default()).unwrap();
+    let transmitting = solution.timelines[0]
+        .tokens
+        .iter()
+        .find(|t| t.value == "transmitting")
+        .unwrap();
+    let rates = [("transmitting", -2.5), ("charging", 2.0)];
+    assert!(battery_level(&solution, 30.0, &rates, transmitting.start_time) >= 20.0 - 1e-3);
+    assert!(battery_level(&solution, 30.0, &rates, transmitting.end_time) >= -1e-3);
+}
+
+#[test]
+pub fn numeric_condition_requires_charging() {
+    let solution = solve(&satellite_problem(10.0), &Default::default()).unwrap();
+    let transmitting = solution.timelines[0]
+        .tokens
+        .iter()
+        .find(|t| t.value == "transmitting")
+        .unwrap();
+    assert!(solution.timelines[1]
+        .tokens
+        .iter()
+        .any(|t| t.value == "charging"));
+
+    let rates = [("transmitting", -2.5), ("charging", 2.0)];
+    assert!(battery_level(&solution, 10.0, &rates, transmitting.start_time) >= 20.0 - 1e-3);
+    assert!(battery_level(&solution, 10.0, &rates, transmitting.end_time) >= -1e-3);
+}
+
+// The tank drains while pumping and is refilled when pumping ends, which is when
+// the valve opens at time 11. It would go below the minimum just before the refill
+// if pumping started too early, which is what the negative cost of pumping rewards.
+#[test]
+pub fn bounds_before_effects() {
+    let problem = Problem {
+        timelines: vec![
+            Timeline {
+                name: "pump".to_string(),
+                token_types: vec![
+                    TokenType {
+                        value: "idle".to_string(),
+                        duration_limits: (1, None),
+                        ..Default::default()
+                    },
+                    TokenType {
+                        value: "pumping".to_string(),
+                        cost: Cost {
+                            fixed: 0.0,
+                            per_time_unit: -1.0,
+                        },
+                        duration_limits: (1, Some(10)),
+                        numeric_rates: vec![NumericRate {
+                            timeline_ref: "tank".to_string(),
+                            rate: -2.0,
+                        }],
+                        resource_effects: vec![ResourceEffect {
+                            timeline_ref: "tank".to_string(),
+                            time: TokenPoint::End,
+                            amount: 15,
+                        }],
+                        conditions: vec![
+                            transition_from_on("pump", &["idle"]),
+                            vec![cond("valve", TemporalRelationship::Meets, "open")],
+                        ],
+                        ..Default::default()
+                    },
+                    TokenType {
+                        value: "done".to_string(),
+                        conditions: vec![transition_from_on("pump", &["pumping"])],
+                        duration_limits: (1, None),
+                        ..Default::default()
+                    },
+                ],
+                static_tokens: vec![
+                    static_token("idle", TokenTime::Fact(Some(0), None)),
+                    static_token("done", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+            Timeline {
+                name: "valve".to_string(),
+                static_tokens: vec![static_token("open", TokenTime::Fact(Some(11), Some(12)))],
+                ..Default::default()
+            },
+            Timeline {
+                name: "tank".to_string(),
+                token_types: vec![],
+                static_tokens: vec![],
+                kind: TimelineKind::Numeric(NumericVariable {
+                    initial: 10.0,
+                    min: Some(0.0),
+                    max: None,
+                }),
+            },
+        ],
+    };
+
+    let settings = SolverSettings {
+        optimize: Some(Objective::Cost),
+        ..Default::default()
+    };
+    let solution = solve(&problem, &settings).unwrap();
+    let pumping = solution.timelines[0]
+        .tokens
+        .iter()
+        .find(|t| t.value == "pumping")
+        .unwrap();
+    assert!((pumping.end_time - pumping.start_time - 5.0).abs() < 1e-3);
+}
+
+#[test]
+pub fn level_users_on_unknown_or_symbolic_timeline() {
+    let mut problem = satellite_problem(30.0);
+    problem.timelines[0].token_types[1].numeric_conditions[0].timeline_ref = "battery2".to_string();
+    assert!(matches!(
+        solve(&problem, &Default::default()),
+        Err(SolverError::InvalidProblem(_))
+    ));
+
+    let mut problem = satellite_problem(30.0);
+    problem.timelines[0].token_types[1].numeric_rates[0].timeline_ref = "panel".to_string();
+    assert!(matches!(
+        solve(&problem, &Default::default()),
+        Err(SolverError::InvalidProblem(_))
+    ));
+}
@@ -0,0 +1,154 @@
+mod common;
+use common::token_mut;
+use paraspace::{plancheck::check_plan, problem::*, transitionsolver};
+
+fn condition(
+    timeline: &str,
+    relation: TemporalRelationship,
+    value: &str,
+    amount: u32,
+) -> Condition {
+    Condition {
+        temporal_relationship: relation,
+        amount,
+        timeline_ref: timeline.to_string(),
+        value: value.to_string(),
+    }
+}
+
+// Two jobs that both need the single machine for 5 time units.
+fn machine_problem() -> Problem {
+    let job = |name: &str| Timeline {
+        name: name.to_string(),
+        token_types: vec![
+            TokenType {
+                value: "Waiting".to_string(),
+                duration_limits: (0, None),
+                ..Default::default()
+            },
+            TokenType {
+                value: "Running".to_string(),
+                duration_limits: (5, Some(5)),
+                conditions: vec![
+                    vec![condition(name, TemporalRelationship::MetBy, "Waiting", 0)],
+                    vec![condition(
+                        "machine",
+                        TemporalRelationship::Cover,
+                        "Available",
+                        1,
+                    )],
+                ],
+                ..Default::default()
+            },
+            TokenType {
+                value: "Done".to_string(),
+                duration_limits: (1, None),
+                conditions: vec![vec![condition(
+                    name,
+                    TemporalRelationship::MetBy,
+                    "Running",
+                    0,
+                )]],
+                ..Default::default()
+            },
+        ],
+        static_tokens: vec![
+            Token {
+                value: "Waiting".to_string(),
+                const_time: TokenTime::Fact(Some(0), None),
+                capacity: 0,
+                conditions: vec![],
+            },
+            Token {
+                value: "Done".to_string(),
+                const_time: TokenTime::Goal,
+                capacity: 0,
+                conditions: vec![],
+            },
+        ],
+        ..Default::default()
+    };
+
+    Problem {
+        timelines: vec![
+            job("job1"),
+            job("job2"),
+            Timeline {
+                name: "machine".to_string(),
+                token_types: vec![],
+                static_tokens: vec![Token {
+                    value: "Available".to_string(),
+                    const_time: TokenTime::Fact(Some(0), None),
+                    capacity: 1,
+                    conditions: vec![],
+                }],
+                ..Default::default()
+            },
+        ],
+    }
+}
+
+#[test]
+pub fn valid_plan() {
+    let problem = machine_problem();
+    let solution = transitionsolver::solve(&problem, &Default::default()).unwrap();
+    assert_eq!(check_plan(&problem, &solution), Ok(()));
+}
+
+#[test]
+pub fn invalid_plans() {
+    let problem = machine_problem();
+
+    // Both jobs run at the same time on the machine.
+    let mut solution = transitionsolver::solve(&problem, &Default::default()).unwrap();
+    let running1 = token_mut(&mut solution, "job1", "Running");
+    let (start, end) = (running1.start_time, running1.end_time);
+    for job in ["job1", "job2"] {
+        let tokens = &mut solution
+            .timelines
+            .iter_mut()
+            .find(|tl| tl.name == job)
+            .unwrap()
+            .tokens;
+        tokens[0].end_time = start;
+        tokens[1].start_time = start;
+        tokens[1].end_time = end;
+        tokens[2].start_time = end;
+    }
+    let errors = check_plan(&problem, &solution).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("above its capacity 1"));
+
+    // A token is too short, and the timeline has a gap.
+    let mut solution = transitionsolver::solve(&problem, &Default::default()).unwrap();
+    token_mut(&mut solution, "job2", "Running").end_time -= 1.0;
+    let errors = check_plan(&problem, &solution).unwrap_err();
+    assert!(errors.iter().any(|e| e.contains("outside the limits")));
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("is not followed immediately")));
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("does not satisfy its condition on MetBy")));
+
+    // The goal is missing.
+    let mut solution = transitionsolver::solve(&problem, &Default::default()).unwrap();
+    solution.timelines[0].tokens.pop();
+    let errors = check_plan(&problem, &solution).unwrap_err();
+    assert_eq!(
+        errors,
+        vec!["timeline \"job1\" does not end in its goal \"Done\"".to_string()]
+    );
+}
+
+#[test]
+pub fn unreachable_goal() {
+    let mut problem = machine_problem();
+    // Done can only follow itself on job2.
+    problem.timelines[1].token_types[2].conditions[0][0].value = "Done".to_string();
+    let errors = problem.validate().unwrap_err();
+    assert_eq!(
+        errors,
+        vec!["goal \"Done\" on timeline \"job2\" cannot be reached from its facts".to_string()]
+    );
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::two_routes_problem;
+use paraspace::{
+    plancheck::check_plan,
+    portfolio::{diversify, solve_portfolio},
+    transitionsolver::{solve_with_cancel, Objective, SolverSettings},
+    SolverError,
+};
+use std::sync::atomic::AtomicBool;
+
+#[test]
+pub fn portfolio_finds_plan() {
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        ..Default::default()
+    };
+    let portfolio = diversify(&settings, 8);
+    assert_eq!(portfolio.len(), 8);
+    assert!(portfolio[0].minimize_cores != portfolio[1].minimize_cores);
+    assert!(portfolio[0].expansion_core_size != portfolio[2].expansion_core_size);
+    assert!(portfolio[0].random_seed != portfolio[4].random_seed);
+
+    // The route through C is faster.
+    let problem = two_routes_problem();
+    let solution = solve_portfolio(&problem, &portfolio).unwrap();
+    assert_eq!(check_plan(&problem, &solution), Ok(()));
+    let values = solution.timelines[0]
+        .tokens
+        .iter()
+        .map(|t| t.value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec!["A", "moveAC", "C", "moveCB", "B"]);
+}
+
+#[test]
+pub fn cancelled_before_start() {
+    let cancel = AtomicBool::new(true);
+    assert!(matches!(
+        solve_with_cancel(&two_routes_problem(), &Default::default(), &cancel),
+        Err(SolverError::Cancelled)
+    ));
+}
+
+#[test]
+pub fn portfolio_ignores_failed_configuration() {
+    // The missing solver fails at once, before the other configuration finds a plan.
+    let broken = SolverSettings {
+        smt_solver_command: Some("paraspace-no-such-solver".to_string()),
+        ..Default::default()
+    };
+    let portfolio = vec![broken.clone(), Default::default()];
+    assert!(solve_portfolio(&two_routes_problem(), &portfolio).is_ok());
+
+    assert!(matches!(
+        solve_portfolio(&two_routes_problem(), &[broken.clone(), broken]),
+        Err(SolverError::SolverProcess(_))
+    ));
+}
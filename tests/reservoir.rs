@@ -0,0 +1,184 @@
+mod common;
+use common::{static_token, transition_from, transition_from_on};
+use paraspace::{problem::*, transitionsolver::solve, SolverError};
+
+fn battery_effect(time: TokenPoint, amount: i32) -> ResourceEffect {
+    ResourceEffect {
+        timeline_ref: "battery".to_string(),
+        time,
+        amount,
+    }
+}
+
+// The robot drives from a to b and from b to c, and each drive uses
+// 6 units from a battery that holds 10 units.
+fn rover_problem(can_charge: bool) -> Problem {
+    let mut token_types = vec![
+        TokenType {
+            value: "at_a".to_string(),
+            duration_limits: (1, None),
+            ..Default::default()
+        },
+        TokenType {
+            value: "drive_ab".to_string(),
+            conditions: vec![transition_from(&["at_a"])],
+            duration_limits: (5, Some(5)),
+            resource_effects: vec![battery_effect(TokenPoint::Start, -6)],
+            ..Default::default()
+        },
+        TokenType {
+            value: "at_b".to_string(),
+            conditions: vec![transition_from(if can_charge {
+                &["drive_ab", "charge"]
+            } else {
+                &["drive_ab"]
+            })],
+            duration_limits: (1, None),
+            ..Default::default()
+        },
+        TokenType {
+            value: "drive_bc".to_string(),
+            conditions: vec![transition_from(&["at_b"])],
+            duration_limits: (5, Some(5)),
+            resource_effects: vec![battery_effect(TokenPoint::Start, -6)],
+            ..Default::default()
+        },
+        TokenType {
+            value: "at_c".to_string(),
+            conditions: vec![transition_from(&["drive_bc"])],
+            duration_limits: (1, None),
+            ..Default::default()
+        },
+    ];
+
+    if can_charge {
+        token_types.push(TokenType {
+            value: "charge".to_string(),
+            conditions: vec![transition_from(&["at_b"])],
+            duration_limits: (3, Some(3)),
+            resource_effects: vec![battery_effect(TokenPoint::End, 5)],
+            ..Default::default()
+        });
+    }
+
+    Problem {
+        timelines: vec![
+            Timeline {
+                name: "robot".to_string(),
+                token_types,
+                static_tokens: vec![
+                    static_token("at_a", TokenTime::Fact(Some(0), None)),
+                    static_token("at_c", TokenTime::Goal),
+                ],
+                ..Default::default()
+            },
+            Timeline {
+                name: "battery".to_string(),
+                token_types: vec![],
+                static_tokens: vec![],
+                kind: TimelineKind::Reservoir(Reservoir {
+                    initial: 10,
+                    min: 0,
+                    max: 10,
+                }),
+            },
+        ],
+    }
+}
+
+#[test]
+pub fn reservoir_requires_charging() {
+    let solution = solve(&rover_problem(true), &Default::default()).unwrap();
+    let robot = &solution.timelines[0];
+    let values = robot
+        .tokens
+        .iter()
+        .map(|t| t.value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec!["at_a", "drive_ab", "at_b", "charge", "at_b", "drive_bc", "at_c"]
+    );
+}
+
+// Two rovers borrow 6 units each from the battery while driving,
+// so they cannot drive at the same time.
+#[test]
+pub fn reservoir_borrow_and_return() {
+    let rover = |name: &str| Timeline {
+        name: name.to_string(),
+        token_types: vec![
+            TokenType {
+                value: "at_a".to_string(),
+                duration_limits: (1, None),
+                ..Default::default()
+            },
+            TokenType {
+                value: "drive".to_string(),
+                conditions: vec![transition_from_on(name, &["at_a"])],
+                duration_limits: (5, Some(5)),
+                resource_effects: vec![
+                    battery_effect(TokenPoint::Start, -6),
+                    battery_effect(TokenPoint::End, 6),
+                ],
+                ..Default::default()
+            },
+            TokenType {
+                value: "at_b".to_string(),
+                conditions: vec![transition_from_on(name, &["drive"])],
+                duration_limits: (1, None),
+                ..Default::default()
+            },
+        ],
+        static_tokens: vec![
+            static_token("at_a", TokenTime::Fact(Some(0), None)),
+            static_token("at_b", TokenTime::Goal),
+        ],
+        ..Default::default()
+    };
+
+    let problem = Problem {
+        timelines: vec![
+            rover("rover1"),
+            rover("rover2"),
+            Timeline {
+                name: "battery".to_string(),
+                token_types: vec![],
+                static_tokens: vec![],
+                kind: TimelineKind::Reservoir(Reservoir {
+                    initial: 10,
+                    min: 0,
+                    max: 10,
+                }),
+            },
+        ],
+    };
+
+    let solution = solve(&problem, &Default::default()).unwrap();
+    let drive = |tl: usize| {
+        solution.timelines[tl]
+            .tokens
+            .iter()
+            .find(|t| t.value == "drive")
+            .unwrap()
+    };
+    let (d1, d2) = (drive(0), drive(1));
+    assert!(d1.end_time <= d2.start_time + 1e-5 || d2.end_time <= d1.start_time + 1e-5);
+}
+
+#[test]
+pub fn effect_on_unknown_or_symbolic_timeline() {
+    let mut problem = rover_problem(false);
+    problem.timelines[1].name = "battery2".to_string();
+    assert!(matches!(
+        solve(&problem, &Default::default()),
+        Err(SolverError::InvalidProblem(_))
+    ));
+
+    let mut problem = rover_problem(false);
+    problem.timelines[1].kind = TimelineKind::Symbolic;
+    assert!(matches!(
+        solve(&problem, &Default::default()),
+        Err(SolverError::InvalidProblem(_))
+    ));
+}
@@ -0,0 +1,206 @@
+use paraspace::{generators, server::Server};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+fn start_server() -> SocketAddr {
+    start_server_keeping(100)
+}
+
+fn start_server_keeping(keep_finished: usize) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(None, 4, keep_finished);
+    std::thread::spawn(move || server.serve_tcp(listener));
+    addr
+}
+
+// Send an HTTP request, and return the status code and the JSON body.
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (code, serde_json::from_str(body).unwrap())
+}
+
+fn wait_for_job(addr: SocketAddr, id: &Value) -> Value {
+    loop {
+        let (code, status) = request(addr, "GET", &format!("/jobs/{}", id), "");
+        assert_eq!(code, 200);
+        if status["status"] != "Running" {
+            return status;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+#[test]
+pub fn solve_and_validate() {
+    let addr = start_server();
+    let problem = serde_json::to_value(generators::goac(1, 1)).unwrap();
+
+    let (code, response) = request(
+        addr,
+        "POST",
+        "/solve",
+        &json!({ "problem": problem }).to_string(),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "Solved");
+    assert_eq!(response["solution"]["end_of_time"], 4000.0);
+
+    let (code, response) = request(addr, "POST", "/validate", &problem.to_string());
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "valid": true, "errors": [] }));
+
+    let mut invalid = problem.clone();
+    invalid["timelines"][0]["static_tokens"][0]["const_time"] = json!("Goal");
+    invalid["timelines"][0]["static_tokens"][0]["value"] = json!("Nowhere");
+    let (_, response) = request(addr, "POST", "/validate", &invalid.to_string());
+    assert_eq!(response["valid"], false);
+
+    let (code, response) = request(addr, "POST", "/solve", "{");
+    assert_eq!(code, 400);
+    assert_eq!(response["kind"], "InvalidJson");
+
+    let (code, _) = request(addr, "GET", "/plans", "");
+    assert_eq!(code, 404);
+}
+
+#[test]
+pub fn jobs() {
+    let addr = start_server();
+    let problem = generators::ceramic(2, 4);
+    let request_body = json!({ "problem": problem }).to_string();
+    let (code, started) = request(addr, "POST", "/jobs", &request_body);
+    assert_eq!(code, 202);
+    let id = &started["id"];
+    let status = wait_for_job(addr, id);
+    assert_eq!(status["status"], "Solved");
+    assert_eq!(status["cancelled"], false);
+    assert_eq!(status["response"]["status"], "Solved");
+
+    // The list of jobs has the status without the response.
+    let (_, list) = request(addr, "GET", "/jobs", "");
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["id"], *id);
+    assert!(list[0].get("response").is_none());
+
+    // A cancelled job stops before its timeout.
+    let optimize = json!({
+        "problem": problem,
+        "settings": { "optimize": "Makespan", "timeout": 60.0 },
+    })
+    .to_string();
+    let (_, started) = request(addr, "POST", "/jobs", &optimize);
+    let other = &started["id"];
+    let (code, _) = request(addr, "POST", &format!("/jobs/{}/cancel", other), "");
+    assert_eq!(code, 200);
+    let status = wait_for_job(addr, other);
+    assert_eq!(status["cancelled"], true);
+    assert!(status["elapsed"].as_f64().unwrap() < 60.0);
+
+    let (code, _) = request(addr, "DELETE", &format!("/jobs/{}", id), "");
+    assert_eq!(code, 200);
+    let (code, error) = request(addr, "GET", &format!("/jobs/{}", id), "");
+    assert_eq!(code, 404);
+    assert_eq!(error["kind"], "UnknownJob");
+}
+
+#[test]
+pub fn large_body() {
+    let addr = start_server();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST /solve HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+        paraspace::server::MAX_BODY_SIZE + 1
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 413 "));
+    let error = serde_json::from_str::<Value>(body).unwrap();
+    assert_eq!(error["kind"], "PayloadTooLarge");
+}
+
+#[test]
+pub fn large_headers() {
+    let addr = start_server();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let request_line = "GET /jobs HTTP/1.1\r\nX-Padding: ";
+    let padding = "a".repeat(paraspace::server::MAX_HEADER_SIZE - request_line.len());
+    write!(stream, "{}{}", request_line, padding).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 431 "));
+    let error = serde_json::from_str::<Value>(body).unwrap();
+    assert_eq!(error["kind"], "HeaderTooLarge");
+}
+
+#[test]
+pub fn finished_jobs_are_removed() {
+    let addr = start_server_keeping(2);
+    let request_body = json!({ "problem": generators::goac(1, 1) }).to_string();
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let (_, started) = request(addr, "POST", "/jobs", &request_body);
+        wait_for_job(addr, &started["id"]);
+        ids.push(started["id"].clone());
+    }
+
+    // Only the last two finished jobs are kept.
+    let (_, list) = request(addr, "GET", "/jobs", "");
+    let listed = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|job| job["id"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(listed, ids[1..]);
+    let (code, _) = request(addr, "GET", &format!("/jobs/{}", ids[0]), "");
+    assert_eq!(code, 404);
+
+    // A solve request is answered even though it finishes after the other jobs.
+    let (code, response) = request(addr, "POST", "/solve", &request_body);
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "Solved");
+}
+
+#[test]
+pub fn delete_while_solving() {
+    let addr = start_server();
+    let optimize = json!({
+        "problem": generators::ceramic(2, 4),
+        "settings": { "optimize": "Makespan", "timeout": 60.0 },
+    })
+    .to_string();
+    let solving = std::thread::spawn(move || request(addr, "POST", "/solve", &optimize));
+
+    let id = loop {
+        let (_, list) = request(addr, "GET", "/jobs", "");
+        if let Some(job) = list.as_array().unwrap().first() {
+            break job["id"].clone();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    let (code, _) = request(addr, "DELETE", &format!("/jobs/{}", id), "");
+    assert_eq!(code, 200);
+
+    let (code, error) = solving.join().unwrap();
+    assert_eq!(code, 404);
+    assert_eq!(error["kind"], "UnknownJob");
+}
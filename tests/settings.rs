@@ -0,0 +1,150 @@
+mod common;
+use common::{cond, cond_with_amount, static_token, static_token_with_capacity};
+use paraspace::{
+    problem::*,
+    transitionsolver::{solve, Objective, ResourceEncoding, SolverSettings},
+};
+
+// Two jobs that each cook for 5 time units on the same stove.
+fn stove_problem(stove_capacity: u32) -> Problem {
+    let job = |name: &str| Timeline {
+        name: name.to_string(),
+        token_types: vec![
+            TokenType {
+                value: "Wait".to_string(),
+                duration_limits: (0, None),
+                ..Default::default()
+            },
+            TokenType {
+                value: "Cook".to_string(),
+                duration_limits: (5, Some(5)),
+                conditions: vec![
+                    vec![cond(name, TemporalRelationship::MetBy, "Wait")],
+                    vec![cond_with_amount(
+                        "stove",
+                        TemporalRelationship::Cover,
+                        "Available",
+                        1,
+                    )],
+                ],
+                ..Default::default()
+            },
+            TokenType {
+                value: "Done".to_string(),
+                duration_limits: (1, None),
+                conditions: vec![vec![cond(name, TemporalRelationship::MetBy, "Cook")]],
+                ..Default::default()
+            },
+        ],
+        static_tokens: vec![
+            static_token("Wait", TokenTime::Fact(Some(0), None)),
+            static_token("Done", TokenTime::Goal),
+        ],
+        ..Default::default()
+    };
+
+    Problem {
+        timelines: vec![
+            job("job1"),
+            job("job2"),
+            Timeline {
+                name: "stove".to_string(),
+                static_tokens: vec![static_token_with_capacity(
+                    "Available",
+                    TokenTime::Fact(Some(0), None),
+                    stove_capacity,
+                )],
+                ..Default::default()
+            },
+        ],
+    }
+}
+
+fn cook_times(solution: &Solution) -> Vec<(f32, f32)> {
+    solution
+        .timelines
+        .iter()
+        .flat_map(|tl| tl.tokens.iter())
+        .filter(|t| t.value == "Cook")
+        .map(|t| (t.start_time, t.end_time))
+        .collect()
+}
+
+#[test]
+pub fn resource_encodings() {
+    for encoding in [ResourceEncoding::Pairwise, ResourceEncoding::PseudoBoolean] {
+        let settings = SolverSettings {
+            resource_encoding: encoding,
+            ..Default::default()
+        };
+
+        let solution = solve(&stove_problem(1), &settings).unwrap();
+        let cooks = cook_times(&solution);
+        assert_eq!(cooks.len(), 2);
+        assert!(cooks[0].1 <= cooks[1].0 + 1e-5 || cooks[1].1 <= cooks[0].0 + 1e-5);
+
+        // With room for both, they can cook at the same time.
+        let settings = SolverSettings {
+            optimize: Some(Objective::Makespan),
+            ..settings
+        };
+        let solution = solve(&stove_problem(2), &settings).unwrap();
+        assert!(solution.end_of_time < 10.0);
+    }
+}
+
+#[test]
+pub fn weighted_objective_without_costs() {
+    let settings = SolverSettings {
+        optimize: Some(Objective::Weighted {
+            cost: 1.0,
+            makespan: 2.0,
+        }),
+        ..Default::default()
+    };
+    let solution = solve(&stove_problem(1), &settings).unwrap();
+    assert_eq!(solution.total_cost, 0.0);
+    assert!(solution.end_of_time >= 10.0 - 1e-5);
+}
+
+#[test]
+pub fn timeout_returns_best_plan() {
+    // Without a required improvement, the optimization never ends by itself.
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        min_improvement: 0.0,
+        optimization_patience: usize::MAX,
+        timeout: Some(0.5),
+        ..Default::default()
+    };
+    let start = std::time::Instant::now();
+    let solution = solve(&stove_problem(1), &settings).unwrap();
+    assert!(start.elapsed().as_secs_f64() < 10.0);
+    assert_eq!(cook_times(&solution).len(), 2);
+}
+
+#[test]
+pub fn settings_from_json_and_strings() {
+    let settings: SolverSettings =
+        serde_json::from_str(r#"{"minimize_cores": true, "optimize": "Cost", "timeout": 2.5}"#)
+            .unwrap();
+    assert!(settings.minimize_cores);
+    assert!(matches!(settings.optimize, Some(Objective::Cost)));
+    assert_eq!(settings.timeout, Some(2.5));
+    assert_eq!(settings.optimization_patience, 25);
+    assert_eq!(settings.resource_encoding, ResourceEncoding::Pairwise);
+
+    assert!(matches!(
+        "makespan".parse::<Objective>(),
+        Ok(Objective::Makespan)
+    ));
+    assert!(matches!(
+        "weighted:1,0.5".parse::<Objective>(),
+        Ok(Objective::Weighted { cost, makespan }) if cost == 1.0 && makespan == 0.5
+    ));
+    assert!("fastest".parse::<Objective>().is_err());
+    assert_eq!(
+        "pseudo-boolean".parse::<ResourceEncoding>(),
+        Ok(ResourceEncoding::PseudoBoolean)
+    );
+}
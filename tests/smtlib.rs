@@ -0,0 +1,113 @@
+mod common;
+
+use common::two_routes_problem;
+use paraspace::{
+    transitionsolver::{solve, Objective, SolverSettings},
+    SolverError,
+};
+
+#[test]
+#[ignore = "needs an SMT solver, such as PARASPACE_TEST_SMT_SOLVER=\"cvc5 --incremental\""]
+pub fn external_solver() {
+    let command =
+        std::env::var("PARASPACE_TEST_SMT_SOLVER").unwrap_or_else(|_| "z3 -in".to_string());
+    let settings = SolverSettings {
+        optimize: Some(Objective::Makespan),
+        smt_solver_command: Some(command),
+        ..Default::default()
+    };
+    let solution = solve(&two_routes_problem(), &settings).unwrap();
+    let last = solution.timelines[0].tokens.last().unwrap();
+    assert_eq!(last.value, "B");
+    assert!(last.start_time <= 10.0 + 1e-5);
+}
+
+#[test]
+pub fn missing_solver() {
+    let settings = SolverSettings {
+        smt_solver_command: Some("paraspace-no-such-solver -in".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        solve(&two_routes_problem(), &settings),
+        Err(SolverError::SolverProcess(_))
+    ));
+}
+
+// A shell script that stands in for an SMT solver. It answers the commands that
+// start with each prefix, and ignores the rest.
+#[cfg(unix)]
+fn fake_solver(name: &str, answers: &[(&str, &str)]) -> String {
+    let mut script = "while read -r line; do\n  case \"$line\" in\n".to_string();
+    for (prefix, answer) in answers {
+        script.push_str(&format!("    \"{}\"*) {} ;;\n", prefix, answer));
+    }
+    script.push_str("  esac\ndone\n");
+    let path = std::env::temp_dir().join(format!("paraspace_{}_{}.sh", name, std::process::id()));
+    std::fs::write(&path, script).unwrap();
+    format!("sh {}", path.display())
+}
+
+// The robot stays at A, so the solution has no boolean values to ask for.
+#[cfg(unix)]
+fn idle_problem() -> paraspace::problem::Problem {
+    let mut problem = two_routes_problem();
+    problem.timelines[0].static_tokens.pop();
+    problem
+}
+
+#[test]
+#[cfg(unix)]
+pub fn fake_solver_values() {
+    let command = fake_solver(
+        "values",
+        &[
+            ("(set-option :random-seed", "echo unsupported"),
+            ("(check-sat-assuming", "echo sat"),
+            ("(get-value", "printf '((x\\n  (/ (- 3.0) 2.0)))\\n'"),
+        ],
+    );
+    let settings = SolverSettings {
+        smt_solver_command: Some(command),
+        ..Default::default()
+    };
+    let solution = solve(&idle_problem(), &settings).unwrap();
+    assert_eq!(solution.timelines[0].tokens[0].start_time, -1.5);
+    assert_eq!(solution.end_of_time, -1.5);
+}
+
+#[test]
+#[cfg(unix)]
+pub fn fake_solver_error() {
+    let command = fake_solver(
+        "error",
+        &[(
+            "(check-sat-assuming",
+            "echo '(error \"line 9: unexpected ) in (check-sat-assuming\")'",
+        )],
+    );
+    let settings = SolverSettings {
+        smt_solver_command: Some(command),
+        ..Default::default()
+    };
+    match solve(&two_routes_problem(), &settings) {
+        Err(SolverError::SolverProcess(e)) => {
+            assert_eq!(e, "(error \"line 9: unexpected ) in (check-sat-assuming\")")
+        }
+        x => panic!("unexpected result {:?}", x),
+    }
+}
+
+#[test]
+#[cfg(unix)]
+pub fn fake_solver_exits() {
+    let command = fake_solver("exits", &[("(check-sat-assuming", "exit 1")]);
+    let settings = SolverSettings {
+        smt_solver_command: Some(command),
+        ..Default::default()
+    };
+    assert!(matches!(
+        solve(&two_routes_problem(), &settings),
+        Err(SolverError::SolverProcess(_))
+    ));
+}
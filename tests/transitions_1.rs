@@ -11,6 +11,7 @@ pub fn transitions_1() {
                     conditions: Vec::new(),
                     duration_limits: (5, Some(6)),
                     capacity: 0,
+                    ..Default::default()
                 },
                 TokenType {
                     value: "s2".to_string(),
@@ -22,6 +23,7 @@ pub fn transitions_1() {
                     }]],
                     duration_limits: (1, None),
                     capacity: 0,
+                    ..Default::default()
                 },
             ],
             static_tokens: vec![Token {
@@ -30,6 +32,7 @@ pub fn transitions_1() {
                 capacity: 0,
                 conditions: vec![],
             }],
+            ..Default::default()
         }],
     };
 
@@ -61,12 +64,14 @@ pub fn unrestricted_transitions() {
                     conditions: Vec::new(),
                     duration_limits: (5, Some(6)),
                     capacity: 0,
+                    ..Default::default()
                 },
                 TokenType {
                     value: "s2".to_string(),
                     conditions: vec![],
                     duration_limits: (1, None),
                     capacity: 0,
+                    ..Default::default()
                 },
             ],
             static_tokens: vec![Token {
@@ -80,6 +85,7 @@ pub fn unrestricted_transitions() {
                 capacity: 0,
                 conditions: vec![],
             }],
+            ..Default::default()
         }],
     };
 
@@ -11,6 +11,7 @@ pub fn transitions_2() {
                     conditions: Vec::new(),
                     duration_limits: (5, Some(6)),
                     capacity: 0,
+                    ..Default::default()
                 },
                 TokenType {
                     value: "s2".to_string(),
@@ -22,6 +23,7 @@ pub fn transitions_2() {
                     }]],
                     duration_limits: (1, None),
                     capacity: 0,
+                    ..Default::default()
                 },
                 TokenType {
                     value: "s3".to_string(),
@@ -33,6 +35,7 @@ pub fn transitions_2() {
                     }]],
                     duration_limits: (1, None),
                     capacity: 0,
+                    ..Default::default()
                 },
             ],
             static_tokens: vec![Token {
@@ -41,6 +44,7 @@ pub fn transitions_2() {
                 capacity: 0,
                 conditions: vec![],
             }],
+            ..Default::default()
         }],
     };
 